@@ -0,0 +1,29 @@
+export const dynamic = 'force-dynamic';
+export const runtime = 'edge';
+
+// The Rust backend that checks the quiz window and builds the access URL
+const BACKEND_URL = process.env.BACKEND_URL || "http://localhost:3001";
+
+export async function POST(req: Request) {
+  try {
+    const res = await fetch(`${BACKEND_URL}/api/quiz/start`, {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: await req.text(),
+    });
+
+    return new Response(await res.text(), {
+      status: res.status,
+      headers: {
+        "Content-Type": "application/json",
+        "Cache-Control": "no-store",
+      },
+    });
+  } catch (error) {
+    console.error("[quiz-start] backend unreachable:", error instanceof Error ? error.message : String(error));
+    return new Response(JSON.stringify({ error: "Quiz service unavailable" }), {
+      status: 502,
+      headers: { "Content-Type": "application/json", "Cache-Control": "no-store" },
+    });
+  }
+}
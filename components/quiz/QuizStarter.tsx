@@ -11,7 +11,6 @@ export function QuizStarter() {
 
   useEffect(() => {
     setAdmissionNumber(localStorage.getItem("admissionNumber") || "");
-    setPin(localStorage.getItem("studentPin") || "");
   }, []);
 
   useEffect(() => {
@@ -31,60 +30,41 @@ export function QuizStarter() {
     setError("");
 
     try {
-      const res = await fetch(
-        "https://faas-blr1-8177d592.doserverless.co/api/v1/web/fn-1c23ee6f-939a-44b2-9c4e-d17970ddd644/abes/fetchQuizDetails",
-        {
-          method: "POST",
-          headers: { "Content-Type": "application/json" },
-          body: JSON.stringify({
-            quiz_uc: code,
-            user_unique_code: admissionNumber,
-            pin: pin,
-          }),
-        }
-      );
+      const res = await fetch("/api/quiz/start", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({
+          quiz_code: code,
+          user_unique_code: admissionNumber,
+          pin: pin,
+        }),
+      });
 
       const data = await res.json();
-      const quizData = data?.response?.data;
-      
-      if (!quizData) throw new Error("Quiz data not found");
-      
-      if (
-        data?.msg === "Invalid Quiz ID" ||
-        !data?.response?.data ||
-        data.response.data.length === 0
-      ) {
+
+      if (res.status === 404) {
         throw new Error("Invalid Quiz Code. Please try again.");
       }
+      if (!res.ok) {
+        throw new Error(data?.error || "Something went wrong");
+      }
 
-      const now = new Date();
-      const quizStart = new Date(quizData.login_time);
-      const quizEnd = new Date(quizData.end_time);
-
-      if (now < quizStart) {
-        const secondsLeft = Math.floor((quizStart.getTime() - now.getTime()) / 1000);
-        setCountdown(secondsLeft);
-        setQuizStartTime(quizStart);
+      if (data.status === "not_started") {
+        setCountdown(data.seconds_remaining);
+        setQuizStartTime(new Date(data.starts_at));
         return;
       }
 
-      if (now >= quizEnd) {
+      if (data.status === "ended") {
         setError("Quiz has already ended. You cannot attempt it now.");
         return;
       }
 
       localStorage.setItem("admissionNumber", admissionNumber);
-      localStorage.setItem("studentPin", pin);
       localStorage.setItem("quizCode", code);
 
-      const today = new Date().toISOString().split("T")[0];
-      const finalId = quizData.cf_id;
-      const reqIdPlain = `${today}_${admissionNumber}_${code}_${finalId}`;
-      const encodedReqId = btoa(reqIdPlain);
-
       setTimeout(() => {
-        const targetUrl = `https://abesquiz.netlify.app/#/access-quiz?req_id=${encodedReqId}`;
-        window.location.href = targetUrl;
+        window.location.href = data.access_url;
       }, 300);
     } catch (err: any) {
       console.error(err);
@@ -115,6 +95,19 @@ export function QuizStarter() {
           }`}
         />
 
+        <input
+          type="password"
+          inputMode="numeric"
+          autoComplete="off"
+          value={pin}
+          onChange={(e) => {
+            setPin(e.target.value.trim());
+            setError("");
+          }}
+          placeholder="Enter your quiz PIN"
+          className="w-full px-4 py-2 border border-gray-300 rounded-md text-center"
+        />
+
         {error && <p className="text-sm text-red-600 text-center">{error}</p>}
 
         {countdown > 0 && (
@@ -126,13 +119,15 @@ export function QuizStarter() {
 
         <button
           onClick={() => {
-            if (quizCode.length === 4) {
-              handleSubmit(quizCode);
-            } else {
+            if (quizCode.length !== 4) {
               setError("Please enter a valid 4-digit quiz code");
+            } else if (!pin) {
+              setError("Please enter your quiz PIN");
+            } else {
+              handleSubmit(quizCode);
             }
           }}
-          disabled={loading || quizCode.length !== 4 || countdown > 0}
+          disabled={loading || quizCode.length !== 4 || !pin || countdown > 0}
           className="w-full px-4 py-2 bg-blue-600 text-white rounded-md hover:bg-blue-700 disabled:opacity-50"
         >
           {loading ? "Loading..." : "Start Quiz"}
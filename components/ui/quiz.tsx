@@ -45,25 +45,6 @@ interface CourseMap {
   [key: string]: string;
 }
 
-// Updated helper to parse pin that appears inside the fragment (#…?pin=…)
-function getPinFromQuizLink(htmlString: string): string | null {
-  if (typeof window === "undefined") return null;
-  const parser = new DOMParser();
-  const doc = parser.parseFromString(htmlString, "text/html");
-  const anchor = doc.querySelector("a");
-  if (!anchor) return null;
-
-  const rawHref = anchor.getAttribute("href") || anchor.href;
-  const hashParts = rawHref.split("#");
-  if (hashParts.length < 2) return null;
-  const fragment = hashParts[1];
-  const qIndex = fragment.indexOf("?");
-  if (qIndex === -1) return null;
-  const queryString = fragment.slice(qIndex + 1);
-  const params = new URLSearchParams(queryString);
-  return params.get("pin");
-}
-
 // Fetch function with timeout and retry logic
 const fetchWithRetry = async (
   url: string,
@@ -311,8 +292,6 @@ const QuizList = memo(function QuizList() {
         localStorage.setItem("studentName", first.student_name);
         window.dispatchEvent(new Event('student-name-updated'));
         localStorage.setItem("admissionNumber", first.admission_number);
-        const pinValue = getPinFromQuizLink(first.quiz_link) || "";
-        localStorage.setItem("studentPin", pinValue);
       } catch {}
     }
   }, [quizzes]);
@@ -0,0 +1,124 @@
+// Operator endpoints under `/admin`, mounted only when `admin_token` is set. Every call,
+// including rejected ones, goes to the audit log with the caller's address.
+use std::net::SocketAddr;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Path, State},
+    http::request::Parts,
+    routing::{delete, get},
+    Json, Router,
+};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::{
+    auth::bearer_token,
+    cache::CacheName,
+    error::AppError,
+    server::AppState,
+};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/cache", get(cache_stats_handler).delete(flush_handler))
+        .route("/cache/students/:student_id", delete(invalidate_student_handler))
+        .route("/cache/:name", delete(invalidate_cache_handler))
+        .route("/pending", get(pending_handler))
+}
+
+/// An authenticated admin request: `Authorization: Bearer <admin_token>`.
+pub struct Admin {
+    caller: String,
+    action: String,
+}
+
+impl Admin {
+    fn audit(&self, outcome: &str) {
+        info!(target: "aims_backend::audit", "[Audit] {} {}: {}", self.caller, self.action, outcome);
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let caller = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let action = format!("{} {}", parts.method, parts.uri.path());
+
+        let presented = bearer_token(&parts.headers).ok().flatten();
+        let expected = state.config.admin_token.as_deref();
+        // Comparing digests keeps the comparison time independent of the token
+        let allowed = match (presented, expected) {
+            (Some(presented), Some(expected)) => Sha256::digest(presented) == Sha256::digest(expected),
+            _ => false,
+        };
+        if !allowed {
+            warn!(target: "aims_backend::audit", "[Audit] {} {}: rejected, bad or missing admin token", caller, action);
+            return Err(match presented {
+                Some(_) => AppError::AuthenticationError("Invalid admin token".to_string()),
+                None => AppError::MissingCredentials("Admin token required".to_string()),
+            });
+        }
+
+        Ok(Self { caller, action })
+    }
+}
+
+async fn cache_stats_handler(admin: Admin, State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    state.cache.purge_expired().await;
+    let mut caches = Map::new();
+    for name in CacheName::ALL {
+        let stats = state.cache.cache_stats(name).await?;
+        caches.insert(name.as_str().to_string(), json!(stats));
+    }
+    let pending = state.cache.pending_requests().await.len();
+
+    admin.audit("viewed cache stats");
+    Ok(Json(json!({ "caches": caches, "pending_requests": pending })))
+}
+
+async fn invalidate_cache_handler(
+    admin: Admin,
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let Some(cache) = CacheName::parse(&name) else {
+        admin.audit("rejected, unknown cache");
+        return Err(AppError::NotFound(format!(
+            "No cache named '{}'; expected attendance, quiz or all-attendance",
+            name
+        )));
+    };
+
+    let removed = state.cache.invalidate(cache).await?;
+    admin.audit(&format!("invalidated {} entries", removed));
+    Ok(Json(json!({ "cache": cache, "removed": removed })))
+}
+
+async fn invalidate_student_handler(
+    admin: Admin,
+    State(state): State<AppState>,
+    Path(student_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let removed = state.cache.invalidate_student(&student_id).await?;
+    admin.audit(&format!("invalidated {} entries", removed));
+    Ok(Json(json!({ "student_id": student_id, "removed": removed })))
+}
+
+async fn flush_handler(admin: Admin, State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    let removed = state.cache.flush().await?;
+    admin.audit(&format!("flushed {} entries", removed));
+    Ok(Json(json!({ "removed": removed })))
+}
+
+async fn pending_handler(admin: Admin, State(state): State<AppState>) -> Json<Value> {
+    let pending = state.cache.pending_requests().await;
+    admin.audit(&format!("viewed {} pending requests", pending.len()));
+    Json(json!({ "pending": pending }))
+}
@@ -0,0 +1,148 @@
+use rayon::prelude::*;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::{
+    error::AppError,
+    models::*,
+    services::ExternalApiService,
+};
+
+// Raw per-subject card data behind the all-attendance view
+pub struct SubjectRecords {
+    pub subject: Subject,
+    pub records: Vec<QuizRecord>,
+}
+
+pub struct FullAttendance {
+    pub student_id: String,
+    pub course_code_map: HashMap<String, String>,
+    pub subjects: Vec<SubjectRecords>,
+    pub failed_subjects: Vec<String>,
+}
+
+pub(crate) async fn fetch_full_attendance(api_service: &ExternalApiService, token: &str) -> Result<FullAttendance, AppError> {
+    // Get student ID from attendance API
+    let attendance_records = api_service.get_attendance_records(token).await?;
+    let student_id = attendance_records
+        .first()
+        .map(|record| record.student_id.clone())
+        .ok_or_else(|| AppError::ExternalApiError("No attendance records returned".to_string()))?;
+
+    info!("[Aggregation] studentId: {}", student_id);
+
+    // Get subjects list
+    let subjects_data = api_service.get_subjects(token).await?;
+    let subjects: Vec<Subject> = subjects_data.par_iter().map(Subject::from).collect();
+
+    if subjects.is_empty() {
+        return Err(AppError::ExternalApiError("No subjects found".to_string()));
+    }
+
+    // Create course code mapping
+    let course_code_map: HashMap<String, String> = subjects_data
+        .par_iter()
+        .map(|entry| (
+            entry.cdata.course_code.clone(),
+            entry.cdata.course_name.trim().to_string()
+        ))
+        .collect();
+
+    // Parallel fetching for all subjects with enhanced timeout and retry logic
+    let fetch_results: Vec<_> = subjects
+        .par_iter()
+        .map(|subject| {
+            let subject_name = subject.name.clone();
+            let subject_cf_id = subject.cf_id.clone();
+            let student_id = student_id.clone();
+            let token = token.to_string();
+
+            async move {
+                api_service.fetch_subject_attendance(&token, &subject_name, &subject_cf_id, &student_id).await
+            }
+        })
+        .collect();
+
+    let mut subject_records = Vec::with_capacity(subjects.len());
+    let mut failed_subjects = Vec::new();
+
+    for (subject, result) in subjects.into_iter().zip(fetch_results) {
+        match result.await {
+            Ok(records) => subject_records.push(SubjectRecords { subject, records }),
+            Err(e) => {
+                warn!("[Aggregation] Failed to fetch data for {}: {}", subject.name, e);
+                failed_subjects.push(subject.name);
+            }
+        }
+    }
+
+    Ok(FullAttendance {
+        student_id,
+        course_code_map,
+        subjects: subject_records,
+        failed_subjects,
+    })
+}
+
+/// Calendar day (YYYY-MM-DD) a card belongs to; `None` when the portal gave no usable date.
+pub fn record_date(record: &QuizRecord) -> Option<String> {
+    if let Some(start_time) = &record.start_time {
+        let dt = chrono::DateTime::parse_from_rfc3339(start_time).ok()?;
+        Some(dt.format("%Y-%m-%d").to_string())
+    } else {
+        let date_formatted = record.date_formatted.as_ref()?;
+        date_formatted.split_whitespace().last().map(str::to_string)
+    }
+}
+
+pub fn summarize(full: &FullAttendance) -> AllAttendanceResponse {
+    let mut grand_present = 0;
+    let mut grand_absent = 0;
+    let mut subjects_summary: HashMap<String, SubjectSummary> = HashMap::new();
+
+    for SubjectRecords { subject, records } in &full.subjects {
+        let present_count = records.iter().filter(|d| d.state == "Present").count() as i32;
+        let absent_count = records.iter().filter(|d| d.state == "Absent").count() as i32;
+        grand_present += present_count;
+        grand_absent += absent_count;
+
+        // Process daily attendance
+        let mut by_date: HashMap<String, (i32, i32)> = HashMap::new();
+        for record in records {
+            // Undated cards count towards today, as they always have in the daily totals
+            let date = record_date(record).unwrap_or_else(|| chrono::Utc::now().format("%Y-%m-%d").to_string());
+            let entry = by_date.entry(date).or_insert((0, 0));
+            if record.state == "Present" {
+                entry.0 += 1;
+            } else {
+                entry.1 += 1;
+            }
+        }
+
+        let daily: Vec<DailyAttendanceRecord> = by_date
+            .into_iter()
+            .map(|(date, (present, absent))| DailyAttendanceRecord {
+                date,
+                present,
+                absent,
+            })
+            .collect();
+
+        subjects_summary.insert(subject.name.clone(), SubjectSummary {
+            total_present: present_count,
+            total_absent: absent_count,
+            daily,
+        });
+    }
+
+    AllAttendanceResponse {
+        student_id: full.student_id.clone(),
+        total_present_all_subjects: grand_present,
+        total_absent_all_subjects: grand_absent,
+        subjects: subjects_summary,
+        course_code_map: full.course_code_map.clone(),
+        cached_at: chrono::Utc::now(),
+        performance: None, // Could be enhanced with actual performance metrics
+        changes: None,
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+};
+
+use crate::{config::Config, error::AppError, redact::Secret, server::AppState, sessions::{self, Session}};
+
+/// The portal token for a request, taken from `Authorization: Bearer <token>` or, failing
+/// that, from the session cookie. Rejects with a 401 carrying `WWW-Authenticate`.
+pub struct UpstreamToken {
+    token: Secret<String>,
+    // Set only when the token came from the session cookie
+    session: Option<Arc<Session>>,
+}
+
+impl UpstreamToken {
+    pub fn token(&self) -> &str {
+        self.token.expose()
+    }
+
+    pub fn session(&self) -> Option<&Arc<Session>> {
+        self.session.as_ref()
+    }
+
+    /// A token the server stored itself, such as the one behind a calendar feed.
+    pub fn stored(token: Secret<String>) -> Self {
+        Self { token, session: None }
+    }
+
+    /// Applies the legacy `token` body field of `POST /api/attendance` and
+    /// `POST /api/subscriptions`. When `LEGACY_BODY_TOKEN` is on, a body token is used unless
+    /// an Authorization header was sent; otherwise it is ignored.
+    pub fn with_body_token(
+        extracted: Result<Self, AppError>,
+        body_token: Secret<String>,
+        config: &Config,
+    ) -> Result<Self, AppError> {
+        let from_header = matches!(&extracted, Ok(auth) if auth.session.is_none());
+        if !config.legacy_body_token || body_token.expose().is_empty() || from_header {
+            return extracted;
+        }
+
+        Ok(Self {
+            token: body_token,
+            session: None,
+        })
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for UpstreamToken {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if let Some(token) = bearer_token(&parts.headers)? {
+            return Ok(Self {
+                token: Secret::new(token.to_string()),
+                session: None,
+            });
+        }
+
+        let Some(id) = sessions::session_id(&parts.headers) else {
+            return Err(AppError::MissingCredentials(
+                "Send an Authorization: Bearer header or log in for a session".to_string(),
+            ));
+        };
+
+        match state.sessions.get(id).await {
+            Some(session) => Ok(Self {
+                token: session.upstream_token(),
+                session: Some(session),
+            }),
+            None => Err(AppError::AuthenticationError("Session expired, please log in again".to_string())),
+        }
+    }
+}
+
+// `Ok(None)` when no Authorization header was sent; any other scheme is rejected
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Result<Option<&str>, AppError> {
+    let Some(value) = headers.get(header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+
+    let invalid = || AppError::AuthenticationError("Authorization header must be 'Bearer <token>'".to_string());
+    let value = value.to_str().map_err(|_| invalid())?;
+    match value.trim().split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() => {
+            Ok(Some(token.trim()))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, authorization.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn parses_bearer_scheme_only() {
+        assert_eq!(bearer_token(&HeaderMap::new()).unwrap(), None);
+        assert_eq!(bearer_token(&headers("Bearer abc.def")).unwrap(), Some("abc.def"));
+        assert_eq!(bearer_token(&headers("bearer  abc ")).unwrap(), Some("abc"));
+
+        for value in ["abc.def", "Bearer ", "Basic dXNlcjpwYXNz"] {
+            let response = bearer_token(&headers(value)).unwrap_err().into_response();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(response.headers()[header::WWW_AUTHENTICATE]
+                .to_str()
+                .unwrap()
+                .contains("error=\"invalid_token\""));
+        }
+    }
+
+    #[test]
+    fn body_token_is_a_fallback_behind_the_flag() {
+        let mut config = Config::defaults();
+        let header_token = || Ok(UpstreamToken { token: Secret::new("header".to_string()), session: None });
+        let missing = || Err(AppError::MissingCredentials("missing".to_string()));
+        let body = || Secret::new("body".to_string());
+
+        config.legacy_body_token = true;
+        let auth = UpstreamToken::with_body_token(header_token(), body(), &config).unwrap();
+        assert_eq!(auth.token(), "header");
+        let auth = UpstreamToken::with_body_token(missing(), body(), &config).unwrap();
+        assert_eq!(auth.token(), "body");
+
+        config.legacy_body_token = false;
+        assert!(UpstreamToken::with_body_token(missing(), body(), &config).is_err());
+    }
+}
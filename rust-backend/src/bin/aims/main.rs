@@ -0,0 +1,288 @@
+// `aims`: attendance and quiz results from the terminal, built on the same client and
+// aggregation as the web API
+use std::io::{BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+use clap::{Parser, Subcommand};
+use serde_json::json;
+
+use aims_backend::{
+    aggregation,
+    client::{AimsClient, DEFAULT_BASE_URL},
+    models::{AllAttendanceResponse, AttendanceResponse, Quiz},
+    AppError,
+};
+
+mod output;
+mod store;
+
+use output::{percent, Format, Table};
+use store::SavedLogin;
+
+type CliResult<T = ()> = Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[command(name = "aims", version, about = "ABES attendance and quiz results from the terminal")]
+struct Cli {
+    /// Portal API base URL [default: the one used at login, else the production portal]
+    #[arg(long, env = "AIMS_API_BASE", global = true)]
+    base_url: Option<String>,
+
+    /// Portal token to use instead of the one saved by `aims login`
+    #[arg(long, env = "AIMS_TOKEN", global = true, hide_env_values = true)]
+    token: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+
+    /// Shorthand for `--format json`
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Log in to the portal and save the token for later commands
+    Login {
+        /// Portal username (roll number)
+        username: String,
+        /// Read from the terminal when not given
+        #[arg(long, env = "AIMS_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Attendance percentage per course and overall
+    Attendance,
+    /// Present and absent classes per subject
+    Subjects,
+    /// Day-by-day attendance for one subject, matched by name or course code
+    Subject { name: String },
+    /// Evaluated quiz results
+    Quiz,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let format = if cli.json { Format::Json } else { cli.format };
+
+    match run(cli, format).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match e.downcast_ref::<AppError>() {
+                Some(AppError::UpstreamUnauthorized(_) | AppError::AuthenticationError(_)) => {
+                    eprintln!("aims: {}\nRun `aims login <username>` to sign in again.", e)
+                }
+                _ => eprintln!("aims: {}", e),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli, format: Format) -> CliResult {
+    let saved = store::load()?;
+    let base_url = cli
+        .base_url
+        .clone()
+        .or_else(|| saved.as_ref().map(|login| login.base_url.clone()))
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+    let client = AimsClient::new(&base_url);
+
+    let token = move || -> CliResult<String> {
+        cli.token
+            .or_else(|| saved.map(|login| login.token))
+            .ok_or_else(|| "not logged in; run `aims login <username>` or set AIMS_TOKEN".into())
+    };
+
+    match cli.command {
+        Command::Login { username, password } => return login(&client, base_url, username, password).await,
+        Command::Attendance => {
+            let summary = client.attendance_summary(&token()?).await?;
+            output::print(format, &summary, attendance_table)?;
+        }
+        Command::Subjects => {
+            let summary = full_summary(&client, &token()?).await?;
+            output::print(format, &summary, subjects_table)?;
+        }
+        Command::Subject { name } => {
+            let summary = full_summary(&client, &token()?).await?;
+            let (subject, code) = find_subject(&summary, &name)?;
+            let detail = &summary.subjects[&subject];
+            let value = json!({
+                "subject": subject,
+                "course_code": code,
+                "total_present": detail.total_present,
+                "total_absent": detail.total_absent,
+                "daily": detail.daily,
+            });
+            output::print(format, &value, |_| {
+                let mut days: Vec<_> = detail.daily.iter().collect();
+                days.sort_by(|a, b| a.date.cmp(&b.date));
+                let mut table = Table::new(&["Date", "Present", "Absent"]);
+                for day in days {
+                    table.push(vec![day.date.clone(), day.present.to_string(), day.absent.to_string()]);
+                }
+                table
+            })?;
+        }
+        Command::Quiz => {
+            let quizzes = client.quizzes(&token()?).await?;
+            output::print(format, &quizzes, |quizzes| quiz_table(quizzes))?;
+        }
+    }
+    Ok(())
+}
+
+async fn login(client: &AimsClient, base_url: String, username: String, password: Option<String>) -> CliResult {
+    let password = match password {
+        Some(password) => password,
+        None => prompt_password()?,
+    };
+
+    let login = client.login(&username, &password).await?;
+    let path = store::save(&SavedLogin {
+        username,
+        base_url,
+        token: login.token.expose().clone(),
+        saved_at: chrono::Utc::now(),
+    })?;
+    eprintln!("Logged in; token saved to {}", path.display());
+    Ok(())
+}
+
+// Reads a line from stdin, with echo turned off when it is a terminal
+fn prompt_password() -> CliResult<String> {
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    if interactive {
+        eprint!("Password: ");
+        std::io::stderr().flush()?;
+        set_echo(false);
+    }
+
+    let mut password = String::new();
+    let read = stdin.lock().read_line(&mut password);
+    if interactive {
+        set_echo(true);
+        eprintln!();
+    }
+    read?;
+
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err("no password given".into());
+    }
+    Ok(password)
+}
+
+fn set_echo(on: bool) {
+    #[cfg(unix)]
+    {
+        let _ = std::process::Command::new("stty")
+            .arg(if on { "echo" } else { "-echo" })
+            .stdin(std::process::Stdio::inherit())
+            .status();
+    }
+    #[cfg(not(unix))]
+    let _ = on;
+}
+
+// Same fetch and aggregation as `GET /api/all-attendance`
+async fn full_summary(client: &AimsClient, token: &str) -> CliResult<AllAttendanceResponse> {
+    let full = client.full_attendance(token).await?;
+    if !full.failed_subjects.is_empty() {
+        eprintln!("aims: could not fetch {}", full.failed_subjects.join(", "));
+    }
+    Ok(aggregation::summarize(&full))
+}
+
+fn course_code<'a>(summary: &'a AllAttendanceResponse, subject: &str) -> &'a str {
+    summary
+        .course_code_map
+        .iter()
+        .find(|(_, name)| name.as_str() == subject)
+        .map(|(code, _)| code.as_str())
+        .unwrap_or("")
+}
+
+// An exact course code or name wins; otherwise the name must contain `query` for one subject only
+fn find_subject(summary: &AllAttendanceResponse, query: &str) -> CliResult<(String, String)> {
+    let query = query.trim().to_lowercase();
+    let mut names: Vec<&String> = summary.subjects.keys().collect();
+    names.sort();
+
+    let exact = names
+        .iter()
+        .find(|name| name.to_lowercase() == query || course_code(summary, name).to_lowercase() == query);
+    let matches: Vec<&&String> = match exact {
+        Some(name) => vec![name],
+        None => names.iter().filter(|name| name.to_lowercase().contains(&query)).collect(),
+    };
+
+    match matches.as_slice() {
+        [name] => Ok((name.to_string(), course_code(summary, name).to_string())),
+        [] => Err(format!("no subject matches '{}'; run `aims subjects` for the list", query).into()),
+        several => Err(format!(
+            "'{}' matches {}; be more specific",
+            query,
+            several.iter().map(|name| name.as_str()).collect::<Vec<_>>().join(", ")
+        )
+        .into()),
+    }
+}
+
+fn attendance_table(summary: &AttendanceResponse) -> Table {
+    let mut table = Table::new(&["Course", "Present", "Total", "Percent"]);
+    for course in &summary.daily_attendance {
+        table.push(vec![
+            course.course.clone(),
+            course.present.to_string(),
+            course.total.to_string(),
+            percent(course.present, course.total),
+        ]);
+    }
+    table.push(vec![
+        "Overall".to_string(),
+        summary.total_present.to_string(),
+        summary.total_classes.to_string(),
+        percent(summary.total_present, summary.total_classes),
+    ]);
+    table
+}
+
+fn subjects_table(summary: &AllAttendanceResponse) -> Table {
+    let mut names: Vec<&String> = summary.subjects.keys().collect();
+    names.sort();
+
+    let mut table = Table::new(&["Subject", "Code", "Present", "Absent", "Percent"]);
+    for name in names {
+        let subject = &summary.subjects[name];
+        table.push(vec![
+            name.clone(),
+            course_code(summary, name).to_string(),
+            subject.total_present.to_string(),
+            subject.total_absent.to_string(),
+            percent(subject.total_present, subject.total_present + subject.total_absent),
+        ]);
+    }
+    table
+}
+
+fn quiz_table(quizzes: &[Quiz]) -> Table {
+    let number = |value: Option<f64>| value.map(|v| format!("{}", v)).unwrap_or_default();
+
+    let mut table = Table::new(&["Date", "Subject", "Quiz", "Marks", "Out of", "Percentile"]);
+    for quiz in quizzes {
+        table.push(vec![
+            quiz.date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+            quiz.subject.clone().unwrap_or_default(),
+            quiz.title.clone().unwrap_or_default(),
+            number(quiz.marks_obtained),
+            number(quiz.max_marks),
+            number(quiz.percentile),
+        ]);
+    }
+    table
+}
@@ -0,0 +1,139 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Rows of a command's output, rendered as an aligned table or as CSV.
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        debug_assert_eq!(row.len(), self.headers.len());
+        self.rows.push(row);
+    }
+
+    fn render_table(&self) -> String {
+        let widths: Vec<usize> = (0..self.headers.len())
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .map(|row| row[column].chars().count())
+                    .chain(std::iter::once(self.headers[column].len()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        // Columns holding only numbers and percentages are right-aligned
+        let numeric: Vec<bool> = (0..self.headers.len())
+            .map(|column| {
+                self.rows.iter().all(|row| {
+                    let cell = row[column].trim_end_matches('%');
+                    cell.is_empty() || cell.parse::<f64>().is_ok()
+                })
+            })
+            .collect();
+
+        let line = |cells: &[&str]| {
+            let padded: Vec<String> = cells
+                .iter()
+                .enumerate()
+                .map(|(column, cell)| match numeric[column] {
+                    true => format!("{:>width$}", cell, width = widths[column]),
+                    false => format!("{:<width$}", cell, width = widths[column]),
+                })
+                .collect();
+            format!("{}\n", padded.join("  ").trim_end())
+        };
+
+        let rules: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        let mut out = line(&self.headers);
+        out.push_str(&line(&rules.iter().map(String::as_str).collect::<Vec<_>>()));
+        for row in &self.rows {
+            out.push_str(&line(&row.iter().map(String::as_str).collect::<Vec<_>>()));
+        }
+        out
+    }
+
+    fn render_csv(&self) -> String {
+        let mut out = format!("{}\r\n", self.headers.join(","));
+        for row in &self.rows {
+            let fields: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+            out.push_str(&format!("{}\r\n", fields.join(",")));
+        }
+        out
+    }
+}
+
+/// Prints `value` as JSON, or the table built from it.
+pub fn print<T: Serialize>(format: Format, value: &T, table: impl FnOnce(&T) -> Table) -> serde_json::Result<()> {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Format::Table => print!("{}", table(value).render_table()),
+        Format::Csv => print!("{}", table(value).render_csv()),
+    }
+    Ok(())
+}
+
+// Same rules as the web export: RFC 4180 quoting, spreadsheet formula prefixes neutralised
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+pub fn percent(present: i32, total: i32) -> String {
+    if total == 0 {
+        return String::new();
+    }
+    format!("{:.1}%", present as f64 * 100.0 / total as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Table {
+        let mut table = Table::new(&["Subject", "Present", "Percent"]);
+        table.push(vec!["Operating Systems".to_string(), "2".to_string(), percent(2, 2)]);
+        table.push(vec!["DBMS, Lab".to_string(), "13".to_string(), percent(3, 4)]);
+        table
+    }
+
+    #[test]
+    fn renders_aligned_tables_and_csv() {
+        assert_eq!(
+            sample().render_table(),
+            "Subject            Present  Percent\n\
+             -----------------  -------  -------\n\
+             Operating Systems        2   100.0%\n\
+             DBMS, Lab               13    75.0%\n"
+        );
+        assert_eq!(
+            sample().render_csv(),
+            "Subject,Present,Percent\r\nOperating Systems,2,100.0%\r\n\"DBMS, Lab\",13,75.0%\r\n"
+        );
+    }
+}
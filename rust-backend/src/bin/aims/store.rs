@@ -0,0 +1,99 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+/// What `aims login` leaves behind for the other commands.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedLogin {
+    pub username: String,
+    pub base_url: String,
+    pub token: String,
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+}
+
+// `AIMS_CONFIG_DIR`, else the platform's per-user config directory
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("AIMS_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|dir| dir.join("aims"))
+}
+
+fn login_path() -> io::Result<PathBuf> {
+    config_dir()
+        .map(|dir| dir.join("login.json"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory; set AIMS_CONFIG_DIR"))
+}
+
+pub fn load() -> io::Result<Option<SavedLogin>> {
+    let bytes = match fs::read(login_path()?) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_slice(&bytes).map(Some).map_err(io::Error::other)
+}
+
+/// Writes the login readable by the current user only (0600, in a 0700 directory).
+pub fn save(login: &SavedLogin) -> io::Result<PathBuf> {
+    let path = login_path()?;
+    let dir = path.parent().expect("login path has a parent");
+    fs::create_dir_all(dir)?;
+
+    let bytes = serde_json::to_vec_pretty(login).map_err(io::Error::other)?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        options.mode(0o600);
+        // `mode` only applies to new files; tighten one left by an older version too
+        if path.exists() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    io::Write::write_all(&mut options.open(&path)?, &bytes)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_login_is_private_to_the_user() {
+        let dir = std::env::temp_dir().join(format!("aims-cli-{}", uuid::Uuid::new_v4()));
+        std::env::set_var("AIMS_CONFIG_DIR", &dir);
+
+        let login = SavedLogin {
+            username: "2200320100001".to_string(),
+            base_url: "http://portal.test".to_string(),
+            token: "portal-token".to_string(),
+            saved_at: chrono::Utc::now(),
+        };
+        let path = save(&login).unwrap();
+        assert_eq!(load().unwrap().unwrap().token, "portal-token");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+            assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
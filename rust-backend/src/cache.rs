@@ -1,150 +1,470 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use chrono::Utc;
-use moka::future::Cache as MokaCache;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use tracing::{info, warn};
-use std::time::Duration as StdDuration;
+use std::time::{Duration as StdDuration, Instant};
 
-use crate::models::CacheEntry;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
 
+use crate::{
+    cache_backend::CacheBackend,
+    config::LiveTunables,
+    error::AppError,
+    models::CacheEntry,
+};
+
+type PendingSender = tokio::sync::oneshot::Sender<Result<serde_json::Value, String>>;
+
+/// Cache key for data fetched with `token`. The token is hashed so keys are safe to log and
+/// tokens sharing a prefix (every JWT starts with `eyJ`) never share an entry.
+pub fn token_key(kind: &str, token: &str) -> String {
+    let digest = hex::encode(Sha256::digest(token.as_bytes()));
+    format!("{}_{}", kind, &digest[..16])
+}
+
+/// The caches an admin can inspect or invalidate by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheName {
+    Attendance,
+    Quiz,
+    AllAttendance,
+    Lectures,
+}
+
+impl CacheName {
+    pub const ALL: [CacheName; 4] = [CacheName::Attendance, CacheName::Quiz, CacheName::AllAttendance, CacheName::Lectures];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|cache| cache.as_str() == name)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheName::Attendance => "attendance",
+            CacheName::Quiz => "quiz",
+            CacheName::AllAttendance => "all-attendance",
+            CacheName::Lectures => "lectures",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            CacheName::Attendance => "Attendance",
+            CacheName::Quiz => "Quiz",
+            CacheName::AllAttendance => "All attendance",
+            CacheName::Lectures => "Lectures",
+        }
+    }
+
+    // Where an entry of this cache lives in the backend
+    fn backend_key(self, key: &str) -> String {
+        format!("{}:{}", self.as_str(), key)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub entries: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// A request other callers with the same key are waiting on instead of calling the portal.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingRequest {
+    pub key: String,
+    pub started_at: DateTime<Utc>,
+    pub waiters: usize,
+}
+
+// A leader that has not completed in this long is assumed gone and replaced
+const PENDING_STALE_AFTER: StdDuration = StdDuration::from_secs(30);
+// How often a replica waiting on another replica's fetch checks the shared cache
+const LOCK_POLL_INTERVAL: StdDuration = StdDuration::from_millis(100);
+
+struct Pending {
+    started: Instant,
+    started_at: DateTime<Utc>,
+    waiters: Vec<PendingSender>,
+    // Whether this replica holds the backend's coalescing lock for the key
+    holds_lock: bool,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Attendance, lecture and quiz responses keyed by token, stored in a `CacheBackend`. A failing
+/// backend is logged and treated as a miss, so a cache outage never fails a request.
 pub struct Cache {
-    attendance_cache: MokaCache<String, CacheEntry<serde_json::Value>>,
-    quiz_cache: MokaCache<String, CacheEntry<serde_json::Value>>,
-    all_attendance_cache: MokaCache<String, CacheEntry<serde_json::Value>>,
-    pending_requests: Arc<RwLock<HashMap<String, tokio::sync::oneshot::Sender<Result<serde_json::Value, String>>>>>,
+    backend: Arc<dyn CacheBackend>,
+    // Read when an entry is written, so a reloaded `cache_ttl_seconds` applies to every
+    // entry stored after the reload
+    tunables: Arc<LiveTunables>,
+    counters: [Counters; 4],
+    pending_requests: Arc<RwLock<HashMap<String, Pending>>>,
 }
 
 impl Cache {
-    pub fn new() -> Self {
-        // Configure cache with TTL and max capacity
-        let attendance_cache = MokaCache::builder()
-            .time_to_live(StdDuration::from_secs(5 * 60))
-            .max_capacity(1000)
-            .build();
-
-        let quiz_cache = MokaCache::builder()
-            .time_to_live(StdDuration::from_secs(5 * 60))
-            .max_capacity(1000)
-            .build();
-
-        let all_attendance_cache = MokaCache::builder()
-            .time_to_live(StdDuration::from_secs(5 * 60))
-            .max_capacity(1000)
-            .build();
+    /// An in-process cache.
+    #[cfg(test)]
+    pub fn new(tunables: Arc<LiveTunables>) -> Self {
+        Self::with_backend(Arc::new(crate::cache_backend::MemoryBackend::new(64 << 20)), tunables)
+    }
 
+    pub fn with_backend(backend: Arc<dyn CacheBackend>, tunables: Arc<LiveTunables>) -> Self {
         Self {
-            attendance_cache,
-            quiz_cache,
-            all_attendance_cache,
+            backend,
+            tunables,
+            counters: Default::default(),
             pending_requests: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    pub async fn get_attendance(&self, key: &str) -> Option<serde_json::Value> {
-        if let Some(entry) = self.attendance_cache.get(key).await {
-            info!("[Cache] Attendance cache HIT for key: {}", key);
-            Some(entry.data)
+    fn counters(&self, name: CacheName) -> &Counters {
+        &self.counters[name as usize]
+    }
+
+    async fn lookup(&self, name: CacheName, key: &str) -> Option<serde_json::Value> {
+        match self.backend.get(&name.backend_key(key)).await {
+            Ok(Some(bytes)) => match decode_entry(&bytes) {
+                Ok(entry) => Some(entry.data),
+                Err(e) => {
+                    warn!("[Cache] Ignoring unreadable {} entry {}: {}", name.as_str(), key, e);
+                    None
+                }
+            },
+            Ok(None) => None,
+            Err(e) => {
+                warn!("[Cache] {} lookup failed, treating as a miss: {}", name.label(), e);
+                None
+            }
+        }
+    }
+
+    async fn get(&self, name: CacheName, key: &str) -> Option<serde_json::Value> {
+        let value = self.lookup(name, key).await;
+        if value.is_some() {
+            info!("[Cache] {} cache HIT for key: {}", name.label(), key);
+            self.counters(name).hits.fetch_add(1, Ordering::Relaxed);
         } else {
-            info!("[Cache] Attendance cache MISS for key: {}", key);
-            None
+            info!("[Cache] {} cache MISS for key: {}", name.label(), key);
+            self.counters(name).misses.fetch_add(1, Ordering::Relaxed);
         }
+        value
     }
 
-    pub async fn set_attendance(&self, key: String, data: serde_json::Value) {
+    async fn set(&self, name: CacheName, key: String, data: serde_json::Value) {
         let entry = CacheEntry {
             data,
             timestamp: Utc::now(),
         };
-        self.attendance_cache.insert(key.clone(), entry).await;
-        info!("[Cache] Stored attendance data for key: {}", key);
+        let bytes = match encode_entry(&entry) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("[Cache] Cannot serialize {} data for key {}: {}", name.as_str(), key, e);
+                return;
+            }
+        };
+
+        let ttl = StdDuration::from_secs(self.tunables.get().cache_ttl_seconds);
+        match self.backend.set(&name.backend_key(&key), &bytes, ttl).await {
+            Ok(()) => info!("[Cache] Stored {} data for key: {}", name.label().to_lowercase(), key),
+            Err(e) => warn!("[Cache] Failed to store {} data for key {}: {}", name.as_str(), key, e),
+        }
+    }
+
+    pub async fn get_attendance(&self, key: &str) -> Option<serde_json::Value> {
+        self.get(CacheName::Attendance, key).await
+    }
+
+    pub async fn set_attendance(&self, key: String, data: serde_json::Value) {
+        self.set(CacheName::Attendance, key, data).await
     }
 
     pub async fn get_quiz(&self, key: &str) -> Option<serde_json::Value> {
-        if let Some(entry) = self.quiz_cache.get(key).await {
-            info!("[Cache] Quiz cache HIT for key: {}", key);
-            Some(entry.data)
-        } else {
-            info!("[Cache] Quiz cache MISS for key: {}", key);
-            None
-        }
+        self.get(CacheName::Quiz, key).await
     }
 
     pub async fn set_quiz(&self, key: String, data: serde_json::Value) {
-        let entry = CacheEntry {
-            data,
-            timestamp: Utc::now(),
-        };
-        self.quiz_cache.insert(key.clone(), entry).await;
-        info!("[Cache] Stored quiz data for key: {}", key);
+        self.set(CacheName::Quiz, key, data).await
     }
 
     pub async fn get_all_attendance(&self, key: &str) -> Option<serde_json::Value> {
-        if let Some(entry) = self.all_attendance_cache.get(key).await {
-            info!("[Cache] All attendance cache HIT for key: {}", key);
-            Some(entry.data)
-        } else {
-            info!("[Cache] All attendance cache MISS for key: {}", key);
-            None
-        }
+        self.get(CacheName::AllAttendance, key).await
     }
 
     pub async fn set_all_attendance(&self, key: String, data: serde_json::Value) {
-        let entry = CacheEntry {
-            data,
-            timestamp: Utc::now(),
-        };
-        self.all_attendance_cache.insert(key.clone(), entry).await;
-        info!("[Cache] Stored all attendance data for key: {}", key);
+        self.set(CacheName::AllAttendance, key, data).await
+    }
+
+    pub async fn get_lectures(&self, key: &str) -> Option<serde_json::Value> {
+        self.get(CacheName::Lectures, key).await
+    }
+
+    pub async fn set_lectures(&self, key: String, data: serde_json::Value) {
+        self.set(CacheName::Lectures, key, data).await
     }
 
-    // Request deduplication for quiz endpoint
+    /// Coalesces concurrent requests for `key`. The first caller gets `Err` and must fetch,
+    /// then call `complete_pending_request`; later callers wait for its result. A waiter also
+    /// gets `Err` if the leader goes away or is too slow, and then fetches itself. With a
+    /// shared backend the first caller may instead wait for another replica's fetch.
     pub async fn get_or_create_pending_request(
         &self,
+        name: CacheName,
         key: String,
     ) -> Result<serde_json::Value, String> {
-        // Check if request is already pending
-        {
-            let pending = self.pending_requests.read().await;
-            if let Some(_sender) = pending.get(&key) {
-                info!("[Cache] Waiting for pending request: {}", key);
-                // Create a new channel for this request
-                let (_tx, rx) = tokio::sync::oneshot::channel();
-                drop(pending);
-                
-                // Wait for the result
-                match rx.await {
-                    Ok(result) => return result,
-                    Err(_) => {
-                        warn!("[Cache] Pending request failed, creating new one: {}", key);
+        let rx = {
+            let mut pending = self.pending_requests.write().await;
+            match pending.get_mut(&key) {
+                Some(request) if request.started.elapsed() < PENDING_STALE_AFTER => {
+                    let (tx, rx) = tokio::sync::oneshot::channel();
+                    request.waiters.push(tx);
+                    rx
+                }
+                _ => {
+                    pending.insert(
+                        key.clone(),
+                        Pending {
+                            started: Instant::now(),
+                            started_at: Utc::now(),
+                            waiters: Vec::new(),
+                            holds_lock: false,
+                        },
+                    );
+                    drop(pending);
+                    return self.lead(name, key).await;
+                }
+            }
+        };
+
+        info!("[Cache] Waiting for pending request: {}", key);
+        match tokio::time::timeout(PENDING_STALE_AFTER, rx).await {
+            Ok(Ok(result)) => result,
+            _ => {
+                warn!("[Cache] Pending request failed, creating new one: {}", key);
+                Err("leader_gone".to_string())
+            }
+        }
+    }
+
+    // The first caller on this replica takes the backend lock and fetches. While another
+    // replica holds it, wait for that replica's result to reach the cache instead.
+    async fn lead(&self, name: CacheName, key: String) -> Result<serde_json::Value, String> {
+        let lock = format!("lock:{}", name.backend_key(&key));
+        let deadline = Instant::now() + PENDING_STALE_AFTER;
+        loop {
+            match self.backend.try_lock(&lock, PENDING_STALE_AFTER).await {
+                Ok(false) if Instant::now() < deadline => {}
+                Ok(acquired) => {
+                    if let Some(request) = self.pending_requests.write().await.get_mut(&key) {
+                        request.holds_lock = acquired;
                     }
+                    return Err("leader".to_string());
+                }
+                Err(e) => {
+                    warn!("[Cache] Coalescing lock unavailable, fetching without it: {}", e);
+                    return Err("leader".to_string());
                 }
             }
+
+            tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+            if let Some(value) = self.lookup(name, &key).await {
+                info!("[Cache] Another replica fetched {}", key);
+                self.complete_pending_request(name, &key, Ok(value.clone())).await;
+                return Ok(value);
+            }
+        }
+    }
+
+    pub async fn complete_pending_request(&self, name: CacheName, key: &str, result: Result<serde_json::Value, String>) {
+        let request = self.pending_requests.write().await.remove(key);
+        let Some(request) = request else { return };
+
+        if request.holds_lock {
+            if let Err(e) = self.backend.unlock(&format!("lock:{}", name.backend_key(key))).await {
+                warn!("[Cache] Failed to release the coalescing lock for {}: {}", key, e);
+            }
+        }
+        for waiter in request.waiters {
+            let _ = waiter.send(result.clone());
+        }
+    }
+
+    pub async fn pending_requests(&self) -> Vec<PendingRequest> {
+        let pending = self.pending_requests.read().await;
+        let mut requests: Vec<PendingRequest> = pending
+            .iter()
+            .map(|(key, request)| PendingRequest {
+                key: key.clone(),
+                started_at: request.started_at,
+                waiters: request.waiters.len(),
+            })
+            .collect();
+        requests.sort_by_key(|request| request.started_at);
+        requests
+    }
+
+    /// Applies pending expirations and evictions so entry counts are exact.
+    pub async fn purge_expired(&self) {
+        self.backend.purge_expired().await;
+    }
+
+    /// Entry count (exact after `purge_expired`) and hits and misses on this replica.
+    pub async fn cache_stats(&self, name: CacheName) -> Result<CacheStats, AppError> {
+        let counters = self.counters(name);
+        Ok(CacheStats {
+            entries: self.backend.keys(&name.backend_key("")).await?.len() as u64,
+            hits: counters.hits.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+        })
+    }
+
+    // Removes every entry of `name` whose key matches, returning how many went
+    async fn remove_where(&self, name: CacheName, matches: impl Fn(&str) -> bool) -> Result<u64, AppError> {
+        let prefix = name.backend_key("");
+        let mut removed = 0;
+        for key in self.backend.keys(&prefix).await? {
+            if matches(&key[prefix.len()..]) && self.backend.delete(&key).await? {
+                removed += 1;
+            }
         }
+        Ok(removed)
+    }
 
-    // Request deduplication is disabled in this simplified implementation.
-    // Returning an Err will let the caller proceed to make the request.
-    Err("dedupe_not_supported".to_string())
+    /// Drops every entry of one cache; returns how many there were.
+    pub async fn invalidate(&self, name: CacheName) -> Result<u64, AppError> {
+        let removed = self.remove_where(name, |_| true).await?;
+        info!("[Cache] Invalidated {} {} entries", removed, name.as_str());
+        Ok(removed)
     }
 
-    pub async fn complete_pending_request(&self, key: &str, result: Result<serde_json::Value, String>) {
-        let mut pending = self.pending_requests.write().await;
-        if let Some(sender) = pending.remove(key) {
-            let _ = sender.send(result);
+    /// Drops every entry fetched with the tokens of `student_id`. Entries are keyed by token,
+    /// so the tokens are found through the student id in cached attendance; quiz entries
+    /// for a token with no cached attendance are not found.
+    pub async fn invalidate_student(&self, student_id: &str) -> Result<u64, AppError> {
+        let mut digests = HashSet::new();
+        for name in [CacheName::Attendance, CacheName::AllAttendance] {
+            let prefix = name.backend_key("");
+            for key in self.backend.keys(&prefix).await? {
+                let key = &key[prefix.len()..];
+                let belongs = self
+                    .lookup(name, key)
+                    .await
+                    .is_some_and(|data| data["student_id"].as_str() == Some(student_id));
+                if belongs {
+                    digests.extend(key_digest(key).map(str::to_string));
+                }
+            }
+        }
+        if digests.is_empty() {
+            return Ok(0);
         }
+
+        let mut removed = 0;
+        for name in CacheName::ALL {
+            removed += self
+                .remove_where(name, |key| key_digest(key).is_some_and(|digest| digests.contains(digest)))
+                .await?;
+        }
+        info!("[Cache] Invalidated {} entries for student {}", removed, student_id);
+        Ok(removed)
     }
 
-    pub async fn _clear_expired(&self) {
-        // Moka cache automatically handles expiration
-        info!("[Cache] Cache cleanup completed");
+    /// Drops every entry of every cache.
+    pub async fn flush(&self) -> Result<u64, AppError> {
+        let mut removed = 0;
+        for name in CacheName::ALL {
+            removed += self.remove_where(name, |_| true).await?;
+        }
+        info!("[Cache] Flushed {} entries", removed);
+        Ok(removed)
     }
 
-    pub async fn _get_stats(&self) -> HashMap<String, u64> {
+    /// Hits and misses per cache on this replica, and requests being coalesced.
+    pub async fn stats(&self) -> HashMap<String, u64> {
         let mut stats = HashMap::new();
-        stats.insert("attendance_cache_size".to_string(), self.attendance_cache.entry_count());
-        stats.insert("quiz_cache_size".to_string(), self.quiz_cache.entry_count());
-        stats.insert("all_attendance_cache_size".to_string(), self.all_attendance_cache.entry_count());
+        for name in CacheName::ALL {
+            let counters = self.counters(name);
+            let name = name.as_str().replace('-', "_");
+            stats.insert(format!("{}_hits", name), counters.hits.load(Ordering::Relaxed));
+            stats.insert(format!("{}_misses", name), counters.misses.load(Ordering::Relaxed));
+        }
         stats.insert("pending_requests".to_string(), self.pending_requests.read().await.len() as u64);
         stats
     }
 }
+
+// Entries are stored as gzipped JSON; a full attendance response shrinks to a fraction of
+// its size, and far more than its `serde_json::Value` tree takes in memory
+fn encode_entry(entry: &CacheEntry<serde_json::Value>) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    serde_json::to_writer(&mut encoder, entry)?;
+    encoder.finish()
+}
+
+fn decode_entry(bytes: &[u8]) -> serde_json::Result<CacheEntry<serde_json::Value>> {
+    serde_json::from_reader(GzDecoder::new(bytes))
+}
+
+// The token digest `token_key` puts after the kind
+fn key_digest(key: &str) -> Option<&str> {
+    key.rsplit_once('_').map(|(_, digest)| digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn concurrent_requests_wait_for_the_first() {
+        let cache = Arc::new(Cache::new(Arc::new(LiveTunables::new(Config::defaults().tunables()))));
+        let key = token_key("quiz", "portal-token");
+
+        assert!(cache.get_or_create_pending_request(CacheName::Quiz, key.clone()).await.is_err(), "first caller fetches");
+        let waiter = tokio::spawn({
+            let (cache, key) = (cache.clone(), key.clone());
+            async move { cache.get_or_create_pending_request(CacheName::Quiz, key).await }
+        });
+        while cache.pending_requests().await[0].waiters == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        cache.complete_pending_request(CacheName::Quiz, &key, Ok(serde_json::json!({ "quizzes": [] }))).await;
+        assert_eq!(waiter.await.unwrap().unwrap()["quizzes"], serde_json::json!([]));
+        assert!(cache.pending_requests().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn entries_are_stored_compressed() {
+        let backend = Arc::new(crate::cache_backend::MemoryBackend::new(1 << 20));
+        let tunables = Arc::new(LiveTunables::new(Config::defaults().tunables()));
+        let cache = Cache::with_backend(backend.clone(), tunables);
+        let subjects: Vec<_> = (0..200)
+            .map(|i| serde_json::json!({ "subject": format!("Subject {}", i), "present": i, "total": 200 }))
+            .collect();
+        let data = serde_json::json!({ "student_id": "2200320100001", "subjects": subjects });
+
+        let key = token_key("attendance", "portal-token");
+        cache.set_attendance(key.clone(), data.clone()).await;
+        let stored = backend.get(&CacheName::Attendance.backend_key(&key)).await.unwrap().unwrap();
+        assert_eq!(stored[..2], [0x1f, 0x8b]);
+        assert!(stored.len() * 5 < data.to_string().len(), "{} bytes", stored.len());
+        assert_eq!(cache.get_attendance(&key).await, Some(data.clone()));
+
+        // Anything that is not a gzipped entry is a miss
+        let plain = serde_json::to_vec(&CacheEntry { data, timestamp: Utc::now() }).unwrap();
+        let key = token_key("attendance", "other-token");
+        backend.set(&CacheName::Attendance.backend_key(&key), &plain, StdDuration::from_secs(60)).await.unwrap();
+        assert_eq!(cache.get_attendance(&key).await, None);
+    }
+}
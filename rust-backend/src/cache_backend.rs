@@ -0,0 +1,272 @@
+// Storage behind `Cache`. Backends hold opaque bytes under string keys; `Cache` owns the
+// key layout, serialization and statistics. Memory (moka) is the default; with the `redis`
+// feature, replicas can share entries through Redis, alone or behind a short-lived moka tier.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use moka::{future::Cache as MokaCache, Expiry};
+use tracing::info;
+
+use crate::{config::Config, error::AppError};
+
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError>;
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), AppError>;
+
+    /// Returns whether the key existed.
+    async fn delete(&self, key: &str) -> Result<bool, AppError>;
+
+    /// Every live key starting with `prefix`.
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, AppError>;
+
+    /// Takes the coalescing lock for `key` unless it is held; it lapses after `ttl` so a
+    /// holder that disappears does not block others for long.
+    async fn try_lock(&self, key: &str, ttl: Duration) -> Result<bool, AppError>;
+
+    async fn unlock(&self, key: &str) -> Result<(), AppError>;
+
+    /// Applies pending expirations so `keys` is exact; a no-op where that is always so.
+    async fn purge_expired(&self) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Per-process moka cache
+    Memory,
+    /// Redis only, shared by every replica
+    Redis,
+    /// Moka in front of Redis; local entries live at most `cache_l1_ttl_seconds`
+    Tiered,
+}
+
+impl BackendKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "memory" => Some(Self::Memory),
+            "redis" => Some(Self::Redis),
+            "tiered" => Some(Self::Tiered),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Memory => "memory",
+            Self::Redis => "redis",
+            Self::Tiered => "tiered",
+        }
+    }
+}
+
+/// The backend `config.cache_backend` names. Redis is not contacted here; an unreachable
+/// server shows up as cache misses and warnings, not as a failed start.
+pub fn from_config(config: &Config) -> Result<Arc<dyn CacheBackend>, AppError> {
+    info!("[Cache] Using the {} backend", config.cache_backend.as_str());
+    let max_bytes = config.cache_max_megabytes.saturating_mul(1024 * 1024);
+    match config.cache_backend {
+        BackendKind::Memory => Ok(Arc::new(MemoryBackend::new(max_bytes))),
+        #[cfg(feature = "redis")]
+        BackendKind::Redis => Ok(Arc::new(crate::redis::RedisBackend::from_config(config)?)),
+        #[cfg(feature = "redis")]
+        BackendKind::Tiered => Ok(Arc::new(TieredBackend::new(
+            Arc::new(crate::redis::RedisBackend::from_config(config)?),
+            Duration::from_secs(config.cache_l1_ttl_seconds),
+            max_bytes,
+        ))),
+        // Config validation rejects these in builds without the feature
+        #[cfg(not(feature = "redis"))]
+        BackendKind::Redis | BackendKind::Tiered => Err(AppError::InternalError(format!(
+            "cache_backend {} needs a build with --features redis",
+            config.cache_backend.as_str()
+        ))),
+    }
+}
+
+#[derive(Clone)]
+struct Stored {
+    value: Arc<[u8]>,
+    ttl: Duration,
+}
+
+// Each entry expires after the TTL it was stored with
+struct StoredTtl;
+
+impl Expiry<String, Stored> for StoredTtl {
+    fn expire_after_create(&self, _: &String, stored: &Stored, _: Instant) -> Option<Duration> {
+        Some(stored.ttl)
+    }
+
+    fn expire_after_update(&self, _: &String, stored: &Stored, _: Instant, _: Option<Duration>) -> Option<Duration> {
+        Some(stored.ttl)
+    }
+}
+
+pub struct MemoryBackend {
+    entries: MokaCache<String, Stored>,
+    locks: Mutex<HashMap<String, Instant>>,
+}
+
+impl MemoryBackend {
+    /// Evicts least recently used entries once keys and values together exceed `max_bytes`.
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            entries: MokaCache::builder()
+                .expire_after(StoredTtl)
+                .weigher(|key: &String, stored: &Stored| {
+                    u32::try_from(key.len() + stored.value.len()).unwrap_or(u32::MAX)
+                })
+                .max_capacity(max_bytes)
+                .build(),
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl CacheBackend for MemoryBackend {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError> {
+        Ok(self.entries.get(key).await.map(|stored| stored.value.to_vec()))
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), AppError> {
+        let stored = Stored { value: value.into(), ttl };
+        self.entries.insert(key.to_string(), stored).await;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, AppError> {
+        Ok(self.entries.remove(key).await.is_some())
+    }
+
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, AppError> {
+        Ok(self
+            .entries
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, _)| key.to_string())
+            .collect())
+    }
+
+    async fn try_lock(&self, key: &str, ttl: Duration) -> Result<bool, AppError> {
+        let mut locks = self.locks.lock().unwrap();
+        let now = Instant::now();
+        locks.retain(|_, until| *until > now);
+        if locks.contains_key(key) {
+            return Ok(false);
+        }
+        locks.insert(key.to_string(), now + ttl);
+        Ok(true)
+    }
+
+    async fn unlock(&self, key: &str) -> Result<(), AppError> {
+        self.locks.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn purge_expired(&self) {
+        self.entries.run_pending_tasks().await;
+    }
+}
+
+/// A local moka tier in front of a shared backend. Reads fill the local tier, writes and
+/// deletes go to both, and listing and locking use the shared tier only. An entry changed
+/// or invalidated through another replica can be served from here for up to `l1_ttl`.
+#[cfg(any(test, feature = "redis"))]
+pub struct TieredBackend {
+    l1: MemoryBackend,
+    l2: Arc<dyn CacheBackend>,
+    l1_ttl: Duration,
+}
+
+#[cfg(any(test, feature = "redis"))]
+impl TieredBackend {
+    pub fn new(l2: Arc<dyn CacheBackend>, l1_ttl: Duration, l1_max_bytes: u64) -> Self {
+        Self {
+            l1: MemoryBackend::new(l1_max_bytes),
+            l2,
+            l1_ttl,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "redis"))]
+#[async_trait]
+impl CacheBackend for TieredBackend {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError> {
+        if let Some(value) = self.l1.get(key).await? {
+            return Ok(Some(value));
+        }
+        let value = self.l2.get(key).await?;
+        if let Some(value) = &value {
+            self.l1.set(key, value, self.l1_ttl).await?;
+        }
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), AppError> {
+        self.l1.set(key, value, ttl.min(self.l1_ttl)).await?;
+        self.l2.set(key, value, ttl).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, AppError> {
+        let local = self.l1.delete(key).await?;
+        Ok(self.l2.delete(key).await? || local)
+    }
+
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, AppError> {
+        self.l2.keys(prefix).await
+    }
+
+    async fn try_lock(&self, key: &str, ttl: Duration) -> Result<bool, AppError> {
+        self.l2.try_lock(key, ttl).await
+    }
+
+    async fn unlock(&self, key: &str) -> Result<(), AppError> {
+        self.l2.unlock(key).await
+    }
+
+    async fn purge_expired(&self) {
+        self.l1.purge_expired().await;
+        self.l2.purge_expired().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tiered_reads_through_and_deletes_from_both_tiers() {
+        let shared = Arc::new(MemoryBackend::new(1 << 20));
+        let replica_a = TieredBackend::new(shared.clone(), Duration::from_secs(30), 1 << 20);
+        let replica_b = TieredBackend::new(shared.clone(), Duration::from_secs(30), 1 << 20);
+
+        replica_a.set("quiz:quiz_abc", b"cached", Duration::from_secs(300)).await.unwrap();
+        assert_eq!(replica_b.get("quiz:quiz_abc").await.unwrap().as_deref(), Some(&b"cached"[..]));
+        assert_eq!(replica_b.keys("quiz:").await.unwrap(), vec!["quiz:quiz_abc".to_string()]);
+
+        assert!(replica_b.delete("quiz:quiz_abc").await.unwrap());
+        assert_eq!(replica_b.get("quiz:quiz_abc").await.unwrap(), None);
+        // Replica A still has its local copy until the L1 TTL passes
+        assert!(replica_a.get("quiz:quiz_abc").await.unwrap().is_some());
+
+        assert!(replica_a.try_lock("lock:quiz_abc", Duration::from_secs(30)).await.unwrap());
+        assert!(!replica_b.try_lock("lock:quiz_abc", Duration::from_secs(30)).await.unwrap());
+        replica_a.unlock("lock:quiz_abc").await.unwrap();
+        assert!(replica_b.try_lock("lock:quiz_abc", Duration::from_secs(30)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn memory_capacity_is_counted_in_bytes() {
+        let backend = MemoryBackend::new(10_000);
+        for i in 0..50 {
+            backend.set(&format!("quiz:quiz_{}", i), &[0; 1000], Duration::from_secs(60)).await.unwrap();
+        }
+        backend.purge_expired().await;
+        let kept = backend.keys("quiz:").await.unwrap().len();
+        assert!((1..10).contains(&kept), "kept {} entries", kept);
+    }
+}
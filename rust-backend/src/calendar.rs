@@ -0,0 +1,217 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aggregation::{record_date, FullAttendance},
+    models::Quiz,
+    quiz::parse_timestamp,
+};
+
+const PRODID: &str = "-//AIMS//Attendance Calendar//EN";
+
+// Cards carry no end time, so lectures and quizzes without one are rendered as one hour slots
+const LECTURE_MINUTES: i64 = 60;
+const QUIZ_DURATION: &str = "PT1H";
+
+/// One lecture card as the calendar shows it. Kept in the lectures cache next to the
+/// all-attendance summary, which only has daily totals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lecture {
+    pub uid: String,
+    pub subject: String,
+    pub code: String,
+    pub state: String,
+    pub start_time: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lectures {
+    pub student_id: String,
+    pub lectures: Vec<Lecture>,
+}
+
+/// Every lecture card of a fetch, in subject and card order.
+pub fn lectures(full: &FullAttendance) -> Lectures {
+    let mut lectures = Vec::new();
+    for subject_records in &full.subjects {
+        let subject = &subject_records.subject;
+
+        for (index, record) in subject_records.records.iter().enumerate() {
+            let date = record_date(record);
+            let record_id = match &record.id {
+                Some(serde_json::Value::String(id)) => id.clone(),
+                Some(id) => id.to_string(),
+                None => format!("{}-{}", date.as_deref().unwrap_or("undated"), index),
+            };
+
+            lectures.push(Lecture {
+                uid: format!("lecture-{}-{}@aims", subject.cf_id, record_id),
+                subject: subject.name.clone(),
+                code: subject.code.clone(),
+                state: record.state.clone(),
+                start_time: record.start_time.clone(),
+                date,
+            });
+        }
+    }
+
+    Lectures {
+        student_id: full.student_id.clone(),
+        lectures,
+    }
+}
+
+/// Renders one event per lecture, with its attendance state in the summary, and the
+/// evaluated quizzes as an RFC 5545 calendar.
+pub fn render_calendar(lectures: &[Lecture], quizzes: &[Quiz], now: DateTime<Utc>) -> String {
+    let dtstamp = format_utc(now);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        "X-WR-CALNAME:AIMS Attendance".to_string(),
+        "REFRESH-INTERVAL;VALUE=DURATION:PT6H".to_string(),
+        "X-PUBLISHED-TTL:PT6H".to_string(),
+    ];
+
+    for lecture in lectures {
+        let start = lecture
+            .start_time
+            .as_deref()
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|dt| dt.with_timezone(&Utc));
+
+        let mut event = vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", escape_text(&lecture.uid)),
+            format!("DTSTAMP:{}", dtstamp),
+        ];
+
+        match start {
+            Some(start) => {
+                event.push(format!("DTSTART:{}", format_utc(start)));
+                event.push(format!("DTEND:{}", format_utc(start + Duration::minutes(LECTURE_MINUTES))));
+            }
+            None => {
+                // Only a calendar day is known; skip cards without one
+                let Some(day) = lecture.date.as_deref().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) else {
+                    continue;
+                };
+                event.push(format!("DTSTART;VALUE=DATE:{}", day.format("%Y%m%d")));
+            }
+        }
+
+        event.push(format!("SUMMARY:{}", escape_text(&format!("{} — {}", lecture.subject, lecture.state))));
+        event.push(format!("CATEGORIES:Attendance,{}", escape_text(&lecture.state)));
+        if !lecture.code.is_empty() {
+            event.push(format!("DESCRIPTION:{}", escape_text(&format!("Course code: {}", lecture.code))));
+        }
+        event.push("TRANSP:TRANSPARENT".to_string());
+        event.push("END:VEVENT".to_string());
+
+        lines.extend(event);
+    }
+
+    for (index, quiz) in quizzes.iter().enumerate() {
+        let Some(start) = quiz.date else {
+            continue;
+        };
+
+        let title = quiz.title.as_deref().unwrap_or("Quiz");
+        let uid = quiz.id.clone().unwrap_or_else(|| format!("{}-{}", format_utc(start), index));
+
+        let mut event = vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:quiz-{}@aims", escape_text(&uid)),
+            format!("DTSTAMP:{}", dtstamp),
+            format!("DTSTART:{}", format_utc(start)),
+        ];
+
+        match quiz.extra.get("end_time").and_then(|v| v.as_str()).and_then(parse_timestamp) {
+            Some(end) if end > start => event.push(format!("DTEND:{}", format_utc(end))),
+            _ => event.push(format!("DURATION:{}", QUIZ_DURATION)),
+        }
+
+        let summary = match &quiz.subject {
+            Some(course) => format!("{} ({})", title, course),
+            None => title.to_string(),
+        };
+        event.push(format!("SUMMARY:{}", escape_text(&summary)));
+        event.push("CATEGORIES:Quiz".to_string());
+        if let Some(marks) = quiz.marks_obtained {
+            event.push(format!("DESCRIPTION:{}", escape_text(&format!("Marks obtained: {}", marks))));
+        }
+        event.push("END:VEVENT".to_string());
+
+        lines.extend(event);
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut body = String::new();
+    for line in lines {
+        body.push_str(&fold_line(&line));
+        body.push_str("\r\n");
+    }
+    body
+}
+
+fn format_utc(dt: DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// RFC 5545 section 3.3.11
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// RFC 5545 section 3.1: lines longer than 75 octets are folded with CRLF + space,
+// never splitting a UTF-8 sequence
+fn fold_line(line: &str) -> String {
+    if line.len() <= 75 {
+        return line.to_string();
+    }
+
+    let mut folded = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut width = 0;
+    for ch in line.chars() {
+        let len = ch.len_utf8();
+        if width + len > 75 {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the continuation line
+            width = 1;
+        }
+        folded.push(ch);
+        width += len;
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape_text("DBMS; Lab, A\\B\nx"), "DBMS\\; Lab\\, A\\\\B\\nx");
+    }
+
+    #[test]
+    fn folds_long_lines_on_char_boundaries() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold_line(&line);
+
+        for part in folded.split("\r\n") {
+            assert!(part.len() <= 75, "line too long: {}", part.len());
+        }
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::{error::AppError, redact::Secret, secrets::Keyring};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarFeed {
+    // Lookups go by hash; the id itself is only kept sealed so the URL can be shown again
+    pub id_hash: String,
+    pub encrypted_id: String,
+    pub student_id: String,
+    pub encrypted_token: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// One feed per student, persisted as a JSON file with the id and token encrypted.
+// Subscribe URLs carry only the random feed id, which can be revoked server-side.
+pub struct CalendarFeedStore {
+    path: PathBuf,
+    keyring: Arc<Keyring>,
+    feeds: RwLock<HashMap<String, CalendarFeed>>,
+}
+
+impl CalendarFeedStore {
+    pub async fn open(path: &str, keyring: Arc<Keyring>) -> Result<Self, AppError> {
+        let mut feeds: HashMap<String, CalendarFeed> = match tokio::fs::read(path).await {
+            Ok(bytes) => {
+                let list: Vec<CalendarFeed> = serde_json::from_slice(&bytes)?;
+                list.into_iter().map(|f| (f.student_id.clone(), f)).collect()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(AppError::InternalError(format!("Failed to read calendar feeds: {}", e)));
+            }
+        };
+
+        info!("[Calendar] Loaded {} feed(s)", feeds.len());
+
+        // Move entries sealed with a retired key over to the active one
+        let mut resealed = 0;
+        for feed in feeds.values_mut() {
+            if !keyring.needs_reseal(&feed.encrypted_id) && !keyring.needs_reseal(&feed.encrypted_token) {
+                continue;
+            }
+            match (keyring.reseal(&feed.encrypted_id), keyring.reseal(&feed.encrypted_token)) {
+                (Ok(id), Ok(token)) => {
+                    feed.encrypted_id = id;
+                    feed.encrypted_token = token;
+                    resealed += 1;
+                }
+                _ => warn!("[Calendar] Cannot re-encrypt the feed for student {}, key missing", feed.student_id),
+            }
+        }
+
+        let store = Self {
+            path: PathBuf::from(path),
+            keyring,
+            feeds: RwLock::new(feeds),
+        };
+
+        if resealed > 0 {
+            store.persist(&*store.feeds.read().await).await?;
+            info!("[Calendar] Re-encrypted {} feed(s) with the active key", resealed);
+        }
+
+        Ok(store)
+    }
+
+    /// Returns the student's feed id, creating the feed on first use. The stored token is
+    /// replaced with `token`, so the feed keeps working while the student keeps logging in.
+    pub async fn issue(&self, student_id: &str, token: &str) -> Result<Secret<String>, AppError> {
+        let now = Utc::now();
+        let encrypted_token = self.keyring.encrypt(token.as_bytes())?;
+
+        let mut feeds = self.feeds.write().await;
+        let existing = match feeds.get(student_id) {
+            Some(feed) => std::str::from_utf8(&self.keyring.decrypt(&feed.encrypted_id)?)
+                .ok()
+                .map(|id| (Secret::new(id.to_string()), feed.clone())),
+            None => None,
+        };
+
+        let (id, feed) = match existing {
+            Some((id, feed)) => (
+                id,
+                CalendarFeed {
+                    encrypted_token,
+                    updated_at: now,
+                    ..feed
+                },
+            ),
+            None => {
+                let mut bytes = [0u8; 32];
+                OsRng.fill_bytes(&mut bytes);
+                let id = Secret::new(hex::encode(bytes));
+                let feed = CalendarFeed {
+                    id_hash: hash_id(id.expose()),
+                    encrypted_id: self.keyring.encrypt(id.expose().as_bytes())?,
+                    student_id: student_id.to_string(),
+                    encrypted_token,
+                    created_at: now,
+                    updated_at: now,
+                };
+                info!("[Calendar] Created feed for student {}", student_id);
+                (id, feed)
+            }
+        };
+
+        feeds.insert(student_id.to_string(), feed);
+        self.persist(&feeds).await?;
+        Ok(id)
+    }
+
+    /// The portal token behind a feed id, or `None` for unknown and revoked ids.
+    pub async fn token(&self, id: &str) -> Option<Secret<String>> {
+        let id_hash = hash_id(id);
+        let feeds = self.feeds.read().await;
+        let feed = feeds.values().find(|feed| feed.id_hash == id_hash)?;
+
+        match self.keyring.decrypt(&feed.encrypted_token) {
+            Ok(plaintext) => std::str::from_utf8(&plaintext).ok().map(|token| Secret::new(token.to_string())),
+            Err(e) => {
+                warn!("[Calendar] Cannot open the feed for student {}: {}", feed.student_id, e);
+                None
+            }
+        }
+    }
+
+    /// Deletes the student's feed; its URL stops working immediately.
+    pub async fn revoke(&self, student_id: &str) -> Result<bool, AppError> {
+        let mut feeds = self.feeds.write().await;
+        let removed = feeds.remove(student_id).is_some();
+        if removed {
+            self.persist(&feeds).await?;
+            info!("[Calendar] Revoked feed for student {}", student_id);
+        }
+        Ok(removed)
+    }
+
+    // Write to a temp file and rename so a crash never leaves a truncated store
+    async fn persist(&self, feeds: &HashMap<String, CalendarFeed>) -> Result<(), AppError> {
+        let list: Vec<&CalendarFeed> = feeds.values().collect();
+        let bytes = serde_json::to_vec_pretty(&list)?;
+        let tmp_path = self.path.with_extension("json.tmp");
+
+        let result = async {
+            if let Some(parent) = self.path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&tmp_path, &bytes).await?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                tokio::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600)).await?;
+            }
+            tokio::fs::rename(&tmp_path, &self.path).await
+        }.await;
+
+        result.map_err(|e| AppError::InternalError(format!("Failed to persist calendar feeds: {}", e)))
+    }
+}
+
+fn hash_id(id: &str) -> String {
+    hex::encode(Sha256::digest(id.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn feeds_are_reused_refreshed_and_revoked() {
+        let path = std::env::temp_dir().join(format!("aims-feeds-{}.json", uuid::Uuid::new_v4()));
+        let keyring = Arc::new(Keyring::parse("test:MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=").unwrap());
+        let store = CalendarFeedStore::open(path.to_str().unwrap(), keyring.clone()).await.unwrap();
+
+        let id = store.issue("2200320100001", "first-token").await.unwrap();
+        let again = store.issue("2200320100001", "second-token").await.unwrap();
+        assert_eq!(id.expose(), again.expose());
+        assert_eq!(store.token(id.expose()).await.unwrap().expose(), "second-token");
+
+        let stored = std::fs::read_to_string(&path).unwrap();
+        assert!(!stored.contains(id.expose().as_str()) && !stored.contains("second-token"));
+
+        let reopened = CalendarFeedStore::open(path.to_str().unwrap(), keyring).await.unwrap();
+        assert_eq!(reopened.token(id.expose()).await.unwrap().expose(), "second-token");
+        assert!(reopened.revoke("2200320100001").await.unwrap());
+        assert!(reopened.token(id.expose()).await.is_none());
+        assert!(!reopened.revoke("2200320100001").await.unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::error::AppError;
+
+const REDACTED: &str = "[REDACTED]";
+
+// Query parameters and body fields that carry credentials; never written to a cassette
+const SECRET_FIELDS: &[&str] = &["token", "access_token", "authToken", "password", "pin"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpstreamMode {
+    /// Talk to the portal
+    Live,
+    /// Talk to the portal and write every exchange to the cassette directory
+    Record,
+    /// Answer from the cassette directory without any network access
+    Replay,
+}
+
+impl UpstreamMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "live" => Some(Self::Live),
+            "record" => Some(Self::Record),
+            "replay" => Some(Self::Replay),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Live => "live",
+            Self::Record => "record",
+            Self::Replay => "replay",
+        }
+    }
+}
+
+/// An upstream request with credentials removed. Only the path and query are kept, so
+/// cassettes recorded against one portal host replay against any other.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub body: Value,
+    // Set when the body was not JSON and is stored as a string
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub raw: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl RecordedRequest {
+    pub fn from_request(request: &reqwest::Request) -> Self {
+        let url = request.url();
+        let mut path = url.path().to_string();
+
+        let mut pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| {
+                let value = if is_secret(&name) { REDACTED.to_string() } else { value.into_owned() };
+                (name.into_owned(), value)
+            })
+            .collect();
+        pairs.sort();
+        if !pairs.is_empty() {
+            let query: String = url::form_urlencoded::Serializer::new(String::new()).extend_pairs(pairs).finish();
+            path = format!("{}?{}", path, query);
+        }
+
+        let body = request.body().and_then(|body| body.as_bytes()).map(|bytes| {
+            let mut value = serde_json::from_slice(bytes).unwrap_or_else(|_| {
+                // Form bodies (the login) are stored as an object of their fields
+                let fields: Map<String, Value> = url::form_urlencoded::parse(bytes)
+                    .map(|(name, value)| (name.into_owned(), Value::String(value.into_owned())))
+                    .collect();
+                Value::Object(fields)
+            });
+            redact_value(&mut value);
+            value
+        });
+
+        Self {
+            method: request.method().to_string(),
+            path,
+            body,
+        }
+    }
+
+    // `<last path segment>-<hash of the redacted request>.json`
+    fn file_name(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.method.as_bytes());
+        hasher.update(b" ");
+        hasher.update(self.path.as_bytes());
+        if let Some(body) = &self.body {
+            hasher.update(b"\n");
+            hasher.update(body.to_string().as_bytes());
+        }
+        let hash = hex::encode(hasher.finalize());
+
+        let segment = self.path.split('?').next().unwrap_or("").rsplit('/').next().unwrap_or("");
+        let slug: String = segment
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        format!("{}-{}.json", if slug.is_empty() { "root" } else { &slug }, &hash[..16])
+    }
+}
+
+/// Directory of recorded portal exchanges, one JSON file per distinct request.
+pub struct Cassettes {
+    dir: PathBuf,
+}
+
+impl Cassettes {
+    pub fn new(dir: &str) -> Self {
+        Self { dir: PathBuf::from(dir) }
+    }
+
+    pub async fn record(&self, request: RecordedRequest, status: u16, body: &[u8]) {
+        let (mut body, raw) = match serde_json::from_slice::<Value>(body) {
+            Ok(value) => (value, false),
+            Err(_) => (Value::String(String::from_utf8_lossy(body).into_owned()), true),
+        };
+        redact_value(&mut body);
+
+        let path = self.dir.join(request.file_name());
+        let interaction = Interaction {
+            request,
+            response: RecordedResponse { status, body, raw },
+            recorded_at: Utc::now(),
+        };
+
+        let result = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let bytes = serde_json::to_vec_pretty(&interaction).map_err(std::io::Error::other)?;
+            tokio::fs::write(&path, bytes).await
+        }.await;
+
+        match result {
+            Ok(()) => info!("[Cassette] Recorded {} {}", interaction.request.method, interaction.request.path),
+            Err(e) => warn!("[Cassette] Failed to write {}: {}", path.display(), e),
+        }
+    }
+
+    /// Status and body recorded for `request`.
+    pub async fn replay(&self, request: &RecordedRequest) -> Result<(u16, Vec<u8>), AppError> {
+        let path = self.dir.join(request.file_name());
+        let bytes = tokio::fs::read(&path).await.map_err(|_| {
+            AppError::ExternalApiError(format!("No cassette for {} {}", request.method, request.path))
+        })?;
+        let interaction: Interaction = serde_json::from_slice(&bytes)
+            .map_err(|e| AppError::InternalError(format!("Corrupt cassette {}: {}", path.display(), e)))?;
+
+        let body = match interaction.response.body {
+            Value::String(text) if interaction.response.raw => text.into_bytes(),
+            body => serde_json::to_vec(&body)?,
+        };
+        Ok((interaction.response.status, body))
+    }
+}
+
+fn is_secret(name: &str) -> bool {
+    SECRET_FIELDS.iter().any(|field| field.eq_ignore_ascii_case(name))
+}
+
+fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if is_secret(name) && !field.is_null() {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_requests_carry_no_credentials() {
+        let client = reqwest::Client::new();
+        let cards = client
+            .get("https://portal.example/api/v1/cards?type=Attendance&token=secret-token&equalto___cf_id=1201")
+            .header("Authorization", "Bearer secret-token")
+            .build()
+            .unwrap();
+        let login = client
+            .post("https://portal.example/api/v1/admin/authenticate")
+            .body("username=2200320100001&password=hunter2")
+            .build()
+            .unwrap();
+
+        let cards = RecordedRequest::from_request(&cards);
+        let login = RecordedRequest::from_request(&login);
+        let stored = serde_json::to_string(&(&cards, &login)).unwrap();
+        assert!(!stored.contains("secret-token") && !stored.contains("hunter2"));
+        assert_eq!(cards.path, "/api/v1/cards?equalto___cf_id=1201&token=%5BREDACTED%5D&type=Attendance");
+
+        // Requests that differ only in their token share a cassette
+        let other = client
+            .get("https://other.example/api/v1/cards?equalto___cf_id=1201&token=another&type=Attendance")
+            .build()
+            .unwrap();
+        assert_eq!(RecordedRequest::from_request(&other).file_name(), cards.file_name());
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use chrono::{DateTime, Utc};
+use moka::future::Cache as MokaCache;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::{
+    aggregation::{record_date, FullAttendance},
+    models::*,
+};
+
+// Number of change sets kept per student for `?since=` queries
+const MAX_CHANGE_SETS: usize = 100;
+
+#[derive(Clone)]
+struct ObservedRecord {
+    date: String,
+    start_time: Option<String>,
+    state: String,
+}
+
+#[derive(Default)]
+struct StudentState {
+    observed_at: Option<DateTime<Utc>>,
+    // subject name -> record key -> record
+    subjects: HashMap<String, HashMap<String, ObservedRecord>>,
+    change_log: VecDeque<AttendanceChanges>,
+}
+
+// Keeps the previous card data per student and diffs each new fetch against it
+pub struct ChangeTracker {
+    students: MokaCache<String, Arc<Mutex<StudentState>>>,
+}
+
+impl ChangeTracker {
+    pub fn new() -> Self {
+        let students = MokaCache::builder()
+            .time_to_idle(StdDuration::from_secs(7 * 24 * 60 * 60))
+            .max_capacity(10_000)
+            .build();
+
+        Self { students }
+    }
+
+    /// Records a fresh fetch and returns what changed since the previous one.
+    /// The first observation for a student only establishes a baseline.
+    pub async fn observe(&self, full: &FullAttendance) -> Option<AttendanceChanges> {
+        let entry = self
+            .students
+            .get_with(full.student_id.clone(), async { Arc::new(Mutex::new(StudentState::default())) })
+            .await;
+        let mut student = entry.lock().await;
+        let now = Utc::now();
+
+        let current: HashMap<String, HashMap<String, ObservedRecord>> = full
+            .subjects
+            .iter()
+            .map(|s| (s.subject.name.clone(), index_records(&s.records)))
+            .collect();
+
+        let previous_fetch = student.observed_at;
+        let mut subjects = Vec::new();
+
+        if previous_fetch.is_some() {
+            let mut names: Vec<&String> = current.keys().chain(student.subjects.keys()).collect();
+            names.sort();
+            names.dedup();
+
+            for name in names {
+                // A failed fetch is not the same as the records disappearing
+                if full.failed_subjects.contains(name) {
+                    continue;
+                }
+
+                let empty = HashMap::new();
+                let before = student.subjects.get(name).unwrap_or(&empty);
+                let after = current.get(name).unwrap_or(&empty);
+
+                if let Some(changes) = diff_subject(name, before, after) {
+                    subjects.push(changes);
+                }
+            }
+        }
+
+        // Keep the last known records for subjects that failed this round
+        let mut merged = current;
+        for name in &full.failed_subjects {
+            if let Some(records) = student.subjects.remove(name) {
+                merged.insert(name.clone(), records);
+            }
+        }
+        student.subjects = merged;
+        student.observed_at = Some(now);
+
+        let previous_fetch = previous_fetch?;
+        if subjects.is_empty() {
+            return None;
+        }
+
+        let changes = AttendanceChanges {
+            previous_fetch,
+            detected_at: now,
+            subjects,
+        };
+
+        info!(
+            "[Changes] {} subject(s) changed for student {}",
+            changes.subjects.len(),
+            full.student_id
+        );
+
+        student.change_log.push_back(changes.clone());
+        while student.change_log.len() > MAX_CHANGE_SETS {
+            student.change_log.pop_front();
+        }
+
+        Some(changes)
+    }
+
+    /// What the latest fetch for the student found, if it found anything.
+    pub async fn latest(&self, student_id: &str) -> Option<AttendanceChanges> {
+        let entry = self.students.get(student_id).await?;
+        let student = entry.lock().await;
+
+        let changes = student.change_log.back()?;
+        (Some(changes.detected_at) == student.observed_at).then(|| changes.clone())
+    }
+
+    pub async fn changes_since(&self, student_id: &str, since: Option<DateTime<Utc>>) -> Vec<AttendanceChanges> {
+        let Some(entry) = self.students.get(student_id).await else {
+            return Vec::new();
+        };
+        let student = entry.lock().await;
+
+        student
+            .change_log
+            .iter()
+            .filter(|c| since.is_none_or(|since| c.detected_at > since))
+            .cloned()
+            .collect()
+    }
+}
+
+// Records without a date are left out; filing them under the day they were seen would report
+// them on the wrong day
+fn index_records(records: &[QuizRecord]) -> HashMap<String, ObservedRecord> {
+    let mut indexed = HashMap::with_capacity(records.len());
+
+    for record in records {
+        let Some(date) = record_date(record) else { continue };
+        let base_key = match &record.id {
+            Some(serde_json::Value::String(id)) => id.clone(),
+            Some(id) => id.to_string(),
+            None => record
+                .start_time
+                .clone()
+                .or_else(|| record.date_formatted.clone())
+                .unwrap_or_else(|| date.clone()),
+        };
+
+        // Disambiguate records that share a timestamp
+        let mut key = base_key.clone();
+        let mut n = 1;
+        while indexed.contains_key(&key) {
+            n += 1;
+            key = format!("{}#{}", base_key, n);
+        }
+
+        indexed.insert(key, ObservedRecord {
+            date,
+            start_time: record.start_time.clone(),
+            state: record.state.clone(),
+        });
+    }
+
+    indexed
+}
+
+fn diff_subject(
+    name: &str,
+    before: &HashMap<String, ObservedRecord>,
+    after: &HashMap<String, ObservedRecord>,
+) -> Option<SubjectChanges> {
+    let to_change = |r: &ObservedRecord| RecordChange {
+        date: r.date.clone(),
+        start_time: r.start_time.clone(),
+        state: r.state.clone(),
+    };
+
+    let mut new_records: Vec<RecordChange> = after
+        .iter()
+        .filter(|(key, _)| !before.contains_key(*key))
+        .map(|(_, r)| to_change(r))
+        .collect();
+
+    let mut removed_records: Vec<RecordChange> = before
+        .iter()
+        .filter(|(key, _)| !after.contains_key(*key))
+        .map(|(_, r)| to_change(r))
+        .collect();
+
+    let mut state_changes: Vec<RecordStateChange> = after
+        .iter()
+        .filter_map(|(key, r)| {
+            let old = before.get(key)?;
+            (old.state != r.state).then(|| RecordStateChange {
+                date: r.date.clone(),
+                start_time: r.start_time.clone(),
+                previous_state: old.state.clone(),
+                state: r.state.clone(),
+            })
+        })
+        .collect();
+
+    if new_records.is_empty() && removed_records.is_empty() && state_changes.is_empty() {
+        return None;
+    }
+
+    new_records.sort_by(|a, b| (&a.date, &a.start_time).cmp(&(&b.date, &b.start_time)));
+    removed_records.sort_by(|a, b| (&a.date, &a.start_time).cmp(&(&b.date, &b.start_time)));
+    state_changes.sort_by(|a, b| (&a.date, &a.start_time).cmp(&(&b.date, &b.start_time)));
+
+    let summary = summarize_changes(name, &new_records, &state_changes, &removed_records);
+
+    Some(SubjectChanges {
+        subject: name.to_string(),
+        summary,
+        new_records,
+        state_changes,
+        removed_records,
+    })
+}
+
+// e.g. "2 new lectures marked in DBMS, 1 absent"
+fn summarize_changes(
+    name: &str,
+    new_records: &[RecordChange],
+    state_changes: &[RecordStateChange],
+    removed_records: &[RecordChange],
+) -> String {
+    let plural = |n: usize, word: &str| {
+        if n == 1 { format!("{} {}", n, word) } else { format!("{} {}s", n, word) }
+    };
+
+    let mut parts = Vec::new();
+
+    if !new_records.is_empty() {
+        let absent = new_records.iter().filter(|r| r.state == "Absent").count();
+        let mut part = format!("{} marked in {}", plural(new_records.len(), "new lecture"), name);
+        if absent > 0 {
+            part.push_str(&format!(", {} absent", absent));
+        }
+        parts.push(part);
+    }
+
+    if !state_changes.is_empty() {
+        parts.push(format!("{} in {}", plural(state_changes.len(), "correction"), name));
+    }
+
+    if !removed_records.is_empty() {
+        parts.push(format!("{} removed from {}", plural(removed_records.len(), "lecture"), name));
+    }
+
+    parts.join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aggregation::SubjectRecords;
+
+    fn record(id: u32, state: &str) -> QuizRecord {
+        QuizRecord {
+            id: Some(serde_json::json!(id)),
+            state: state.to_string(),
+            start_time: Some(format!("2026-03-{:02}T09:00:00+05:30", id)),
+            date_formatted: None,
+        }
+    }
+
+    fn fetch(subjects: &[(&str, Vec<QuizRecord>)]) -> FullAttendance {
+        FullAttendance {
+            student_id: "2200320100001".to_string(),
+            course_code_map: HashMap::new(),
+            subjects: subjects
+                .iter()
+                .map(|(name, records)| SubjectRecords {
+                    subject: Subject {
+                        name: name.to_string(),
+                        code: String::new(),
+                        cf_id: String::new(),
+                    },
+                    records: records.clone(),
+                })
+                .collect(),
+            failed_subjects: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn identical_fetches_report_nothing() {
+        let tracker = ChangeTracker::new();
+        let full = fetch(&[("OS", vec![record(1, "Present"), record(2, "Absent")])]);
+
+        assert!(tracker.observe(&full).await.is_none(), "the first fetch is a baseline");
+        assert!(tracker.observe(&full).await.is_none());
+        assert!(tracker.latest("2200320100001").await.is_none());
+        assert!(tracker.changes_since("2200320100001", None).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn new_subject_is_reported_with_its_records() {
+        let tracker = ChangeTracker::new();
+        tracker.observe(&fetch(&[("OS", vec![record(1, "Present")])])).await;
+
+        let changes = tracker
+            .observe(&fetch(&[("OS", vec![record(1, "Present")]), ("DBMS", vec![record(2, "Present"), record(3, "Absent")])]))
+            .await
+            .unwrap();
+        assert_eq!(changes.subjects.len(), 1);
+        let dbms = &changes.subjects[0];
+        assert_eq!(dbms.subject, "DBMS");
+        assert_eq!(dbms.summary, "2 new lectures marked in DBMS, 1 absent");
+        assert_eq!(dbms.new_records[0].date, "2026-03-02");
+        assert!(dbms.state_changes.is_empty() && dbms.removed_records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn removed_subject_is_reported_unless_its_fetch_failed() {
+        let tracker = ChangeTracker::new();
+        let both = fetch(&[("OS", vec![record(1, "Present")]), ("DBMS", vec![record(2, "Absent")])]);
+        tracker.observe(&both).await;
+
+        let mut failed = fetch(&[("OS", vec![record(1, "Present")])]);
+        failed.failed_subjects.push("DBMS".to_string());
+        assert!(tracker.observe(&failed).await.is_none());
+
+        let changes = tracker.observe(&fetch(&[("OS", vec![record(1, "Present")])])).await.unwrap();
+        assert_eq!(changes.subjects[0].subject, "DBMS");
+        assert_eq!(changes.subjects[0].summary, "1 lecture removed from DBMS");
+        assert_eq!(changes.subjects[0].removed_records[0].state, "Absent");
+    }
+
+    #[tokio::test]
+    async fn changed_present_and_absent_counts() {
+        let tracker = ChangeTracker::new();
+        tracker.observe(&fetch(&[("OS", vec![record(1, "Present"), record(2, "Present")])])).await;
+
+        let changes = tracker
+            .observe(&fetch(&[("OS", vec![record(1, "Present"), record(2, "Absent"), record(3, "Absent")])]))
+            .await
+            .unwrap();
+        let os = &changes.subjects[0];
+        assert_eq!(os.summary, "1 new lecture marked in OS, 1 absent; 1 correction in OS");
+        assert_eq!(os.state_changes[0].previous_state, "Present");
+        assert_eq!(os.state_changes[0].state, "Absent");
+        assert_eq!(tracker.latest("2200320100001").await.unwrap().detected_at, changes.detected_at);
+
+        // A later fetch that finds nothing clears the latest changes but keeps the log
+        tracker.observe(&fetch(&[("OS", vec![record(1, "Present"), record(2, "Absent"), record(3, "Absent")])])).await;
+        assert!(tracker.latest("2200320100001").await.is_none());
+        assert_eq!(tracker.changes_since("2200320100001", None).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn undated_records_are_not_reported() {
+        let tracker = ChangeTracker::new();
+        tracker.observe(&fetch(&[("OS", vec![record(1, "Present")])])).await;
+
+        let undated = QuizRecord {
+            id: Some(serde_json::json!(9)),
+            state: "Absent".to_string(),
+            start_time: None,
+            date_formatted: None,
+        };
+        assert!(tracker.observe(&fetch(&[("OS", vec![record(1, "Present"), undated])])).await.is_none());
+    }
+}
@@ -0,0 +1,113 @@
+// Circuit breaker for portal calls. After enough consecutive failures (transport errors or
+// 5xx) calls fail fast until the open period passes; then one trial call is let through and
+// its outcome closes or re-opens the circuit.
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+enum Inner {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    // A trial is under way; another goes ahead after `retry_after` if it never reports back
+    HalfOpen { retry_after: Instant },
+}
+
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_for: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_for: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            open_for,
+            inner: Mutex::new(Inner::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may go ahead now.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        match *inner {
+            Inner::Closed { .. } => true,
+            Inner::Open { until } | Inner::HalfOpen { retry_after: until } if now < until => false,
+            _ => {
+                *inner = Inner::HalfOpen { retry_after: now + self.open_for };
+                true
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if !matches!(*inner, Inner::Closed { .. }) {
+            info!("[Circuit] Portal call succeeded, closing the circuit");
+        }
+        *inner = Inner::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let failures = match *inner {
+            Inner::Closed { failures } => failures + 1,
+            _ => self.failure_threshold,
+        };
+        if failures < self.failure_threshold {
+            *inner = Inner::Closed { failures };
+            return;
+        }
+        warn!("[Circuit] Portal failing, opening the circuit for {}s", self.open_for.as_secs());
+        *inner = Inner::Open { until: Instant::now() + self.open_for };
+    }
+
+    /// Open only while calls are being refused; half-open once a trial call may go ahead.
+    pub fn state(&self) -> CircuitState {
+        match *self.inner.lock().unwrap() {
+            Inner::Closed { .. } => CircuitState::Closed,
+            Inner::Open { until } if Instant::now() < until => CircuitState::Open,
+            _ => CircuitState::HalfOpen,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures_and_closes_after_a_good_trial() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(20));
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow());
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.allow());
+        assert!(!breaker.allow(), "only one trial at a time");
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
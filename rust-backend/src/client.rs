@@ -0,0 +1,178 @@
+use rayon::prelude::*;
+use serde_json::Value;
+
+use crate::{
+    aggregation::{self, FullAttendance},
+    config::Config,
+    error::AppError,
+    models::*,
+    redact::Secret,
+    services::ExternalApiService,
+};
+
+/// The production portal API.
+pub const DEFAULT_BASE_URL: &str = "https://abes.platform.simplifii.com/api/v1";
+
+/// Typed client for the ABES portal. One instance shares its HTTP connection pool, so build
+/// it once and reuse it.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), aims_backend::error::AppError> {
+/// let client = aims_backend::client::AimsClient::new(aims_backend::client::DEFAULT_BASE_URL);
+/// let login = client.login("2200320100001", "password").await?;
+/// let summary = client.attendance_summary(login.token.expose()).await?;
+/// println!("{}% overall", summary.overall_percentage);
+/// # Ok(())
+/// # }
+/// ```
+pub struct AimsClient {
+    api: ExternalApiService,
+    quiz_details_url: String,
+}
+
+/// A successful portal login.
+#[derive(Debug, Clone)]
+pub struct Login {
+    /// Portal token to pass to the other calls
+    pub token: Secret<String>,
+    /// The portal's login response with every token field removed
+    pub profile: Value,
+}
+
+impl AimsClient {
+    /// Client for the portal API at `base_url`, normally [`DEFAULT_BASE_URL`].
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            api: ExternalApiService::with_base_url(base_url),
+            quiz_details_url: String::new(),
+        }
+    }
+
+    // The server's client honours its upstream mode, cassettes and quiz details URL
+    pub(crate) fn from_config(config: &Config) -> Self {
+        Self {
+            api: ExternalApiService::new(config),
+            quiz_details_url: config.quiz_details_url.clone(),
+        }
+    }
+
+    pub(crate) fn circuit_state(&self) -> crate::circuit::CircuitState {
+        self.api.circuit_state()
+    }
+
+    pub(crate) async fn probe(&self) -> Result<(), AppError> {
+        self.api.probe().await
+    }
+
+    /// Logs in with portal credentials. Rejected credentials give
+    /// `AppError::AuthenticationError` with the portal's message.
+    pub async fn login(&self, username: &str, password: &str) -> Result<Login, AppError> {
+        let (status, mut profile) = self.api.authenticate(username, password).await?;
+
+        if !status.is_success() {
+            let message = profile["message"]
+                .as_str()
+                .or(profile["msg"].as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("Portal login failed with {}", status));
+            return Err(match status.as_u16() {
+                400 | 401 | 403 => AppError::AuthenticationError(message),
+                _ => AppError::ExternalApiError(message),
+            });
+        }
+
+        let token = take_upstream_token(&mut profile)
+            .ok_or_else(|| AppError::AuthenticationError("Portal login returned no token".to_string()))?;
+        Ok(Login {
+            token: Secret::new(token),
+            profile,
+        })
+    }
+
+    /// Per-course attendance percentages and the overall total.
+    pub async fn attendance_summary(&self, token: &str) -> Result<AttendanceResponse, AppError> {
+        let records = self.api.get_attendance_records(token).await?;
+
+        // The portal appends the overall total as the last record
+        let (total_summary, daily_records) = records
+            .split_last()
+            .filter(|(_, daily)| !daily.is_empty())
+            .ok_or_else(|| AppError::ExternalApiError("No attendance records returned".to_string()))?;
+
+        let daily_attendance = daily_records
+            .par_iter()
+            .map(|r| DailyAttendance {
+                course: r.cdata.course_name.trim().to_string(),
+                present: r.attendance_summary.Present,
+                total: r.attendance_summary.Total,
+                percent: r.attendance_summary.Percent,
+            })
+            .collect();
+
+        Ok(AttendanceResponse {
+            daily_attendance,
+            total_present: total_summary.attendance_summary.Present,
+            total_classes: total_summary.attendance_summary.Total,
+            overall_percentage: total_summary.attendance_summary.Percent,
+            batch: daily_records[0].batch.clone(),
+            section: daily_records[0].section.clone(),
+            branch: daily_records[0].dept.clone(),
+            student_id: daily_records[0].student_id.clone(),
+        })
+    }
+
+    /// Every attendance card of every subject. Subjects whose cards could not be fetched are
+    /// listed in `failed_subjects`; `aggregation::summarize` turns the result into totals.
+    pub async fn full_attendance(&self, token: &str) -> Result<FullAttendance, AppError> {
+        aggregation::fetch_full_attendance(&self.api, token).await
+    }
+
+    /// The student id (roll number) the token belongs to.
+    pub async fn student_id(&self, token: &str) -> Result<String, AppError> {
+        let records = self.api.get_attendance_records(token).await?;
+        records
+            .first()
+            .map(|record| record.student_id.clone())
+            .ok_or_else(|| AppError::ExternalApiError("No attendance records returned".to_string()))
+    }
+
+    /// Subjects the student is enrolled in.
+    pub async fn subjects(&self, token: &str) -> Result<Vec<Subject>, AppError> {
+        let subjects = self.api.get_subjects(token).await?;
+        Ok(subjects.iter().map(Subject::from).collect())
+    }
+
+    /// Attendance cards for one subject, newest first.
+    pub async fn subject_attendance(
+        &self,
+        token: &str,
+        subject: &Subject,
+        student_id: &str,
+    ) -> Result<Vec<QuizRecord>, AppError> {
+        self.api.fetch_subject_attendance(token, &subject.name, &subject.cf_id, student_id).await
+    }
+
+    /// Evaluated quizzes in the normalized schema.
+    pub async fn quizzes(&self, token: &str) -> Result<Vec<Quiz>, AppError> {
+        self.api.get_quiz_data(token).await
+    }
+
+    pub(crate) async fn quiz_details(&self, request: &StartQuizRequest) -> Result<Value, AppError> {
+        self.api.fetch_quiz_details(&self.quiz_details_url, request).await
+    }
+}
+
+// The portal has used several field names for the token over time; all are stripped
+fn take_upstream_token(data: &mut Value) -> Option<String> {
+    let mut found = Vec::new();
+    for field in ["token", "access_token", "authToken"] {
+        found.extend(data.as_object_mut().and_then(|o| o.remove(field)));
+    }
+    for field in ["token", "access_token"] {
+        found.extend(data.get_mut("response").and_then(|r| r.as_object_mut()).and_then(|o| o.remove(field)));
+    }
+
+    found
+        .into_iter()
+        .find_map(|value| value.as_str().filter(|t| !t.is_empty()).map(|t| t.to_string()))
+}
@@ -1,43 +1,603 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
 use anyhow::Result;
 
+use crate::{
+    cache_backend::BackendKind,
+    cassette::UpstreamMode,
+    client::DEFAULT_BASE_URL,
+    config_file::{self, FileValue},
+};
+
+const REDACTED: &str = "[REDACTED]";
+
+// Keys SIGHUP applies without a restart; everything else is read once at startup
+const RELOADABLE: &[&str] = &[
+    "cache_ttl_seconds",
+    "rate_limit_per_minute",
+    "cors_origin",
+    "poll_interval_seconds",
+    "min_poll_interval_seconds",
+];
+
+// Keys whose values `--print-config` never shows
+const SECRETS: &[&str] = &["subscription_encryption_key", "secrets_keys", "admin_token", "redis_url"];
+
+/// Where the configuration comes from. Later sources win: built-in defaults, the config
+/// file, environment variables (the key in upper case), then command-line overrides.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigSources {
+    pub file: Option<PathBuf>,
+    /// `(key, value)` pairs, e.g. from `--set port=8080`
+    pub overrides: Vec<(String, String)>,
+}
+
 #[derive(Clone, Debug)]
+#[allow(dead_code)]
 pub struct Config {
     pub host: String,
     pub port: u16,
     pub external_api_base: String,
-    pub cors_origin: String,
+    /// Origins allowed by CORS; comma separated in the environment
+    pub cors_origin: Vec<String>,
     pub rate_limit_per_minute: u32,
+    pub cache_ttl_seconds: u64,
+    pub cache_backend: BackendKind,
+    /// Required by the `redis` and `tiered` backends. May carry a password, so it is
+    /// redacted like the other secrets
+    pub redis_url: Option<String>,
+    /// How long the local tier of the `tiered` backend keeps an entry
+    pub cache_l1_ttl_seconds: u64,
+    /// Memory for in-process cache entries, counted as compressed bytes
+    pub cache_max_megabytes: u64,
     pub request_timeout_seconds: u64,
     pub max_concurrent_requests: usize,
+    /// How long shutdown waits for in-flight requests and background tasks
+    pub shutdown_grace_seconds: u64,
+    /// Consecutive portal failures that open the circuit, and how long it stays open
+    pub circuit_failure_threshold: u32,
+    pub circuit_open_seconds: u64,
+    pub health_probe_interval_seconds: u64,
+    /// Bearer token for `/admin`; the admin API is off when unset
+    pub admin_token: Option<String>,
+    pub history_db_path: String,
+    pub history_snapshot_interval_seconds: u64,
+    pub poll_interval_seconds: u64,
+    pub min_poll_interval_seconds: u64,
+    pub subscriptions_path: String,
+    pub calendar_feeds_path: String,
+    pub subscription_encryption_key: Option<String>,
+    pub secrets_keys: Option<String>,
+    pub secrets_key_file: Option<String>,
+    pub secrets_active_key_id: Option<String>,
+    pub enable_subscriptions: bool,
+    pub public_base_url: String,
+    pub webhook_max_retries: usize,
+    pub webhook_retry_base_delay_ms: u64,
+    pub webhook_dead_letter_path: String,
+    /// Hosts notifications may reach over http or at private addresses, such as a local
+    /// test sink; comma separated in the environment
+    pub webhook_allowed_hosts: Vec<String>,
+    pub quiz_details_url: String,
+    pub quiz_access_url: String,
+    pub session_ttl_seconds: u64,
+    pub session_idle_timeout_seconds: u64,
+    pub session_cookie_secure: bool,
+    pub legacy_body_token: bool,
+    pub upstream_mode: UpstreamMode,
+    pub cassette_dir: String,
 }
 
 impl Config {
-    pub fn from_env() -> Result<Self> {
+    /// Built-in defaults only, ignoring the environment; what tests start from.
+    pub fn defaults() -> Self {
+        Self::from_layers(Layers::defaults()).expect("built-in defaults are valid")
+    }
+
+    /// Reads every source and validates the result. All problems are reported together,
+    /// each naming the setting and where its value came from.
+    pub fn load(sources: &ConfigSources) -> Result<Self> {
+        Self::from_layers(Layers::read(sources)?)
+    }
+
+    fn from_layers(layers: Layers) -> Result<Self> {
+
+        let subscription_encryption_key = layers.optional("subscription_encryption_key");
+        let secrets_keys = layers.optional("secrets_keys");
+        let secrets_key_file = layers.optional("secrets_key_file");
+        let has_key = subscription_encryption_key.is_some() || secrets_keys.is_some() || secrets_key_file.is_some();
+
+        let config = Self {
+            host: layers.get("host", "127.0.0.1".to_string()),
+            port: layers.get("port", 3001),
+            external_api_base: layers.get("external_api_base", DEFAULT_BASE_URL.to_string()),
+            cors_origin: layers.get("cors_origin", vec!["http://localhost:3000".to_string()]),
+            rate_limit_per_minute: layers.get("rate_limit_per_minute", 100),
+            cache_ttl_seconds: layers.get("cache_ttl_seconds", 300),
+            cache_backend: layers.get("cache_backend", BackendKind::Memory),
+            redis_url: layers.optional("redis_url"),
+            cache_l1_ttl_seconds: layers.get("cache_l1_ttl_seconds", 30),
+            cache_max_megabytes: layers.get("cache_max_megabytes", 64),
+            request_timeout_seconds: layers.get("request_timeout_seconds", 10),
+            max_concurrent_requests: layers.get("max_concurrent_requests", 100),
+            shutdown_grace_seconds: layers.get("shutdown_grace_seconds", 30),
+            circuit_failure_threshold: layers.get("circuit_failure_threshold", 5),
+            circuit_open_seconds: layers.get("circuit_open_seconds", 30),
+            health_probe_interval_seconds: layers.get("health_probe_interval_seconds", 30),
+            admin_token: layers.optional("admin_token"),
+            history_db_path: layers.get("history_db_path", "data/attendance_history.db".to_string()),
+            history_snapshot_interval_seconds: layers.get("history_snapshot_interval_seconds", 21600),
+            poll_interval_seconds: layers.get("poll_interval_seconds", 900),
+            min_poll_interval_seconds: layers.get("min_poll_interval_seconds", 300),
+            subscriptions_path: layers.get("subscriptions_path", "data/subscriptions.json".to_string()),
+            calendar_feeds_path: layers.get("calendar_feeds_path", "data/calendar_feeds.json".to_string()),
+            subscription_encryption_key,
+            secrets_keys,
+            secrets_key_file,
+            secrets_active_key_id: layers.optional("secrets_active_key_id"),
+            // Stays on by default for deployments that already configured a key
+            enable_subscriptions: layers.get("enable_subscriptions", has_key),
+            public_base_url: layers.get("public_base_url", "http://localhost:3001".to_string()),
+            webhook_max_retries: layers.get("webhook_max_retries", 3),
+            webhook_retry_base_delay_ms: layers.get("webhook_retry_base_delay_ms", 1000),
+            webhook_dead_letter_path: layers.get("webhook_dead_letter_path", "data/webhook_dead_letter.jsonl".to_string()),
+            webhook_allowed_hosts: layers.get("webhook_allowed_hosts", Vec::new()),
+            quiz_details_url: layers.get(
+                "quiz_details_url",
+                "https://faas-blr1-8177d592.doserverless.co/api/v1/web/fn-1c23ee6f-939a-44b2-9c4e-d17970ddd644/abes/fetchQuizDetails".to_string(),
+            ),
+            quiz_access_url: layers.get("quiz_access_url", "https://abesquiz.netlify.app/#/access-quiz".to_string()),
+            session_ttl_seconds: layers.get("session_ttl_seconds", 43200),
+            session_idle_timeout_seconds: layers.get("session_idle_timeout_seconds", 7200),
+            session_cookie_secure: layers.get("session_cookie_secure", true),
+            legacy_body_token: layers.get("legacy_body_token", true),
+            upstream_mode: layers.get("upstream_mode", UpstreamMode::Live),
+            cassette_dir: layers.get("cassette_dir", "data/cassettes".to_string()),
+        };
+
+        let mut errors = layers.finish();
+        // Cross-field checks only make sense once every value parsed
+        if errors.is_empty() {
+            errors = config.validate();
+        }
+        if !errors.is_empty() {
+            anyhow::bail!("invalid configuration:\n  - {}", errors.join("\n  - "));
+        }
+        Ok(config)
+    }
+
+    pub fn has_secrets_key(&self) -> bool {
+        self.secrets_keys.is_some() || self.secrets_key_file.is_some() || self.subscription_encryption_key.is_some()
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, message: String| {
+            if !ok {
+                errors.push(message);
+            }
+        };
+
+        // Features that persist tokens must not start without a key to encrypt them
+        check(
+            !self.enable_subscriptions || self.has_secrets_key(),
+            "enable_subscriptions requires an encryption key (secrets_key_file, secrets_keys or subscription_encryption_key)".to_string(),
+        );
+        for (key, value) in [
+            ("rate_limit_per_minute", self.rate_limit_per_minute as u64),
+            ("cache_ttl_seconds", self.cache_ttl_seconds),
+            ("cache_l1_ttl_seconds", self.cache_l1_ttl_seconds),
+            ("cache_max_megabytes", self.cache_max_megabytes),
+            ("request_timeout_seconds", self.request_timeout_seconds),
+            ("max_concurrent_requests", self.max_concurrent_requests as u64),
+            ("shutdown_grace_seconds", self.shutdown_grace_seconds),
+            ("circuit_failure_threshold", self.circuit_failure_threshold as u64),
+            ("circuit_open_seconds", self.circuit_open_seconds),
+            ("health_probe_interval_seconds", self.health_probe_interval_seconds),
+            ("session_ttl_seconds", self.session_ttl_seconds),
+        ] {
+            check(value > 0, format!("{} must be greater than 0", key));
+        }
+        check(
+            self.min_poll_interval_seconds <= self.poll_interval_seconds,
+            format!(
+                "poll_interval_seconds ({}) must not be below min_poll_interval_seconds ({})",
+                self.poll_interval_seconds, self.min_poll_interval_seconds
+            ),
+        );
+        check(
+            self.session_idle_timeout_seconds <= self.session_ttl_seconds,
+            format!(
+                "session_idle_timeout_seconds ({}) must not exceed session_ttl_seconds ({})",
+                self.session_idle_timeout_seconds, self.session_ttl_seconds
+            ),
+        );
+        for (key, value) in [
+            ("external_api_base", &self.external_api_base),
+            ("public_base_url", &self.public_base_url),
+            ("quiz_details_url", &self.quiz_details_url),
+            ("quiz_access_url", &self.quiz_access_url),
+        ] {
+            check(is_http_url(value), format!("{} must be an http(s) URL, got '{}'", key, value));
+        }
+        check(
+            self.cache_backend == BackendKind::Memory || cfg!(feature = "redis"),
+            format!("cache_backend {} needs a build with --features redis", self.cache_backend.as_str()),
+        );
+        check(
+            self.cache_backend == BackendKind::Memory
+                || self.redis_url.as_ref().is_some_and(|url| url.starts_with("redis://")),
+            format!("cache_backend {} needs a redis_url starting with redis://", self.cache_backend.as_str()),
+        );
+        check(
+            self.admin_token.as_ref().is_none_or(|token| token.len() >= 32),
+            "admin_token must be at least 32 characters".to_string(),
+        );
+        check(!self.cors_origin.is_empty(), "cors_origin must list at least one origin".to_string());
+        for origin in &self.cors_origin {
+            check(
+                is_origin(origin),
+                format!("cors_origin entry '{}' must be a scheme and host like https://aims.example", origin),
+            );
+        }
+
+        errors
+    }
+
+    /// The hot-reloadable part of the configuration.
+    pub fn tunables(&self) -> Tunables {
+        Tunables {
+            cache_ttl_seconds: self.cache_ttl_seconds,
+            rate_limit_per_minute: self.rate_limit_per_minute,
+            cors_origins: self.cors_origin.clone(),
+            poll_interval_seconds: self.poll_interval_seconds,
+            min_poll_interval_seconds: self.min_poll_interval_seconds,
+        }
+    }
+
+    /// The effective configuration as a config file, with secrets redacted.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::from("# Effective configuration; secrets are redacted\n");
+        for (key, value) in self.entries() {
+            match value {
+                Some(_) if SECRETS.contains(&key) => out.push_str(&format!("{} = \"{}\"\n", key, REDACTED)),
+                Some(value) => out.push_str(&format!("{} = {}\n", key, value)),
+                None => out.push_str(&format!("# {} is not set\n", key)),
+            }
+        }
+        out
+    }
+
+    /// Keys that differ from `other` but only take effect after a restart.
+    pub fn restart_required(&self, other: &Config) -> Vec<&'static str> {
+        self.entries()
+            .into_iter()
+            .zip(other.entries())
+            .filter(|((key, old), (_, new))| old != new && !RELOADABLE.contains(key))
+            .map(|((key, _), _)| key)
+            .collect()
+    }
+
+    // Every setting with its value written as TOML; `None` when unset
+    fn entries(&self) -> Vec<(&'static str, Option<String>)> {
+        let string = |value: &str| Some(toml_string(value));
+        let number = |value: u64| Some(value.to_string());
+        let flag = |value: bool| Some(value.to_string());
+        let optional = |value: &Option<String>| value.as_deref().map(toml_string);
+
+        vec![
+            ("host", string(&self.host)),
+            ("port", number(self.port as u64)),
+            ("external_api_base", string(&self.external_api_base)),
+            ("cors_origin", Some(format!("[{}]", self.cors_origin.iter().map(|o| toml_string(o)).collect::<Vec<_>>().join(", ")))),
+            ("rate_limit_per_minute", number(self.rate_limit_per_minute as u64)),
+            ("cache_ttl_seconds", number(self.cache_ttl_seconds)),
+            ("cache_backend", string(self.cache_backend.as_str())),
+            ("redis_url", optional(&self.redis_url)),
+            ("cache_l1_ttl_seconds", number(self.cache_l1_ttl_seconds)),
+            ("cache_max_megabytes", number(self.cache_max_megabytes)),
+            ("request_timeout_seconds", number(self.request_timeout_seconds)),
+            ("max_concurrent_requests", number(self.max_concurrent_requests as u64)),
+            ("shutdown_grace_seconds", number(self.shutdown_grace_seconds)),
+            ("circuit_failure_threshold", number(self.circuit_failure_threshold as u64)),
+            ("circuit_open_seconds", number(self.circuit_open_seconds)),
+            ("health_probe_interval_seconds", number(self.health_probe_interval_seconds)),
+            ("admin_token", optional(&self.admin_token)),
+            ("history_db_path", string(&self.history_db_path)),
+            ("history_snapshot_interval_seconds", number(self.history_snapshot_interval_seconds)),
+            ("poll_interval_seconds", number(self.poll_interval_seconds)),
+            ("min_poll_interval_seconds", number(self.min_poll_interval_seconds)),
+            ("subscriptions_path", string(&self.subscriptions_path)),
+            ("calendar_feeds_path", string(&self.calendar_feeds_path)),
+            ("subscription_encryption_key", optional(&self.subscription_encryption_key)),
+            ("secrets_keys", optional(&self.secrets_keys)),
+            ("secrets_key_file", optional(&self.secrets_key_file)),
+            ("secrets_active_key_id", optional(&self.secrets_active_key_id)),
+            ("enable_subscriptions", flag(self.enable_subscriptions)),
+            ("public_base_url", string(&self.public_base_url)),
+            ("webhook_max_retries", number(self.webhook_max_retries as u64)),
+            ("webhook_retry_base_delay_ms", number(self.webhook_retry_base_delay_ms)),
+            ("webhook_dead_letter_path", string(&self.webhook_dead_letter_path)),
+            ("webhook_allowed_hosts", Some(format!("[{}]", self.webhook_allowed_hosts.iter().map(|h| toml_string(h)).collect::<Vec<_>>().join(", ")))),
+            ("quiz_details_url", string(&self.quiz_details_url)),
+            ("quiz_access_url", string(&self.quiz_access_url)),
+            ("session_ttl_seconds", number(self.session_ttl_seconds)),
+            ("session_idle_timeout_seconds", number(self.session_idle_timeout_seconds)),
+            ("session_cookie_secure", flag(self.session_cookie_secure)),
+            ("legacy_body_token", flag(self.legacy_body_token)),
+            ("upstream_mode", string(self.upstream_mode.as_str())),
+            ("cassette_dir", string(&self.cassette_dir)),
+        ]
+    }
+}
+
+/// Settings that can change while the server runs (see `RELOADABLE`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tunables {
+    pub cache_ttl_seconds: u64,
+    pub rate_limit_per_minute: u32,
+    pub cors_origins: Vec<String>,
+    pub poll_interval_seconds: u64,
+    pub min_poll_interval_seconds: u64,
+}
+
+/// The current `Tunables`, swapped as a whole on reload so readers never see a mix.
+pub struct LiveTunables {
+    current: RwLock<Arc<Tunables>>,
+}
+
+impl LiveTunables {
+    pub fn new(tunables: Tunables) -> Self {
+        Self { current: RwLock::new(Arc::new(tunables)) }
+    }
+
+    pub fn get(&self) -> Arc<Tunables> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn set(&self, tunables: Tunables) {
+        *self.current.write().unwrap() = Arc::new(tunables);
+    }
+}
+
+// A type a setting can be parsed into, with what a valid value looks like
+trait Setting: Sized {
+    const EXPECTED: &'static str;
+    fn parse(text: &str) -> Option<Self>;
+}
+
+impl Setting for String {
+    const EXPECTED: &'static str = "a string";
+    fn parse(text: &str) -> Option<Self> {
+        Some(text.to_string())
+    }
+}
+
+impl Setting for Vec<String> {
+    const EXPECTED: &'static str = "a comma separated list";
+    fn parse(text: &str) -> Option<Self> {
+        Some(text.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from).collect())
+    }
+}
+
+impl Setting for bool {
+    const EXPECTED: &'static str = "true or false";
+    fn parse(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+}
+
+impl Setting for UpstreamMode {
+    const EXPECTED: &'static str = "live, record or replay";
+    fn parse(text: &str) -> Option<Self> {
+        UpstreamMode::parse(text)
+    }
+}
+
+impl Setting for BackendKind {
+    const EXPECTED: &'static str = "memory, redis or tiered";
+    fn parse(text: &str) -> Option<Self> {
+        BackendKind::parse(text)
+    }
+}
+
+macro_rules! integer_setting {
+    ($($ty:ty => $expected:literal),*) => {$(
+        impl Setting for $ty {
+            const EXPECTED: &'static str = $expected;
+            fn parse(text: &str) -> Option<Self> {
+                text.parse().ok()
+            }
+        }
+    )*};
+}
+
+integer_setting!(
+    u16 => "an integer from 0 to 65535",
+    u32 => "a whole number from 0 to 4294967295",
+    u64 => "a non-negative whole number",
+    usize => "a non-negative whole number"
+);
+
+// The sources of one load. Records which keys were read and every value that failed to parse.
+struct Layers {
+    file: HashMap<String, FileValue>,
+    file_name: String,
+    overrides: Vec<(String, String)>,
+    environment: bool,
+    read: RefCell<Vec<&'static str>>,
+    errors: RefCell<Vec<String>>,
+}
+
+impl Layers {
+    fn read(sources: &ConfigSources) -> Result<Self> {
+        let file = match &sources.file {
+            Some(path) => config_file::read(path)?,
+            None => HashMap::new(),
+        };
+
         Ok(Self {
-            host: env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
-            port: env::var("PORT")
-                .unwrap_or_else(|_| "3001".to_string())
-                .parse()
-                .unwrap_or(3001),
-            external_api_base: env::var("EXTERNAL_API_BASE")
-                .unwrap_or_else(|_| "https://abes.platform.simplifii.com/api/v1".to_string()),
-            cors_origin: env::var("CORS_ORIGIN")
-                .unwrap_or_else(|_| "http://localhost:3000".to_string()),
-            rate_limit_per_minute: env::var("RATE_LIMIT_PER_MINUTE")
-                .unwrap_or_else(|_| "100".to_string())
-                .parse()
-                .unwrap_or(100),
-            request_timeout_seconds: env::var("REQUEST_TIMEOUT_SECONDS")
-                .unwrap_or_else(|_| "10".to_string())
-                .parse()
-                .unwrap_or(10),
-            max_concurrent_requests: env::var("MAX_CONCURRENT_REQUESTS")
-                .unwrap_or_else(|_| "100".to_string())
-                .parse()
-                .unwrap_or(100),
+            file,
+            file_name: sources.file.as_ref().map(|path| path.display().to_string()).unwrap_or_default(),
+            overrides: sources.overrides.clone(),
+            environment: true,
+            read: RefCell::new(Vec::new()),
+            errors: RefCell::new(Vec::new()),
+        })
+    }
+
+    fn defaults() -> Self {
+        Self {
+            file: HashMap::new(),
+            file_name: String::new(),
+            overrides: Vec::new(),
+            environment: false,
+            read: RefCell::new(Vec::new()),
+            errors: RefCell::new(Vec::new()),
+        }
+    }
+
+    // The winning raw value for `key` and a description of where it came from
+    fn raw(&self, key: &'static str) -> Option<(String, String)> {
+        self.read.borrow_mut().push(key);
+
+        if let Some((_, value)) = self.overrides.iter().rev().find(|(name, _)| name == key) {
+            return Some((value.clone(), format!("--set {}", key)));
+        }
+        let name = key.to_uppercase();
+        if let Some(value) = env::var(&name).ok().filter(|_| self.environment) {
+            return Some((value, format!("environment variable {}", name)));
+        }
+        self.file
+            .get(key)
+            .map(|value| (value.text.clone(), format!("`{}` in {} line {}", key, self.file_name, value.line)))
+    }
+
+    fn get<T: Setting>(&self, key: &'static str, default: T) -> T {
+        let Some((text, origin)) = self.raw(key) else {
+            return default;
+        };
+        T::parse(text.trim()).unwrap_or_else(|| {
+            self.errors
+                .borrow_mut()
+                .push(format!("{}: '{}' is not {}", origin, text, T::EXPECTED));
+            default
         })
     }
+
+    // Empty values count as unset
+    fn optional(&self, key: &'static str) -> Option<String> {
+        self.raw(key).map(|(text, _)| text).filter(|text| !text.trim().is_empty())
+    }
+
+    // Parse errors plus any file or override key that is not a setting
+    fn finish(self) -> Vec<String> {
+        let read = self.read.into_inner();
+        let mut errors = self.errors.into_inner();
+
+        let mut unknown: Vec<_> = self.file.iter().filter(|(key, _)| !read.contains(&key.as_str())).collect();
+        unknown.sort_by_key(|(_, value)| value.line);
+        for (key, value) in unknown {
+            errors.push(format!("unknown setting `{}` in {} line {}", key, self.file_name, value.line));
+        }
+        for (key, _) in &self.overrides {
+            if !read.contains(&key.as_str()) {
+                errors.push(format!("unknown setting `{}` in --set", key));
+            }
+        }
+        errors
+    }
+}
+
+fn toml_string(value: &str) -> String {
+    // JSON string escaping is valid TOML basic-string escaping
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn is_http_url(value: &str) -> bool {
+    url::Url::parse(value).is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
 }
 
+fn is_origin(value: &str) -> bool {
+    url::Url::parse(value).is_ok_and(|url| {
+        matches!(url.scheme(), "http" | "https") && url.origin().ascii_serialization() == value.trim_end_matches('/')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_file(contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("aims-config-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn layers_file_then_overrides() {
+        let path = write_file("rate_limit_per_minute = 30\ncors_origin = [\"https://aims.example\"]\ncache_ttl_seconds = 60\n");
+        let sources = ConfigSources {
+            file: Some(path.clone()),
+            overrides: vec![("cache_ttl_seconds".to_string(), "120".to_string())],
+        };
+
+        let config = Config::load(&sources).unwrap();
+        assert_eq!(config.rate_limit_per_minute, 30);
+        assert_eq!(config.cors_origin, vec!["https://aims.example"]);
+        assert_eq!(config.cache_ttl_seconds, 120);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reports_every_bad_value_with_its_source() {
+        let path = write_file("port = \"abc\"\nsession_ttl = 5\nupstream_mode = \"mock\"\n");
+        let sources = ConfigSources {
+            file: Some(path.clone()),
+            overrides: vec![("rate_limit_per_minute".to_string(), "-1".to_string())],
+        };
+
+        let error = Config::load(&sources).unwrap_err().to_string();
+        let file = path.display();
+        for expected in [
+            format!("`port` in {} line 1: 'abc' is not an integer from 0 to 65535", file),
+            format!("`upstream_mode` in {} line 3: 'mock' is not live, record or replay", file),
+            "--set rate_limit_per_minute: '-1' is not a whole number".to_string(),
+            format!("unknown setting `session_ttl` in {} line 2", file),
+        ] {
+            assert!(error.contains(&expected), "missing {:?} in:\n{}", expected, error);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn validates_across_settings_and_redacts_secrets() {
+        let overrides = |pairs: &[(&str, &str)]| ConfigSources {
+            file: None,
+            overrides: pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        };
+
+        let error = Config::load(&overrides(&[
+            ("poll_interval_seconds", "60"),
+            ("cors_origin", "https://aims.example/app"),
+        ]))
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("poll_interval_seconds (60) must not be below min_poll_interval_seconds (300)"));
+        assert!(error.contains("cors_origin entry 'https://aims.example/app'"));
 
+        let config = Config::load(&overrides(&[("secrets_keys", "k1:c2VjcmV0")])).unwrap();
+        let printed = config.to_toml();
+        assert!(printed.contains("secrets_keys = \"[REDACTED]\"") && !printed.contains("c2VjcmV0"));
+        assert!(printed.contains("# subscription_encryption_key is not set"));
+
+        // The printed form reads back to the same configuration
+        let path = write_file(&printed.replace("\"[REDACTED]\"", "\"k1:c2VjcmV0\""));
+        let reread = Config::load(&ConfigSources { file: Some(path.clone()), overrides: Vec::new() }).unwrap();
+        assert!(config.restart_required(&reread).is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+// Reader for the config file: TOML with every setting as a top-level key. Values are turned
+// into the text the environment variable would carry, so all layers parse the same way.
+use std::collections::HashMap;
+use std::path::Path;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use toml::{Spanned, Value};
+
+/// A value from the file, already in the textual form the environment variable would use.
+/// Arrays are joined with commas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileValue {
+    pub text: String,
+    pub line: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct ConfigFile {
+    settings: HashMap<String, Spanned<Value>>,
+}
+
+pub fn read(path: &Path) -> Result<HashMap<String, FileValue>> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("cannot read config file {}", path.display()))?;
+    parse(&source).with_context(|| format!("invalid config file {}", path.display()))
+}
+
+pub fn parse(source: &str) -> Result<HashMap<String, FileValue>> {
+    let file: ConfigFile = toml::from_str(source)?;
+
+    let mut values = HashMap::new();
+    for (key, value) in file.settings {
+        let line = source[..value.span().start].matches('\n').count() + 1;
+        let text = match value.into_inner() {
+            Value::Array(items) => items
+                .into_iter()
+                .map(scalar)
+                .collect::<Option<Vec<_>>>()
+                .map(|items| items.join(",")),
+            value => scalar(value),
+        };
+        let Some(text) = text else {
+            bail!("line {}: `{}` must be a string, number, boolean or array of those; tables are not supported", line, key);
+        };
+        values.insert(key, FileValue { text, line });
+    }
+
+    Ok(values)
+}
+
+fn scalar(value: Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text),
+        Value::Integer(number) => Some(number.to_string()),
+        Value::Float(number) => Some(number.to_string()),
+        Value::Boolean(flag) => Some(flag.to_string()),
+        Value::Datetime(_) | Value::Array(_) | Value::Table(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_flat_settings() {
+        let values = parse(
+            r#"
+            # Server
+            host = "0.0.0.0"      # all interfaces
+            port = 8_080
+            session_cookie_secure = false
+            cors_origin = ["https://aims.example", 'http://localhost:3000',]
+            quiz_access_url = "https://quiz.example/#/access"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(values["host"].text, "0.0.0.0");
+        assert_eq!(values["port"], FileValue { text: "8080".to_string(), line: 4 });
+        assert_eq!(values["session_cookie_secure"].text, "false");
+        assert_eq!(values["cors_origin"].text, "https://aims.example,http://localhost:3000");
+        assert_eq!(values["quiz_access_url"].text, "https://quiz.example/#/access");
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_read_with_the_line() {
+        for (source, message) in [
+            ("[server]\nport = 1", "line 1: `server` must be a string"),
+            ("host = \"a\"\nport 3001", "TOML parse error at line 2"),
+            ("host = localhost", "TOML parse error at line 1"),
+            ("port = 1\nport = 2", "TOML parse error at line 2"),
+            ("host = \"open", "TOML parse error at line 1"),
+        ] {
+            let error = parse(source).unwrap_err().to_string();
+            assert!(error.starts_with(message), "{:?} gave {}", source, error);
+        }
+    }
+}
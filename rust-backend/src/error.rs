@@ -2,6 +2,7 @@ use axum::response::IntoResponse;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names, dead_code)]
 pub enum AppError {
     #[error("External API error: {0}")]
     ExternalApiError(String),
@@ -9,6 +10,12 @@ pub enum AppError {
     #[error("Authentication failed: {0}")]
     AuthenticationError(String),
 
+    #[error("Authentication required: {0}")]
+    MissingCredentials(String),
+
+    #[error("Upstream token rejected: {0}")]
+    UpstreamUnauthorized(String),
+
     #[error("Invalid request: {0}")]
     ValidationError(String),
 
@@ -18,6 +25,12 @@ pub enum AppError {
     #[error("Timeout error: {0}")]
     TimeoutError(String),
 
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
     #[error("Rate limit exceeded")]
     RateLimitError,
 
@@ -36,9 +49,13 @@ impl AppError {
         match self {
             AppError::ExternalApiError(_) => axum::http::StatusCode::BAD_GATEWAY,
             AppError::AuthenticationError(_) => axum::http::StatusCode::UNAUTHORIZED,
+            AppError::MissingCredentials(_) => axum::http::StatusCode::UNAUTHORIZED,
+            AppError::UpstreamUnauthorized(_) => axum::http::StatusCode::UNAUTHORIZED,
             AppError::ValidationError(_) => axum::http::StatusCode::BAD_REQUEST,
             AppError::CacheError(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
             AppError::TimeoutError(_) => axum::http::StatusCode::REQUEST_TIMEOUT,
+            AppError::NotFound(_) => axum::http::StatusCode::NOT_FOUND,
+            AppError::ServiceUnavailable(_) => axum::http::StatusCode::SERVICE_UNAVAILABLE,
             AppError::RateLimitError => axum::http::StatusCode::TOO_MANY_REQUESTS,
             AppError::InternalError(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
             AppError::SerializationError(_) => axum::http::StatusCode::BAD_REQUEST,
@@ -56,6 +73,23 @@ impl IntoResponse for AppError {
             timestamp: chrono::Utc::now(),
         };
 
-        (status, axum::Json(error_response)).into_response()
+        let mut response = (status, axum::Json(error_response)).into_response();
+
+        // RFC 6750: every 401 names the Bearer scheme, with an error code once a token was tried
+        let challenge = match self {
+            AppError::MissingCredentials(_) => Some("Bearer realm=\"aims\""),
+            AppError::AuthenticationError(_) | AppError::UpstreamUnauthorized(_) => {
+                Some("Bearer realm=\"aims\", error=\"invalid_token\"")
+            }
+            _ => None,
+        };
+        if let Some(challenge) = challenge {
+            response.headers_mut().insert(
+                axum::http::header::WWW_AUTHENTICATE,
+                axum::http::HeaderValue::from_static(challenge),
+            );
+        }
+
+        response
     }
 }
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use axum::body::Bytes;
+use rust_xlsxwriter::{Format, Workbook};
+
+use crate::{error::AppError, models::AllAttendanceResponse};
+
+const HEADERS: [&str; 5] = ["Subject", "Course Code", "Date", "Present", "Absent"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn parse(raw: Option<&str>) -> Result<Self, AppError> {
+        match raw.unwrap_or("csv").to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "xlsx" => Ok(ExportFormat::Xlsx),
+            other => Err(AppError::ValidationError(format!("Unsupported export format: {}", other))),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+pub struct ExportRow {
+    pub subject: String,
+    pub course_code: String,
+    pub date: String,
+    pub present: i32,
+    pub absent: i32,
+}
+
+/// One row per subject and date, ordered by subject then date.
+pub fn flatten(data: &AllAttendanceResponse) -> Vec<ExportRow> {
+    // course_code_map is code -> name; exports need the reverse
+    let codes: HashMap<&str, &str> = data
+        .course_code_map
+        .iter()
+        .map(|(code, name)| (name.as_str(), code.as_str()))
+        .collect();
+
+    let mut subjects: Vec<_> = data.subjects.iter().collect();
+    subjects.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut rows = Vec::new();
+    for (name, summary) in subjects {
+        let mut daily: Vec<_> = summary.daily.iter().collect();
+        daily.sort_by(|a, b| a.date.cmp(&b.date));
+
+        for day in daily {
+            rows.push(ExportRow {
+                subject: name.clone(),
+                course_code: codes.get(name.as_str()).copied().unwrap_or_default().to_string(),
+                date: day.date.clone(),
+                present: day.present,
+                absent: day.absent,
+            });
+        }
+    }
+
+    rows
+}
+
+/// CSV split into chunks so the response body can be streamed.
+pub fn csv_chunks(rows: Vec<ExportRow>) -> impl Iterator<Item = Bytes> {
+    let header = std::iter::once(Bytes::from(format!("{}\r\n", HEADERS.join(","))));
+
+    let body = rows.into_iter().map(|row| {
+        Bytes::from(format!(
+            "{},{},{},{},{}\r\n",
+            csv_field(&row.subject),
+            csv_field(&row.course_code),
+            csv_field(&row.date),
+            row.present,
+            row.absent
+        ))
+    });
+
+    header.chain(body)
+}
+
+// Quotes per RFC 4180 and neutralises spreadsheet formula prefixes
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+pub fn to_xlsx(data: &AllAttendanceResponse, rows: &[ExportRow]) -> Result<Vec<u8>, AppError> {
+    let xlsx_error = |e: rust_xlsxwriter::XlsxError| AppError::InternalError(format!("XLSX export failed: {}", e));
+
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let percent = Format::new().set_num_format("0.0%");
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Attendance").map_err(xlsx_error)?;
+    for (col, header) in HEADERS.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *header, &bold).map_err(xlsx_error)?;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let r = i as u32 + 1;
+        sheet.write_string(r, 0, &row.subject).map_err(xlsx_error)?;
+        sheet.write_string(r, 1, &row.course_code).map_err(xlsx_error)?;
+        sheet.write_string(r, 2, &row.date).map_err(xlsx_error)?;
+        sheet.write_number(r, 3, row.present).map_err(xlsx_error)?;
+        sheet.write_number(r, 4, row.absent).map_err(xlsx_error)?;
+    }
+    sheet.set_column_width(0, 40).map_err(xlsx_error)?;
+    sheet.set_column_width(1, 14).map_err(xlsx_error)?;
+    sheet.set_column_width(2, 12).map_err(xlsx_error)?;
+
+    let summary = workbook.add_worksheet();
+    summary.set_name("Summary").map_err(xlsx_error)?;
+    for (col, header) in ["Subject", "Present", "Absent", "Percentage"].iter().enumerate() {
+        summary.write_string_with_format(0, col as u16, *header, &bold).map_err(xlsx_error)?;
+    }
+
+    let mut subjects: Vec<_> = data.subjects.iter().collect();
+    subjects.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut r = 1;
+    for (name, totals) in subjects {
+        write_summary_row(summary, r, name, totals.total_present, totals.total_absent, &percent).map_err(xlsx_error)?;
+        r += 1;
+    }
+    write_summary_row(
+        summary,
+        r,
+        "Total",
+        data.total_present_all_subjects,
+        data.total_absent_all_subjects,
+        &percent,
+    )
+    .map_err(xlsx_error)?;
+    summary.set_column_width(0, 40).map_err(xlsx_error)?;
+
+    workbook.save_to_buffer().map_err(xlsx_error)
+}
+
+fn write_summary_row(
+    sheet: &mut rust_xlsxwriter::Worksheet,
+    row: u32,
+    name: &str,
+    present: i32,
+    absent: i32,
+    percent: &Format,
+) -> Result<(), rust_xlsxwriter::XlsxError> {
+    let total = present + absent;
+    sheet.write_string(row, 0, name)?;
+    sheet.write_number(row, 1, present)?;
+    sheet.write_number(row, 2, absent)?;
+    if total > 0 {
+        sheet.write_number_with_format(row, 3, present as f64 / total as f64, percent)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DailyAttendanceRecord, SubjectSummary};
+
+    #[test]
+    fn escapes_csv_fields() {
+        assert_eq!(csv_field("DBMS"), "DBMS");
+        assert_eq!(csv_field("Lab, \"A\""), "\"Lab, \"\"A\"\"\"");
+        assert_eq!(csv_field("=HYPERLINK(1)"), "'=HYPERLINK(1)");
+    }
+
+    #[test]
+    fn flattens_subjects_with_course_codes() {
+        let daily = |date: &str, present, absent| DailyAttendanceRecord {
+            date: date.to_string(),
+            present,
+            absent,
+        };
+        let data = AllAttendanceResponse {
+            student_id: "42".to_string(),
+            total_present_all_subjects: 3,
+            total_absent_all_subjects: 1,
+            subjects: HashMap::from([
+                ("OS".to_string(), SubjectSummary {
+                    total_present: 1,
+                    total_absent: 0,
+                    daily: vec![daily("2024-01-02", 1, 0)],
+                }),
+                ("DBMS".to_string(), SubjectSummary {
+                    total_present: 2,
+                    total_absent: 1,
+                    daily: vec![daily("2024-01-03", 1, 1), daily("2024-01-01", 1, 0)],
+                }),
+            ]),
+            course_code_map: HashMap::from([("BCS501".to_string(), "DBMS".to_string())]),
+            cached_at: chrono::Utc::now(),
+            performance: None,
+            changes: None,
+        };
+
+        let rows = flatten(&data);
+        let keys: Vec<_> = rows.iter().map(|r| (r.subject.as_str(), r.course_code.as_str(), r.date.as_str())).collect();
+        assert_eq!(keys, vec![
+            ("DBMS", "BCS501", "2024-01-01"),
+            ("DBMS", "BCS501", "2024-01-03"),
+            ("OS", "", "2024-01-02"),
+        ]);
+
+        let csv: Vec<u8> = csv_chunks(rows).flat_map(|b| b.to_vec()).collect();
+        assert!(String::from_utf8(csv).unwrap().starts_with("Subject,Course Code,Date,Present,Absent\r\nDBMS,BCS501,2024-01-01,1,0\r\n"));
+        assert!(!to_xlsx(&data, &flatten(&data)).unwrap().is_empty());
+    }
+}
@@ -1,19 +1,26 @@
 use axum::{
+    body::Body,
     extract::State,
-    http::{HeaderMap, StatusCode},
+    http::{header, HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
 use std::time::Instant;
 use tracing::{error, info, warn};
-use rayon::prelude::*;
-use std::collections::HashMap;
 
 use crate::{
+    aggregation,
+    auth::UpstreamToken,
+    cache,
+    calendar,
     error::AppError,
+    export,
     models::*,
-    services::ExternalApiService,
-    AppState,
+    notifications,
+    quiz,
+    report,
+    server::AppState,
+    sessions,
 };
 
 pub async fn login_handler(
@@ -24,40 +31,27 @@ pub async fn login_handler(
     
     info!("[login] start");
     
-    let result = async {
-        let form_data = format!("username={}&password={}", 
-            urlencoding::encode(&payload.username), 
-            urlencoding::encode(&payload.password)
-        );
-
-        let client = reqwest::Client::new();
-        let response = client
-            .post("https://abes.platform.simplifii.com/api/v1/admin/authenticate")
-            .header("Content-Type", "application/x-www-form-urlencoded")
-            .header("Origin", "https://abes.web.simplifii.com")
-            .header("Referer", "https://abes.web.simplifii.com/")
-            .body(form_data)
-            .timeout(std::time::Duration::from_secs(state.config.request_timeout_seconds))
-            .send()
-            .await?;
-
-        let status = response.status();
-        let data: serde_json::Value = response.json().await?;
-
-        Ok::<_, AppError>((status, data))
-    }.await;
+    let result = state.client.login(&payload.username, payload.password.expose()).await;
 
     match result {
-        Ok((status, data)) => {
+        Ok(login) => {
             let duration = start_time.elapsed().as_millis() as u64;
             state.performance_monitor.record_request("login", duration, "success").await;
             
             info!("[login] completed in {}ms", duration);
+
+            // Keep the portal token server-side and hand out an opaque session cookie instead
+            let credentials = payload.remember.then_some(&payload);
+            let session_id = state.sessions.create(login.token.clone(), credentials).await;
+            let cookie = state.sessions.cookie(&session_id);
+            let mut headers = HeaderMap::new();
+            headers.insert(header::SET_COOKIE, cookie.parse().map_err(|_| {
+                AppError::InternalError("Invalid session cookie".to_string())
+            })?);
             
-            Ok((StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::OK), Json(data)))
+            Ok((StatusCode::OK, headers, Json(login.profile)))
         }
         Err(e) => {
-            let duration = start_time.elapsed().as_millis() as u64;
             state.performance_monitor.record_error("login", &e.to_string()).await;
             
             error!("[login] error: {}", e);
@@ -66,23 +60,56 @@ pub async fn login_handler(
     }
 }
 
+pub async fn logout_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(id) = sessions::session_id(&headers) {
+        if state.sessions.remove(id).await {
+            info!("[logout] session ended");
+        }
+    }
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, state.sessions.expired_cookie())],
+    ))
+}
+
+pub async fn session_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let session = match sessions::session_id(&headers) {
+        Some(id) => state.sessions.get(id).await,
+        None => None,
+    }
+    .ok_or_else(|| AppError::AuthenticationError("No active session".to_string()))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(SessionResponse {
+            authenticated: true,
+            created_at: session.created_at,
+            reauth_enabled: session.can_reauthenticate(),
+        }),
+    ))
+}
+
 pub async fn attendance_handler(
     State(state): State<AppState>,
+    auth: Result<UpstreamToken, AppError>,
     Json(payload): Json<AttendanceRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let start_time = Instant::now();
     
     info!("[attendance] start");
 
-    if payload.token.is_empty() {
-        warn!("[attendance] missing token");
-        return Err(AppError::ValidationError("Missing token in request body".to_string()));
-    }
-
-    info!("[attendance] token: {}...", &payload.token[..std::cmp::min(10, payload.token.len())]);
+    let auth = UpstreamToken::with_body_token(auth, payload.token, &state.config)?;
+    let token = auth.token();
 
     // Check cache first
-    let cache_key = format!("attendance_{}", &payload.token[..std::cmp::min(10, payload.token.len())]);
+    let cache_key = cache::token_key("attendance", token);
     if let Some(cached_data) = state.cache.get_attendance(&cache_key).await {
         let duration = start_time.elapsed().as_millis() as u64;
         state.performance_monitor.record_request("attendance", duration, "cache_hit").await;
@@ -96,44 +123,7 @@ pub async fn attendance_handler(
         ));
     }
 
-        let result = async {
-            let api_service = ExternalApiService::new(&state.config.external_api_base);
-            let records = api_service.get_attendance_records(&payload.token).await?;
-
-            if records.is_empty() {
-                return Err(AppError::ExternalApiError("No attendance records returned".to_string()));
-            }
-
-            let total_summary = &records[records.len() - 1];
-            let daily_records = &records[..records.len() - 1];
-
-            let daily_attendance: Vec<DailyAttendance> = daily_records
-                .par_iter()
-                .map(|r| DailyAttendance {
-                    course: r.cdata.course_name.trim().to_string(),
-                    present: r.attendance_summary.Present,
-                    total: r.attendance_summary.Total,
-                    percent: r.attendance_summary.Percent,
-                })
-                .collect();
-
-            let response_data = AttendanceResponse {
-                daily_attendance,
-                total_present: total_summary.attendance_summary.Present,
-                total_classes: total_summary.attendance_summary.Total,
-                overall_percentage: total_summary.attendance_summary.Percent,
-                batch: daily_records[0].batch.clone(),
-                section: daily_records[0].section.clone(),
-                branch: daily_records[0].dept.clone(),
-                student_id: daily_records[0].student_id.clone(),
-            };
-
-            // Store in cache
-            let response_value = serde_json::to_value(&response_data).map_err(|e| AppError::InternalError(format!("Serialization error: {}", e)))?;
-            state.cache.set_attendance(cache_key, response_value.clone()).await;
-
-            Ok::<_, AppError>(response_value)
-        }.await;
+    let result = with_reauth(&state, &auth, |token| fetch_attendance(&state, token)).await;
 
     match result {
         Ok(response_data) => {
@@ -149,7 +139,6 @@ pub async fn attendance_handler(
             ))
         }
         Err(e) => {
-            let duration = start_time.elapsed().as_millis() as u64;
             state.performance_monitor.record_error("attendance", &e.to_string()).await;
             
             error!("[attendance] error: {}", e);
@@ -160,25 +149,17 @@ pub async fn attendance_handler(
 
 pub async fn all_attendance_handler(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    auth: UpstreamToken,
 ) -> Result<impl IntoResponse, AppError> {
     let start_time = Instant::now();
     
     info!("[all-attendance] start (GET)");
 
     // Extract authorization
-    let auth_header = headers
-        .get("authorization")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("");
-    
-    let token = auth_header.trim_start_matches("Bearer ").trim();
-    if token.is_empty() {
-        return Err(AppError::ValidationError("Missing Authorization header with Bearer token".to_string()));
-    }
+    let token = auth.token();
 
     // Check cache
-    let cache_key = format!("attendance_{}", &token[..std::cmp::min(10, token.len())]);
+    let cache_key = cache::token_key("attendance", token);
     if let Some(cached_data) = state.cache.get_all_attendance(&cache_key).await {
         let duration = start_time.elapsed().as_millis() as u64;
         state.performance_monitor.record_request("all-attendance", duration, "cache_hit").await;
@@ -188,177 +169,223 @@ pub async fn all_attendance_handler(
         return Ok((
             StatusCode::OK,
             [("Cache-Control", "max-age=300, stale-while-revalidate=1800"), ("X-Cache", "HIT")],
-            Json(cached_data)
+            Json(with_latest_changes(&state, cached_data).await)
         ));
     }
 
-    let result = async {
-        let api_service = ExternalApiService::new(&state.config.external_api_base);
-        
-        // Get student ID from attendance API
-        let attendance_records = api_service.get_attendance_records(token).await?;
-        let student_id = attendance_records[0].student_id.clone();
-        
-        info!("[all-attendance] studentId: {}", student_id);
-
-        // Get subjects list
-        let subjects_data = api_service.get_subjects(token).await?;
-        let subjects: Vec<Subject> = subjects_data
-            .par_iter()
-            .map(|entry| Subject {
-                name: entry.cdata.course_name.trim().to_string(),
-                code: entry.cdata.course_code.clone(),
-                cf_id: entry.id.clone(),
-            })
-            .collect();
+    let result = with_reauth(&state, &auth, |token| fetch_all_attendance(&state, token)).await;
 
-        if subjects.is_empty() {
-            return Err(AppError::ExternalApiError("No subjects found".to_string()));
+    match result {
+        Ok(response_data) => {
+            let response_data = with_latest_changes(&state, response_data).await;
+            let duration = start_time.elapsed().as_millis() as u64;
+            state.performance_monitor.record_request("all-attendance", duration, "success").await;
+            
+            info!("[all-attendance] completed in {}ms", duration);
+            
+            Ok((
+                StatusCode::OK,
+                [("Cache-Control", "max-age=300, stale-while-revalidate=1800"), ("X-Cache", "MISS")],
+                Json(response_data)
+            ))
+        }
+        Err(e) => {
+            state.performance_monitor.record_error("all-attendance", &e.to_string()).await;
+            
+            error!("[all-attendance] error: {}", e);
+            Err(e)
         }
+    }
+}
 
-        // Create course code mapping
-        let course_code_map: HashMap<String, String> = subjects_data
-            .par_iter()
-            .map(|entry| (
-                entry.cdata.course_code.clone(),
-                entry.cdata.course_name.trim().to_string()
-            ))
-            .collect();
+pub async fn export_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let start_time = Instant::now();
 
-        // Parallel fetching for all subjects with enhanced timeout and retry logic
-        let fetch_results: Vec<_> = subjects
-            .par_iter()
-            .map(|subject| {
-                let base_url = state.config.external_api_base.clone();
-                let subject_name = subject.name.clone();
-                let subject_cf_id = subject.cf_id.clone();
-                let student_id = student_id.clone();
-                let token = token.to_string();
-                
-                async move {
-                    let api_service = ExternalApiService::new(&base_url);
-                    api_service.fetch_subject_attendance(&token, &subject_name, &subject_cf_id, &student_id).await
-                }
-            })
-            .collect();
+    let format = export::ExportFormat::parse(query.format.as_deref())?;
+    let token = auth.token();
 
-        let mut grand_present = 0;
-        let mut grand_absent = 0;
-        let mut subjects_summary: HashMap<String, SubjectSummary> = HashMap::new();
-
-        for (subject, result) in subjects.iter().zip(fetch_results) {
-            match result.await {
-                Ok(data) => {
-                    let present_count = data.iter().filter(|d| d.state == "Present").count() as i32;
-                    let absent_count = data.iter().filter(|d| d.state == "Absent").count() as i32;
-                    grand_present += present_count;
-                    grand_absent += absent_count;
-
-                    // Process daily attendance
-                    let mut by_date: HashMap<String, (i32, i32)> = HashMap::new();
-                    for record in &data {
-                        let date_key = if let Some(start_time) = &record.start_time {
-                            if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(start_time) {
-                                dt.format("%Y-%m-%d").to_string()
-                            } else {
-                                chrono::Utc::now().format("%Y-%m-%d").to_string()
-                            }
-                        } else if let Some(date_formatted) = &record.date_formatted {
-                            if let Some(date_part) = date_formatted.split_whitespace().last() {
-                                date_part.to_string()
-                            } else {
-                                chrono::Utc::now().format("%Y-%m-%d").to_string()
-                            }
-                        } else {
-                            chrono::Utc::now().format("%Y-%m-%d").to_string()
-                        };
-
-                        let entry = by_date.entry(date_key).or_insert((0, 0));
-                        if record.state == "Present" {
-                            entry.0 += 1;
-                        } else {
-                            entry.1 += 1;
-                        }
-                    }
-
-                    let daily: Vec<DailyAttendanceRecord> = by_date
-                        .into_iter()
-                        .map(|(date, (present, absent))| DailyAttendanceRecord {
-                            date,
-                            present,
-                            absent,
-                        })
-                        .collect();
-
-                    subjects_summary.insert(subject.name.clone(), SubjectSummary {
-                        total_present: present_count,
-                        total_absent: absent_count,
-                        daily,
-                    });
-                }
-                Err(e) => {
-                    warn!("[all-attendance] Failed to fetch data for {}: {}", subject.name, e);
-                }
+    info!("[export] start ({})", format.extension());
+
+    let result = async {
+        let cache_key = cache::token_key("attendance", token);
+        let value = match state.cache.get_all_attendance(&cache_key).await {
+            Some(cached) => cached,
+            None => with_reauth(&state, &auth, |token| fetch_all_attendance(&state, token)).await?,
+        };
+        let data: AllAttendanceResponse = serde_json::from_value(value)?;
+        let rows = export::flatten(&data);
+
+        let body = match format {
+            export::ExportFormat::Csv => {
+                let chunks = export::csv_chunks(rows).map(Ok::<_, std::convert::Infallible>);
+                Body::from_stream(futures::stream::iter(chunks))
             }
+            export::ExportFormat::Xlsx => Body::from(export::to_xlsx(&data, &rows)?),
+        };
+
+        Ok::<_, AppError>((data.student_id, body))
+    }.await;
+
+    match result {
+        Ok((student_id, body)) => {
+            let duration = start_time.elapsed().as_millis() as u64;
+            state.performance_monitor.record_request("export", duration, "success").await;
+
+            let filename: String = format!(
+                "attendance_{}_{}.{}",
+                student_id,
+                chrono::Utc::now().format("%Y-%m-%d"),
+                format.extension()
+            )
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            .collect();
+
+            Ok((
+                StatusCode::OK,
+                [
+                    (header::CONTENT_TYPE, format.content_type().to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+                    (header::CACHE_CONTROL, "private, no-store".to_string()),
+                ],
+                body,
+            ))
         }
+        Err(e) => {
+            state.performance_monitor.record_error("export", &e.to_string()).await;
+
+            error!("[export] error: {}", e);
+            Err(e)
+        }
+    }
+}
+
+pub async fn report_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+) -> Result<impl IntoResponse, AppError> {
+    let start_time = Instant::now();
+    let token = auth.token();
+
+    info!("[report] start");
 
-        let response_data = AllAttendanceResponse {
-            student_id,
-            total_present_all_subjects: grand_present,
-            total_absent_all_subjects: grand_absent,
-            subjects: subjects_summary,
-            course_code_map,
-            cached_at: chrono::Utc::now(),
-            performance: None, // Could be enhanced with actual performance metrics
+    let result = async {
+        let cache_key = cache::token_key("attendance", token);
+        let value = match state.cache.get_attendance(&cache_key).await {
+            Some(cached) => cached,
+            None => with_reauth(&state, &auth, |token| fetch_attendance(&state, token)).await?,
         };
+        let data: AttendanceResponse = serde_json::from_value(value)?;
 
-        // Store in cache
-        let response_value = serde_json::to_value(&response_data).map_err(|e| AppError::InternalError(format!("Serialization error: {}", e)))?;
-        state.cache.set_all_attendance(cache_key, response_value.clone()).await;
+        let generated_at = chrono::Utc::now();
+        let hash = report::content_hash(&data, generated_at);
+        let pdf = report::render_pdf(&data, generated_at);
 
-        Ok::<_, AppError>(response_value)
+        Ok::<_, AppError>((data.student_id, hash, pdf))
     }.await;
 
     match result {
-        Ok(response_data) => {
+        Ok((student_id, hash, pdf)) => {
             let duration = start_time.elapsed().as_millis() as u64;
-            state.performance_monitor.record_request("all-attendance", duration, "success").await;
-            
-            info!("[all-attendance] completed in {}ms", duration);
-            
+            state.performance_monitor.record_request("report", duration, "success").await;
+
+            let filename: String = format!("attendance_report_{}.pdf", student_id)
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                .collect();
+
             Ok((
                 StatusCode::OK,
-                [("Cache-Control", "max-age=300, stale-while-revalidate=1800"), ("X-Cache", "MISS")],
-                Json(response_data)
+                [
+                    (header::CONTENT_TYPE, "application/pdf".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("inline; filename=\"{}\"", filename)),
+                    (header::CACHE_CONTROL, "private, no-store".to_string()),
+                    (header::HeaderName::from_static("x-report-hash"), hash),
+                ],
+                pdf,
             ))
         }
         Err(e) => {
-            let duration = start_time.elapsed().as_millis() as u64;
-            state.performance_monitor.record_error("all-attendance", &e.to_string()).await;
-            
-            error!("[all-attendance] error: {}", e);
+            state.performance_monitor.record_error("report", &e.to_string()).await;
+
+            error!("[report] error: {}", e);
             Err(e)
         }
     }
 }
 
+// Fetches the attendance summary, records a history snapshot and caches the result
+async fn fetch_attendance(state: &AppState, token: String) -> Result<serde_json::Value, AppError> {
+    let cache_key = cache::token_key("attendance", &token);
+    let response_data = state.client.attendance_summary(&token).await?;
+
+    // Record a history snapshot; failures must not break the live response
+    #[cfg(feature = "history")]
+    if let Err(e) = state.history.record_snapshot(&response_data).await {
+        warn!("[attendance] failed to record history snapshot: {}", e);
+    }
+
+    // Store in cache
+    let response_value = serde_json::to_value(&response_data).map_err(|e| AppError::InternalError(format!("Serialization error: {}", e)))?;
+    state.cache.set_attendance(cache_key, response_value.clone()).await;
+
+    Ok(response_value)
+}
+
+// Fetches, summarizes and caches the all-attendance payload, recording changes on the way.
+// The changes are not cached; `with_latest_changes` adds them to each response.
+async fn fetch_all_attendance(state: &AppState, token: String) -> Result<serde_json::Value, AppError> {
+    fetch_full(state, token).await.map(|(summary, _)| summary)
+}
+
+// Same fetch as `fetch_all_attendance`, returning the per-lecture list the calendar renders
+async fn fetch_lectures(state: &AppState, token: String) -> Result<serde_json::Value, AppError> {
+    fetch_full(state, token).await.map(|(_, lectures)| lectures)
+}
+
+async fn fetch_full(state: &AppState, token: String) -> Result<(serde_json::Value, serde_json::Value), AppError> {
+    let full = state.client.full_attendance(&token).await?;
+
+    let response_data = aggregation::summarize(&full);
+    state.change_tracker.observe(&full).await;
+
+    // Store in cache
+    let response_value = serde_json::to_value(&response_data).map_err(|e| AppError::InternalError(format!("Serialization error: {}", e)))?;
+    let lectures_value = serde_json::to_value(calendar::lectures(&full)).map_err(|e| AppError::InternalError(format!("Serialization error: {}", e)))?;
+    state.cache.set_all_attendance(cache::token_key("attendance", &token), response_value.clone()).await;
+    state.cache.set_lectures(cache::token_key("lectures", &token), lectures_value.clone()).await;
+
+    Ok((response_value, lectures_value))
+}
+
+// Sets `changes` to what the student's latest fetch found, so a cached payload does not carry
+// the diff from the fetch that produced it after a newer fetch found nothing
+async fn with_latest_changes(state: &AppState, mut data: serde_json::Value) -> serde_json::Value {
+    let Some(student_id) = data["student_id"].as_str().map(str::to_string) else {
+        return data;
+    };
+    let changes = state.change_tracker.latest(&student_id).await;
+    if let Some(fields) = data.as_object_mut() {
+        fields.remove("changes");
+        if let Some(changes) = changes {
+            fields.insert("changes".to_string(), serde_json::json!(changes));
+        }
+    }
+    data
+}
+
 pub async fn quiz_handler(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    auth: UpstreamToken,
 ) -> Result<impl IntoResponse, AppError> {
     let start_time = Instant::now();
     
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("");
-
-    if !auth_header.starts_with("Bearer ") {
-        return Err(AppError::AuthenticationError("Token required in Authorization header".to_string()));
-    }
-
-    let token = auth_header.trim_start_matches("Bearer ").trim();
-    let cache_key = format!("quiz_{}", &token[..std::cmp::min(10, token.len())]);
+    let token = auth.token();
+    let cache_key = cache::token_key("quiz", token);
 
     // Check cache first
     if let Some(cached_data) = state.cache.get_quiz(&cache_key).await {
@@ -375,7 +402,7 @@ pub async fn quiz_handler(
     }
 
     // Check for pending request
-    match state.cache.get_or_create_pending_request(cache_key.clone()).await {
+    match state.cache.get_or_create_pending_request(cache::CacheName::Quiz, cache_key.clone()).await {
         Ok(data) => {
             let duration = start_time.elapsed().as_millis() as u64;
             state.performance_monitor.record_request("quiz", duration, "pending_hit").await;
@@ -393,20 +420,12 @@ pub async fn quiz_handler(
         }
     }
 
-    let result = async {
-        let api_service = ExternalApiService::new(&state.config.external_api_base);
-        let quiz_data = api_service.get_quiz_data(token).await?;
-        
-        // Store in cache
-        state.cache.set_quiz(cache_key.clone(), serde_json::to_value(&quiz_data)?).await;
-        
-        Ok::<_, AppError>(quiz_data)
-    }.await;
+    let result = with_reauth(&state, &auth, |token| fetch_quiz(&state, token)).await;
 
     match result {
         Ok(quiz_data) => {
             // Complete pending request
-            state.cache.complete_pending_request(&cache_key, Ok(serde_json::to_value(&quiz_data)?)).await;
+            state.cache.complete_pending_request(cache::CacheName::Quiz, &cache_key, Ok(quiz_data.clone())).await;
             
             let duration = start_time.elapsed().as_millis() as u64;
             state.performance_monitor.record_request("quiz", duration, "success").await;
@@ -419,9 +438,8 @@ pub async fn quiz_handler(
         }
         Err(e) => {
             // Complete pending request with error
-            state.cache.complete_pending_request(&cache_key, Err(e.to_string())).await;
+            state.cache.complete_pending_request(cache::CacheName::Quiz, &cache_key, Err(e.to_string())).await;
             
-            let duration = start_time.elapsed().as_millis() as u64;
             state.performance_monitor.record_error("quiz", &e.to_string()).await;
             
             error!("[quiz] API error: {}", e);
@@ -429,3 +447,502 @@ pub async fn quiz_handler(
         }
     }
 }
+
+pub async fn quiz_analytics_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+) -> Result<impl IntoResponse, AppError> {
+    let start_time = Instant::now();
+    let token = auth.token();
+
+    info!("[quiz-analytics] start");
+
+    let result = async {
+        let quiz_key = cache::token_key("quiz", token);
+        let analytics_key = cache::token_key("quiz_analytics", token);
+
+        let quiz_data = match state.cache.get_quiz(&quiz_key).await {
+            Some(cached) => cached,
+            None => with_reauth(&state, &auth, |token| fetch_quiz(&state, token)).await?,
+        };
+        let quizzes: QuizResponse = serde_json::from_value(quiz_data)?;
+        let checksum = quiz::checksum(&quizzes.quizzes);
+
+        // Only recompute when the quiz data behind the analytics has changed
+        if let Some(cached) = state.cache.get_quiz(&analytics_key).await {
+            if cached["source_checksum"].as_str() == Some(checksum.as_str()) {
+                return Ok::<_, AppError>((cached, "HIT"));
+            }
+        }
+
+        let analytics = serde_json::to_value(quiz::analyze(&quizzes.quizzes, chrono::Utc::now()))?;
+        state.cache.set_quiz(analytics_key, analytics.clone()).await;
+
+        Ok((analytics, "MISS"))
+    }.await;
+
+    match result {
+        Ok((analytics, cache_status)) => {
+            let duration = start_time.elapsed().as_millis() as u64;
+            let status = if cache_status == "HIT" { "cache_hit" } else { "success" };
+            state.performance_monitor.record_request("quiz-analytics", duration, status).await;
+
+            Ok((
+                StatusCode::OK,
+                [("Cache-Control", "max-age=300, stale-while-revalidate=1800"), ("X-Cache", cache_status)],
+                Json(analytics)
+            ))
+        }
+        Err(e) => {
+            state.performance_monitor.record_error("quiz-analytics", &e.to_string()).await;
+
+            error!("[quiz-analytics] error: {}", e);
+            Err(e)
+        }
+    }
+}
+
+pub async fn quiz_start_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<StartQuizRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let start_time = Instant::now();
+
+    let quiz_code = payload.quiz_code.trim().to_uppercase();
+    if quiz_code.len() != 4 || !quiz_code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(AppError::ValidationError("Quiz code must be 4 letters or digits".to_string()));
+    }
+    if payload.user_unique_code.trim().is_empty() || payload.pin.trim().is_empty() {
+        return Err(AppError::ValidationError("Admission number and PIN are required".to_string()));
+    }
+    let request = StartQuizRequest {
+        quiz_code,
+        user_unique_code: payload.user_unique_code.trim().to_string(),
+        pin: payload.pin.trim().to_string(),
+    };
+
+    // Never log the request itself; it carries the PIN
+    info!("[quiz-start] quiz {}", request.quiz_code);
+
+    let result = async {
+        let details = state.client.quiz_details(&request).await?;
+
+        quiz::start_status(&details, &request, &state.config.quiz_access_url, chrono::Utc::now())
+    }.await;
+
+    match result {
+        Ok(status) => {
+            let duration = start_time.elapsed().as_millis() as u64;
+            state.performance_monitor.record_request("quiz-start", duration, "success").await;
+
+            Ok((StatusCode::OK, [("Cache-Control", "no-store")], Json(status)))
+        }
+        Err(e) => {
+            state.performance_monitor.record_error("quiz-start", &e.to_string()).await;
+
+            error!("[quiz-start] error: {}", e);
+            Err(e)
+        }
+    }
+}
+
+// Fetches quiz results in the normalized schema and caches them
+async fn fetch_quiz(state: &AppState, token: String) -> Result<serde_json::Value, AppError> {
+    let cache_key = cache::token_key("quiz", &token);
+    let quizzes = state.client.quizzes(&token).await?;
+    let quiz_data = serde_json::to_value(QuizResponse {
+        schema_version: QUIZ_SCHEMA_VERSION,
+        quizzes,
+        fetched_at: chrono::Utc::now(),
+    })?;
+
+    // Store in cache
+    state.cache.set_quiz(cache_key, quiz_data.clone()).await;
+
+    Ok(quiz_data)
+}
+
+#[cfg(feature = "history")]
+pub async fn attendance_history_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+    axum::extract::Query(query): axum::extract::Query<AttendanceHistoryQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let start_time = Instant::now();
+
+    info!("[attendance-history] start");
+
+    let token = auth.token();
+
+    let result = async {
+        let student_id = resolve_student_id(&state, token).await?;
+
+        let limit = query.limit.unwrap_or(365).clamp(1, 5000);
+        let snapshots = state.history.get_history(&student_id, query.since, limit).await?;
+
+        Ok::<_, AppError>(AttendanceHistoryResponse { student_id, snapshots })
+    }.await;
+
+    match result {
+        Ok(response_data) => {
+            let duration = start_time.elapsed().as_millis() as u64;
+            state.performance_monitor.record_request("attendance-history", duration, "success").await;
+
+            info!("[attendance-history] returned {} snapshots", response_data.snapshots.len());
+
+            Ok((StatusCode::OK, Json(response_data)))
+        }
+        Err(e) => {
+            state.performance_monitor.record_error("attendance-history", &e.to_string()).await;
+
+            error!("[attendance-history] error: {}", e);
+            Err(e)
+        }
+    }
+}
+
+pub async fn attendance_changes_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+    axum::extract::Query(query): axum::extract::Query<AttendanceChangesQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let start_time = Instant::now();
+
+    info!("[attendance-changes] start");
+
+    let token = auth.token();
+
+    let result = async {
+        let student_id = resolve_student_id(&state, token).await?;
+        let changes = state.change_tracker.changes_since(&student_id, query.since).await;
+
+        Ok::<_, AppError>(AttendanceChangesResponse { student_id, changes })
+    }.await;
+
+    match result {
+        Ok(response_data) => {
+            let duration = start_time.elapsed().as_millis() as u64;
+            state.performance_monitor.record_request("attendance-changes", duration, "success").await;
+
+            Ok((StatusCode::OK, Json(response_data)))
+        }
+        Err(e) => {
+            state.performance_monitor.record_error("attendance-changes", &e.to_string()).await;
+
+            error!("[attendance-changes] error: {}", e);
+            Err(e)
+        }
+    }
+}
+
+pub async fn create_subscription_handler(
+    State(state): State<AppState>,
+    auth: Result<UpstreamToken, AppError>,
+    Json(payload): Json<CreateSubscriptionRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let start_time = Instant::now();
+
+    info!("[subscriptions] create");
+
+    let store = subscription_store(&state)?;
+
+    let auth = UpstreamToken::with_body_token(auth, payload.auth.token.clone(), &state.config)?;
+    let token = auth.token().to_string();
+
+    let tunables = state.tunables.get();
+    let poll_interval_seconds = payload.poll_interval_seconds.unwrap_or(tunables.poll_interval_seconds);
+    if poll_interval_seconds < tunables.min_poll_interval_seconds {
+        return Err(AppError::ValidationError(format!(
+            "poll_interval_seconds must be at least {}",
+            tunables.min_poll_interval_seconds
+        )));
+    }
+    validate_subscription(&payload, &state.config.webhook_allowed_hosts).await?;
+
+    let result = async {
+        // Validate the token the same way /api/attendance does before storing it
+        let student_id = state
+            .client
+            .student_id(&token)
+            .await
+            .map_err(|e| match e {
+                AppError::ExternalApiError(_) | AppError::UpstreamUnauthorized(_) => {
+                    AppError::AuthenticationError("Token rejected by upstream portal".to_string())
+                }
+                other => other,
+            })?;
+
+        let subscription = store
+            .upsert(
+                &student_id,
+                &token,
+                &payload.channel,
+                payload.thresholds.clone(),
+                poll_interval_seconds,
+            )
+            .await?;
+
+        Ok::<_, AppError>(subscription.to_response())
+    }.await;
+
+    match result {
+        Ok(response_data) => {
+            let duration = start_time.elapsed().as_millis() as u64;
+            state.performance_monitor.record_request("subscriptions", duration, "success").await;
+
+            Ok((StatusCode::CREATED, Json(response_data)))
+        }
+        Err(e) => {
+            state.performance_monitor.record_error("subscriptions", &e.to_string()).await;
+
+            error!("[subscriptions] error: {}", e);
+            Err(e)
+        }
+    }
+}
+
+pub async fn get_subscription_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+) -> Result<impl IntoResponse, AppError> {
+    let store = subscription_store(&state)?;
+    let token = auth.token();
+
+    let student_id = resolve_student_id(&state, token).await?;
+    let subscription = store
+        .get(&student_id)
+        .await
+        .ok_or_else(|| AppError::NotFound("No subscription for this student".to_string()))?;
+
+    Ok((StatusCode::OK, Json(subscription.to_response())))
+}
+
+pub async fn delete_subscription_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+) -> Result<impl IntoResponse, AppError> {
+    let store = subscription_store(&state)?;
+    let token = auth.token();
+
+    let student_id = resolve_student_id(&state, token).await?;
+    if !store.remove(&student_id).await? {
+        return Err(AppError::NotFound("No subscription for this student".to_string()));
+    }
+
+    info!("[subscriptions] deleted subscription for {}", student_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn calendar_handler(
+    State(state): State<AppState>,
+    auth: Result<UpstreamToken, AppError>,
+    axum::extract::Query(query): axum::extract::Query<CalendarQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let start_time = Instant::now();
+
+    info!("[calendar] start");
+
+    // Calendar apps cannot send headers, so subscribe URLs carry a feed id instead
+    let auth = match &query.feed {
+        Some(id) => {
+            let token = calendar_feeds(&state)?
+                .token(id)
+                .await
+                .ok_or_else(|| AppError::AuthenticationError("Unknown or revoked calendar feed".to_string()))?;
+            UpstreamToken::stored(token)
+        }
+        None => auth?,
+    };
+    let token = auth.token();
+
+    // Served from the lecture and quiz caches; the portal is only asked on a miss
+    let result = async {
+        let lectures_key = cache::token_key("lectures", token);
+        let value = match state.cache.get_lectures(&lectures_key).await {
+            Some(cached) => cached,
+            None => with_reauth(&state, &auth, |token| fetch_lectures(&state, token)).await?,
+        };
+        let lectures: calendar::Lectures = serde_json::from_value(value)?;
+
+        let quiz_key = cache::token_key("quiz", token);
+        let quizzes = match state.cache.get_quiz(&quiz_key).await {
+            Some(cached) => Ok(cached),
+            None => with_reauth(&state, &auth, |token| fetch_quiz(&state, token)).await,
+        }
+        .and_then(|value| serde_json::from_value::<QuizResponse>(value).map_err(AppError::from));
+        let quizzes = match quizzes {
+            Ok(data) => data.quizzes,
+            Err(e) => {
+                warn!("[calendar] quiz data unavailable: {}", e);
+                Vec::new()
+            }
+        };
+
+        Ok::<_, AppError>(calendar::render_calendar(&lectures.lectures, &quizzes, chrono::Utc::now()))
+    }.await;
+
+    match result {
+        Ok(body) => {
+            let duration = start_time.elapsed().as_millis() as u64;
+            state.performance_monitor.record_request("calendar", duration, "success").await;
+
+            Ok((
+                StatusCode::OK,
+                [
+                    ("Content-Type", "text/calendar; charset=utf-8"),
+                    ("Content-Disposition", "inline; filename=\"aims.ics\""),
+                    ("Cache-Control", "private, max-age=3600"),
+                ],
+                body,
+            ))
+        }
+        Err(e) => {
+            state.performance_monitor.record_error("calendar", &e.to_string()).await;
+
+            error!("[calendar] error: {}", e);
+            Err(e)
+        }
+    }
+}
+
+pub async fn calendar_subscribe_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+) -> Result<impl IntoResponse, AppError> {
+    let feeds = calendar_feeds(&state)?;
+    let token = auth.token();
+
+    // Make sure the token works before handing out a long-lived URL for it
+    let student_id = resolve_student_id(&state, token).await?;
+
+    let id = feeds.issue(&student_id, token).await?;
+    let url = format!(
+        "{}/api/calendar.ics?feed={}",
+        state.config.public_base_url.trim_end_matches('/'),
+        id.expose()
+    );
+    let webcal_url = url
+        .replacen("https://", "webcal://", 1)
+        .replacen("http://", "webcal://", 1);
+
+    Ok((StatusCode::OK, Json(CalendarSubscribeResponse { url, webcal_url })))
+}
+
+pub async fn calendar_unsubscribe_handler(
+    State(state): State<AppState>,
+    auth: UpstreamToken,
+) -> Result<impl IntoResponse, AppError> {
+    let feeds = calendar_feeds(&state)?;
+
+    let student_id = resolve_student_id(&state, auth.token()).await?;
+    if !feeds.revoke(&student_id).await? {
+        return Err(AppError::NotFound("No calendar feed for this student".to_string()));
+    }
+
+    info!("[calendar] revoked feed for {}", student_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn calendar_feeds(state: &AppState) -> Result<std::sync::Arc<crate::calendar_feeds::CalendarFeedStore>, AppError> {
+    state
+        .calendar_feeds
+        .clone()
+        .ok_or_else(|| AppError::ServiceUnavailable("Calendar feeds are not enabled on this server".to_string()))
+}
+
+fn subscription_store(state: &AppState) -> Result<std::sync::Arc<crate::subscriptions::SubscriptionStore>, AppError> {
+    state
+        .subscriptions
+        .clone()
+        .ok_or_else(|| AppError::ServiceUnavailable("Subscriptions are not enabled on this server".to_string()))
+}
+
+async fn validate_subscription(payload: &CreateSubscriptionRequest, allowed_hosts: &[String]) -> Result<(), AppError> {
+    match &payload.channel {
+        NotificationChannel::Webhook { url, secret } => {
+            notifications::check_destination(url, allowed_hosts).await?;
+            if secret.expose().len() < 16 {
+                return Err(AppError::ValidationError("Webhook secret must be at least 16 characters".to_string()));
+            }
+        }
+        NotificationChannel::Discord { webhook_url } => notifications::check_destination(webhook_url, allowed_hosts).await?,
+        NotificationChannel::Telegram { bot_token, chat_id } => {
+            if bot_token.expose().is_empty() || chat_id.is_empty() {
+                return Err(AppError::ValidationError("Telegram channel needs bot_token and chat_id".to_string()));
+            }
+        }
+    }
+
+    if let Some(min) = payload.thresholds.min_percentage {
+        if !(0.0..=100.0).contains(&min) {
+            return Err(AppError::ValidationError("min_percentage must be between 0 and 100".to_string()));
+        }
+    }
+    if payload.thresholds.max_notifications_per_hour == 0 {
+        return Err(AppError::ValidationError("max_notifications_per_hour must be at least 1".to_string()));
+    }
+
+    Ok(())
+}
+
+// Runs an upstream call and, when the portal rejects the token of a session whose student
+// opted in to re-login, logs in again and retries the call once
+async fn with_reauth<T, F, Fut>(state: &AppState, auth: &UpstreamToken, call: F) -> Result<T, AppError>
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = Result<T, AppError>>,
+{
+    let expired = || AppError::AuthenticationError("Portal session expired, please log in again".to_string());
+    let token = auth.token();
+
+    match call(token.to_string()).await {
+        Err(AppError::UpstreamUnauthorized(reason)) => warn!("[reauth] upstream rejected token: {}", reason),
+        other => return other,
+    }
+
+    // Only the session's own token can be renewed; explicit Bearer tokens belong to the caller
+    let Some(session) = auth.session().filter(|s| s.can_reauthenticate()) else {
+        return Err(expired());
+    };
+
+    let renewed = state
+        .sessions
+        .reauthenticate(session, token, |username, password| async move {
+            let login = state.client.login(&username, &password).await?;
+            Ok(login.token)
+        })
+        .await;
+
+    let token = match renewed {
+        Ok(token) => {
+            state.performance_monitor.record_reauth("success").await;
+            token
+        }
+        Err(e) => {
+            state.performance_monitor.record_reauth("failure").await;
+            warn!("[reauth] re-login failed: {}", e);
+            return Err(expired());
+        }
+    };
+
+    call(token.expose().clone()).await.map_err(|e| match e {
+        AppError::UpstreamUnauthorized(_) => expired(),
+        other => other,
+    })
+}
+
+// Resolves the student behind a token from cached responses before asking the portal
+async fn resolve_student_id(state: &AppState, token: &str) -> Result<String, AppError> {
+    let cache_key = cache::token_key("attendance", token);
+
+    let cached = match state.cache.get_attendance(&cache_key).await {
+        Some(data) => Some(data),
+        None => state.cache.get_all_attendance(&cache_key).await,
+    };
+
+    if let Some(student_id) = cached.and_then(|v| v["student_id"].as_str().map(|s| s.to_string())) {
+        return Ok(student_id);
+    }
+
+    state.client.student_id(token).await
+}
@@ -0,0 +1,109 @@
+// Liveness and readiness. Readiness reads the result of a periodic portal probe instead of
+// calling the portal per request, so frequent probes from orchestrators stay cheap.
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{circuit::CircuitState, client::AimsClient, server::AppState};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub reachable: bool,
+    pub latency_ms: u64,
+    pub checked_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub struct Health {
+    started: Instant,
+    upstream: RwLock<Option<ProbeResult>>,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            upstream: RwLock::new(None),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The latest probe; `None` until the first one finishes.
+    pub fn upstream(&self) -> Option<ProbeResult> {
+        self.upstream.read().unwrap().clone()
+    }
+
+    pub async fn probe(&self, client: &AimsClient) {
+        let started = Instant::now();
+        let outcome = client.probe().await;
+        let result = ProbeResult {
+            reachable: outcome.is_ok(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            checked_at: Utc::now(),
+            error: outcome.err().map(|e| e.to_string()),
+        };
+
+        let was_reachable = self.upstream().map(|last| last.reachable);
+        match (&result.error, was_reachable) {
+            (Some(e), Some(true) | None) => warn!("[Health] Portal unreachable: {}", e),
+            (None, Some(false)) => info!("[Health] Portal reachable again"),
+            _ => {}
+        }
+        *self.upstream.write().unwrap() = Some(result);
+    }
+}
+
+/// Probes the portal every `interval` until shutdown.
+pub fn spawn_prober(state: &AppState, interval: Duration) {
+    let health = state.health.clone();
+    let client = state.client.clone();
+    let cancel = state.shutdown.token();
+
+    state.shutdown.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        while cancel.run_until_cancelled(ticker.tick()).await.is_some() {
+            health.probe(&client).await;
+        }
+    });
+}
+
+pub async fn live_handler(State(state): State<AppState>) -> impl IntoResponse {
+    Json(json!({
+        "status": "alive",
+        "uptime_seconds": state.health.uptime().as_secs(),
+    }))
+}
+
+// Ready when the last probe reached the portal, the circuit is not refusing calls and the
+// server is not shutting down. Before the first periodic probe finishes, one runs inline.
+pub async fn ready_handler(State(state): State<AppState>) -> impl IntoResponse {
+    if state.health.upstream().is_none() {
+        state.health.probe(&state.client).await;
+    }
+    let upstream = state.health.upstream();
+    let circuit = state.client.circuit_state();
+    let stopping = state.shutdown.token().is_cancelled();
+    let ready = upstream.as_ref().is_some_and(|probe| probe.reachable) && circuit != CircuitState::Open && !stopping;
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let body = json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_seconds": state.health.uptime().as_secs(),
+        "shutting_down": stopping,
+        "upstream": upstream,
+        "circuit": circuit,
+        "cache": state.cache.stats().await,
+        "timestamp": Utc::now().to_rfc3339(),
+    });
+    (status, Json(body))
+}
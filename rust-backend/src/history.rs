@@ -0,0 +1,247 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use tracing::info;
+
+use crate::{
+    error::AppError,
+    models::{AttendanceHistoryPoint, AttendanceResponse, DailyAttendance},
+};
+
+// SQLite-backed store of periodic AttendanceResponse snapshots per student
+pub struct HistoryStore {
+    conn: Arc<Mutex<Connection>>,
+    snapshot_interval: chrono::Duration,
+}
+
+impl HistoryStore {
+    pub fn open(path: &str, snapshot_interval_seconds: u64) -> Result<Self, AppError> {
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent).map_err(|e| {
+                    AppError::InternalError(format!("Failed to create history directory: {}", e))
+                })?;
+            }
+        }
+
+        let conn = Connection::open(path).map_err(db_error)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS attendance_snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                student_id TEXT NOT NULL,
+                captured_at TEXT NOT NULL,
+                total_present INTEGER NOT NULL,
+                total_classes INTEGER NOT NULL,
+                overall_percentage REAL NOT NULL,
+                courses TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_snapshots_student_time
+                ON attendance_snapshots (student_id, captured_at);",
+        )
+        .map_err(db_error)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            snapshot_interval: chrono::Duration::seconds(snapshot_interval_seconds as i64),
+        })
+    }
+
+    /// Flushes SQLite's page cache to disk; called on shutdown.
+    pub fn flush(&self) -> Result<(), AppError> {
+        let conn = self.conn.lock().map_err(|_| AppError::InternalError("History store lock poisoned".to_string()))?;
+        conn.cache_flush().map_err(db_error)
+    }
+
+    /// Stores a snapshot unless one for the same student was taken within the
+    /// configured interval. Returns whether a row was written.
+    pub async fn record_snapshot(&self, snapshot: &AttendanceResponse) -> Result<bool, AppError> {
+        let conn = self.conn.clone();
+        let interval = self.snapshot_interval;
+        let student_id = snapshot.student_id.clone();
+        let total_present = snapshot.total_present;
+        let total_classes = snapshot.total_classes;
+        let overall_percentage = snapshot.overall_percentage;
+        let courses = serde_json::to_string(&snapshot.daily_attendance)?;
+        let now = Utc::now();
+
+        let written = tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().map_err(|_| AppError::InternalError("History store lock poisoned".to_string()))?;
+
+            let last: Option<String> = conn
+                .query_row(
+                    "SELECT captured_at FROM attendance_snapshots
+                     WHERE student_id = ?1 ORDER BY id DESC LIMIT 1",
+                    params![student_id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(db_error)?;
+
+            if let Some(last) = last.and_then(|s| DateTime::parse_from_rfc3339(&s).ok()) {
+                if now - last.with_timezone(&Utc) < interval {
+                    return Ok::<_, AppError>(false);
+                }
+            }
+
+            conn.execute(
+                "INSERT INTO attendance_snapshots
+                    (student_id, captured_at, total_present, total_classes, overall_percentage, courses)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![student_id, now.to_rfc3339(), total_present, total_classes, overall_percentage, courses],
+            )
+            .map_err(db_error)?;
+
+            Ok(true)
+        })
+        .await
+        .map_err(|e| AppError::InternalError(format!("History task failed: {}", e)))??;
+
+        if written {
+            info!("[History] Stored snapshot for student {}", snapshot.student_id);
+        }
+
+        Ok(written)
+    }
+
+    /// Returns snapshots for a student in chronological order.
+    pub async fn get_history(
+        &self,
+        student_id: &str,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<AttendanceHistoryPoint>, AppError> {
+        let conn = self.conn.clone();
+        let student_id = student_id.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().map_err(|_| AppError::InternalError("History store lock poisoned".to_string()))?;
+
+            // Rows are written in capture order, so walking ids backwards is newest first.
+            // Timestamps are compared parsed; their text does not sort across offsets.
+            let mut stmt = conn
+                .prepare(
+                    "SELECT captured_at, total_present, total_classes, overall_percentage, courses
+                     FROM attendance_snapshots
+                     WHERE student_id = ?1
+                     ORDER BY id DESC",
+                )
+                .map_err(db_error)?;
+
+            let rows = stmt
+                .query_map(params![student_id], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i32>(1)?,
+                        row.get::<_, i32>(2)?,
+                        row.get::<_, f64>(3)?,
+                        row.get::<_, String>(4)?,
+                    ))
+                })
+                .map_err(db_error)?;
+
+            let mut points = Vec::new();
+            for row in rows {
+                let (captured_at, total_present, total_classes, overall_percentage, courses) = row.map_err(db_error)?;
+                let captured_at = DateTime::parse_from_rfc3339(&captured_at)
+                    .map_err(|e| AppError::InternalError(format!("Corrupt snapshot timestamp: {}", e)))?
+                    .with_timezone(&Utc);
+                if since.is_some_and(|since| captured_at < since) {
+                    break;
+                }
+                let courses: Vec<DailyAttendance> = serde_json::from_str(&courses)?;
+
+                points.push(AttendanceHistoryPoint {
+                    captured_at,
+                    total_present,
+                    total_classes,
+                    overall_percentage,
+                    courses,
+                });
+                if points.len() == limit {
+                    break;
+                }
+            }
+
+            // Newest rows were selected so the limit keeps the latest points
+            points.reverse();
+            Ok(points)
+        })
+        .await
+        .map_err(|e| AppError::InternalError(format!("History task failed: {}", e)))?
+    }
+}
+
+fn db_error(e: rusqlite::Error) -> AppError {
+    AppError::InternalError(format!("History database error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(total_present: i32) -> AttendanceResponse {
+        AttendanceResponse {
+            daily_attendance: vec![DailyAttendance {
+                course: "Operating Systems".to_string(),
+                present: total_present,
+                total: 10,
+                percent: total_present as f64 * 10.0,
+            }],
+            total_present,
+            total_classes: 10,
+            overall_percentage: total_present as f64 * 10.0,
+            batch: "2022".to_string(),
+            section: "A".to_string(),
+            branch: "CSE".to_string(),
+            student_id: "2200320100001".to_string(),
+        }
+    }
+
+    // Writes a row with a hand-picked timestamp, as an older release might have stored it
+    fn insert_at(store: &HistoryStore, captured_at: &str, total_present: i32) {
+        store
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO attendance_snapshots
+                    (student_id, captured_at, total_present, total_classes, overall_percentage, courses)
+                 VALUES ('2200320100001', ?1, ?2, 10, 0, '[]')",
+                params![captured_at, total_present],
+            )
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn snapshots_are_throttled_per_student() {
+        let store = HistoryStore::open(":memory:", 3600).unwrap();
+        assert!(store.record_snapshot(&snapshot(7)).await.unwrap());
+        assert!(!store.record_snapshot(&snapshot(8)).await.unwrap(), "within the interval");
+
+        let history = store.get_history("2200320100001", None, 10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].total_present, 7);
+        assert_eq!(history[0].courses[0].course, "Operating Systems");
+        assert!(store.get_history("2200320100002", None, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn since_and_limit_compare_instants_not_text() {
+        let store = HistoryStore::open(":memory:", 0).unwrap();
+        insert_at(&store, "2026-03-01T04:00:00Z", 1);
+        // 04:30 UTC, though its text sorts after everything below
+        insert_at(&store, "2026-03-01T10:00:00+05:30", 2);
+        insert_at(&store, "2026-03-01T05:00:00.5+00:00", 3);
+        insert_at(&store, "2026-03-01T05:00:01+00:00", 4);
+
+        let since = "2026-03-01T04:45:00Z".parse().unwrap();
+        let history = store.get_history("2200320100001", Some(since), 10).await.unwrap();
+        let present: Vec<i32> = history.iter().map(|point| point.total_present).collect();
+        assert_eq!(present, vec![3, 4]);
+
+        let latest = store.get_history("2200320100001", None, 2).await.unwrap();
+        let present: Vec<i32> = latest.iter().map(|point| point.total_present).collect();
+        assert_eq!(present, vec![3, 4], "the limit keeps the latest, oldest first");
+    }
+}
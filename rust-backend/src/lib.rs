@@ -0,0 +1,50 @@
+//! Client SDK and server for the ABES AIMS portal.
+//!
+//! The supported library surface is [`client::AimsClient`] together with the types it
+//! returns: [`models`], [`aggregation`] (the per-subject data and its summary),
+//! [`error::AppError`] and [`redact::Secret`]. These follow semver: a release that removes or
+//! changes any of them, or adds a variant to `AppError`, bumps the minor version while the
+//! crate is 0.x. Public fields may gain siblings in minor releases, so do not construct these
+//! types with struct literals outside the crate. See CHANGELOG.md.
+//!
+//! [`server`] is what the `aims-backend` binary runs. It is public only for that binary and
+//! may change in any release.
+
+mod admin;
+pub mod aggregation;
+mod auth;
+mod cache;
+mod cache_backend;
+mod calendar;
+mod calendar_feeds;
+mod cassette;
+mod changes;
+mod circuit;
+pub mod client;
+mod config;
+mod config_file;
+pub mod error;
+mod export;
+mod handlers;
+mod health;
+#[cfg(feature = "history")]
+mod history;
+mod middleware;
+pub mod models;
+mod notifications;
+mod performance;
+mod quiz;
+#[cfg(feature = "redis")]
+mod redis;
+pub mod redact;
+mod report;
+mod secrets;
+#[doc(hidden)]
+pub mod server;
+mod services;
+mod sessions;
+mod shutdown;
+mod subscriptions;
+
+pub use client::{AimsClient, Login};
+pub use error::AppError;
@@ -1,33 +1,39 @@
-use axum::{
-    extract::State,
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{get, post},
-    Json, Router,
-};
+use std::path::PathBuf;
 use std::sync::Arc;
+use clap::Parser;
 use tracing::info;
 
-mod cache;
-mod config;
-mod error;
-mod handlers;
-mod middleware;
-mod models;
-mod performance;
-mod services;
+use aims_backend::{
+    redact::RedactingMakeWriter,
+    server::{build_state, serve, Config, ConfigSources},
+};
+
+#[derive(Parser)]
+#[command(name = "aims-backend", version, about = "AIMS attendance API server")]
+struct Args {
+    /// TOML config file; environment variables and the flags below override it
+    #[arg(long, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
 
-use cache::Cache;
-use config::Config;
-use handlers::*;
-use middleware::*;
-use performance::PerformanceMonitor;
+    /// Print the effective configuration (secrets redacted) and exit
+    #[arg(long)]
+    print_config: bool,
 
-#[derive(Clone)]
-pub struct AppState {
-    cache: Arc<Cache>,
-    config: Arc<Config>,
-    performance_monitor: Arc<PerformanceMonitor>,
+    #[arg(long)]
+    host: Option<String>,
+
+    #[arg(long)]
+    port: Option<String>,
+
+    /// Override any setting, e.g. `--set rate_limit_per_minute=60`; repeatable
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override)]
+    overrides: Vec<(String, String)>,
+}
+
+fn parse_override(raw: &str) -> Result<(String, String), String> {
+    raw.split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", raw))
 }
 
 #[tokio::main]
@@ -35,57 +41,44 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load environment variables
     dotenvy::dotenv().ok();
 
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter("aims_backend=debug,tower_http=debug")
-        .init();
+    let args = Args::parse();
+    let mut overrides = args.overrides;
+    overrides.extend(args.host.map(|host| ("host".to_string(), host)));
+    overrides.extend(args.port.map(|port| ("port".to_string(), port)));
+    let sources = ConfigSources {
+        file: args.config,
+        overrides,
+    };
 
     // Load configuration
-    let config = Arc::new(Config::from_env()?);
-
-    // Initialize cache
-    let cache = Arc::new(Cache::new());
-
-    // Initialize performance monitor
-    let performance_monitor = Arc::new(PerformanceMonitor::new());
-
-    // Create app state
-    let state = AppState {
-        cache: cache.clone(),
-        config: config.clone(),
-        performance_monitor: performance_monitor.clone(),
+    let config = match Config::load(&sources) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(2);
+        }
     };
+    if args.print_config {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
 
-    // Build router with middleware
-    let app = Router::new()
-        .route("/api/login", post(login_handler))
-        .route("/api/attendance", post(attendance_handler))
-        .route("/api/all-attendance", get(all_attendance_handler))
-        .route("/api/quiz", get(quiz_handler))
-        .route("/health", get(health_check))
-        .route("/metrics", get(metrics_handler))
-        .layer(cors_layer())
-        .layer(rate_limit_layer())
-        .with_state(state);
+    // Initialize tracing; output is scrubbed of anything that looks like a token
+    tracing_subscriber::fmt()
+        .with_env_filter("aims_backend=debug,tower_http=debug")
+        .with_writer(RedactingMakeWriter::new(std::io::stdout))
+        .init();
+
+    let state = build_state(config.clone()).await?;
+    #[cfg(unix)]
+    aims_backend::server::spawn_config_reloader(&state, sources)?;
 
     // Start server
     let addr = format!("{}:{}", config.host, config.port);
     info!("Starting server on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    serve(listener, state).await?;
 
     Ok(())
 }
-
-pub async fn health_check() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "status": "healthy",
-        "timestamp": chrono::Utc::now().to_rfc3339()
-    }))
-}
-
-pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
-    let metrics = state.performance_monitor.get_metrics().await;
-    Json(metrics)
-}
@@ -4,16 +4,21 @@ use axum::{
     middleware::Next,
     response::Response,
 };
+use std::sync::Arc;
 use std::time::Instant;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use tracing::info;
 
-use crate::{AppState, error::AppError};
+use crate::{config::LiveTunables, error::AppError, server::AppState};
 
-pub fn cors_layer() -> CorsLayer {
+// Origins are checked per request against the current tunables, so a reload applies at once
+pub fn cors_layer(tunables: Arc<LiveTunables>) -> CorsLayer {
     CorsLayer::new()
-        .allow_origin("http://localhost:3000".parse::<axum::http::HeaderValue>().unwrap())
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            let origin = origin.to_str().unwrap_or_default();
+            tunables.get().cors_origins.iter().any(|allowed| allowed.trim_end_matches('/') == origin)
+        }))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
         .allow_headers([HeaderName::from_static("authorization"), HeaderName::from_static("content-type")])
         .allow_credentials(true)
 }
@@ -24,7 +29,8 @@ pub fn rate_limit_layer() -> tower::ServiceBuilder<tower::layer::util::Identity>
 
 // trace_layer removed to avoid complex generic signature issues with tower-http TraceLayer.
 // If needed, a simple TraceLayer can be added later with explicit generic parameters.
-pub async fn performance_middleware<B>(
+#[allow(dead_code)]
+pub async fn performance_middleware(
     State(state): State<AppState>,
     request: Request<axum::body::Body>,
     next: Next,
@@ -41,7 +47,8 @@ pub async fn performance_middleware<B>(
     Ok(response)
 }
 
-pub async fn error_handling_middleware<B>(
+#[allow(dead_code)]
+pub async fn error_handling_middleware(
     request: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, AppError> {
@@ -63,7 +70,8 @@ pub async fn error_handling_middleware<B>(
     }
 }
 
-pub async fn logging_middleware<B>(
+#[allow(dead_code)]
+pub async fn logging_middleware(
     request: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, AppError> {
@@ -2,27 +2,41 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 
+use crate::redact::Secret;
+
 // Login models
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
     pub username: String,
-    pub password: String,
+    pub password: Secret<String>,
+    /// Keep the credentials (encrypted) with the session so expired portal tokens can be renewed
+    #[serde(default)]
+    pub remember: bool,
 }
 
 #[derive(Debug, Serialize)]
+#[allow(dead_code)]
 pub struct LoginResponse {
     pub success: bool,
     pub token: Option<String>,
     pub message: Option<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub authenticated: bool,
+    pub created_at: DateTime<Utc>,
+    pub reauth_enabled: bool,
+}
+
 // Attendance models
 #[derive(Debug, Deserialize)]
 pub struct AttendanceRequest {
-    pub token: String,
+    #[serde(default)]
+    pub token: Secret<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DailyAttendance {
     pub course: String,
     pub present: i32,
@@ -30,7 +44,7 @@ pub struct DailyAttendance {
     pub percent: f64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AttendanceResponse {
     pub daily_attendance: Vec<DailyAttendance>,
     pub total_present: i32,
@@ -42,29 +56,64 @@ pub struct AttendanceResponse {
     pub student_id: String,
 }
 
-// All Attendance models
+// Attendance history models
+#[cfg(feature = "history")]
+#[derive(Debug, Deserialize)]
+pub struct AttendanceHistoryQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+#[cfg(feature = "history")]
+#[derive(Debug, Serialize)]
+pub struct AttendanceHistoryPoint {
+    pub captured_at: DateTime<Utc>,
+    pub total_present: i32,
+    pub total_classes: i32,
+    pub overall_percentage: f64,
+    pub courses: Vec<DailyAttendance>,
+}
+
+#[cfg(feature = "history")]
 #[derive(Debug, Serialize)]
+pub struct AttendanceHistoryResponse {
+    pub student_id: String,
+    pub snapshots: Vec<AttendanceHistoryPoint>,
+}
+
+// All Attendance models
+#[derive(Debug, Clone, Serialize)]
 pub struct Subject {
     pub name: String,
     pub code: String,
     pub cf_id: String,
 }
 
-#[derive(Debug, Serialize)]
+impl From<&AttendanceRecord> for Subject {
+    fn from(entry: &AttendanceRecord) -> Self {
+        Self {
+            name: entry.cdata.course_name.trim().to_string(),
+            code: entry.cdata.course_code.clone(),
+            cf_id: entry.id.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DailyAttendanceRecord {
     pub date: String,
     pub present: i32,
     pub absent: i32,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SubjectSummary {
     pub total_present: i32,
     pub total_absent: i32,
     pub daily: Vec<DailyAttendanceRecord>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct AllAttendanceResponse {
     pub student_id: String,
     pub total_present_all_subjects: i32,
@@ -72,11 +121,13 @@ pub struct AllAttendanceResponse {
     pub subjects: HashMap<String, SubjectSummary>,
     pub course_code_map: HashMap<String, String>,
     pub cached_at: DateTime<Utc>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub performance: Option<PerformanceMetrics>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<AttendanceChanges>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PerformanceMetrics {
     pub total_time: u64,
     pub avg_batch_time: u64,
@@ -86,16 +137,290 @@ pub struct PerformanceMetrics {
     pub batch_count: usize,
 }
 
-// Quiz models
+// Change detection models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordChange {
+    pub date: String,
+    pub start_time: Option<String>,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordStateChange {
+    pub date: String,
+    pub start_time: Option<String>,
+    pub previous_state: String,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubjectChanges {
+    pub subject: String,
+    pub summary: String,
+    pub new_records: Vec<RecordChange>,
+    pub state_changes: Vec<RecordStateChange>,
+    pub removed_records: Vec<RecordChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttendanceChanges {
+    pub previous_fetch: DateTime<Utc>,
+    pub detected_at: DateTime<Utc>,
+    pub subjects: Vec<SubjectChanges>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttendanceChangesQuery {
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttendanceChangesResponse {
+    pub student_id: String,
+    pub changes: Vec<AttendanceChanges>,
+}
+
+// Export models
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+}
+
+// Notification models
+#[derive(Debug, Clone, Serialize)]
+pub struct AbsenceRecord {
+    pub subject: String,
+    pub date: String,
+    pub start_time: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
+pub struct AttendanceNotification {
+    pub event: String,
+    pub student_id: String,
+    pub detected_at: DateTime<Utc>,
+    pub summary: String,
+    /// Absent when the event is not about attendance, as for `subscription.disabled`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overall_percentage: Option<f64>,
+    pub absences: Vec<AbsenceRecord>,
+}
+
+// Subscription models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationChannel {
+    Webhook {
+        url: String,
+        #[serde(serialize_with = "serialize_secret")]
+        secret: Secret<String>,
+    },
+    Discord { webhook_url: String },
+    Telegram {
+        #[serde(serialize_with = "serialize_secret")]
+        bot_token: Secret<String>,
+        chat_id: String,
+    },
+}
+
+// Channels are serialized only to be stored encrypted, so the secrets go in as they are
+fn serialize_secret<S: serde::Serializer>(secret: &Secret<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(secret.expose())
+}
+
+impl NotificationChannel {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NotificationChannel::Webhook { .. } => "webhook",
+            NotificationChannel::Discord { .. } => "discord",
+            NotificationChannel::Telegram { .. } => "telegram",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionThresholds {
+    #[serde(default = "default_notify_on_absence")]
+    pub notify_on_absence: bool,
+    #[serde(default)]
+    pub min_percentage: Option<f64>,
+    #[serde(default = "default_max_notifications_per_hour")]
+    pub max_notifications_per_hour: u32,
+}
+
+fn default_notify_on_absence() -> bool {
+    true
+}
+
+fn default_max_notifications_per_hour() -> u32 {
+    6
+}
+
+impl Default for SubscriptionThresholds {
+    fn default() -> Self {
+        Self {
+            notify_on_absence: default_notify_on_absence(),
+            min_percentage: None,
+            max_notifications_per_hour: default_max_notifications_per_hour(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSubscriptionRequest {
+    #[serde(flatten)]
+    pub auth: AttendanceRequest,
+    pub channel: NotificationChannel,
+    #[serde(default)]
+    pub thresholds: SubscriptionThresholds,
+    pub poll_interval_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionResponse {
+    pub id: String,
+    pub student_id: String,
+    pub channel_type: String,
+    pub thresholds: SubscriptionThresholds,
+    pub poll_interval_seconds: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Calendar models
+#[derive(Debug, Deserialize)]
+pub struct CalendarQuery {
+    pub feed: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalendarSubscribeResponse {
+    pub url: String,
+    pub webcal_url: String,
+}
+
+// Quiz models
+pub const QUIZ_SCHEMA_VERSION: u32 = 1;
+
+/// An evaluated quiz normalized from the portal's `myEvaluatedQuizzes` payload.
+/// Fields the portal sends that are not modelled here are kept in `extra`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quiz {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub subject: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub marks_obtained: Option<f64>,
+    pub max_marks: Option<f64>,
+    pub correct: Option<u32>,
+    pub incorrect: Option<u32>,
+    pub not_attempted: Option<u32>,
+    pub rank: Option<u32>,
+    pub percentile: Option<f64>,
+    #[serde(default)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct QuizResponse {
-    pub success: bool,
-    pub data: Option<serde_json::Value>,
-    pub message: Option<String>,
+    pub schema_version: u32,
+    pub quizzes: Vec<Quiz>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuizSummary {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub subject: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub marks_obtained: f64,
+    pub max_marks: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuizTrendPoint {
+    pub date: DateTime<Utc>,
+    pub subject: Option<String>,
+    pub marks_obtained: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuizTrend {
+    /// "improving", "declining", "steady" or "insufficient_data"
+    pub direction: String,
+    /// Least-squares slope in marks per week
+    pub marks_per_week: Option<f64>,
+    pub points: Vec<QuizTrendPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubjectQuizStats {
+    pub subject: String,
+    pub quiz_count: usize,
+    pub average_score: f64,
+    pub average_percentage: Option<f64>,
+    pub average_percentile: Option<f64>,
+    pub best: QuizSummary,
+    pub worst: QuizSummary,
+    pub trend: QuizTrend,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuizAnalyticsResponse {
+    pub schema_version: u32,
+    pub quiz_count: usize,
+    pub average_score: Option<f64>,
+    pub average_percentile: Option<f64>,
+    pub best: Option<QuizSummary>,
+    pub worst: Option<QuizSummary>,
+    pub trend: QuizTrend,
+    pub subjects: Vec<SubjectQuizStats>,
+    /// SHA-256 of the quiz data the analytics were computed from
+    pub source_checksum: String,
+    pub computed_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct StartQuizRequest {
+    pub quiz_code: String,
+    #[serde(alias = "admission_number")]
+    pub user_unique_code: String,
+    pub pin: String,
+}
+
+// Hand-written so the PIN can never end up in a log line
+impl std::fmt::Debug for StartQuizRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StartQuizRequest")
+            .field("quiz_code", &self.quiz_code)
+            .field("user_unique_code", &self.user_unique_code)
+            .field("pin", &"<redacted>")
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum QuizStartResponse {
+    NotStarted {
+        starts_at: DateTime<Utc>,
+        ends_at: Option<DateTime<Utc>>,
+        seconds_remaining: i64,
+    },
+    Active {
+        ends_at: Option<DateTime<Utc>>,
+        seconds_remaining: Option<i64>,
+        access_url: String,
+    },
+    Ended {
+        ended_at: DateTime<Utc>,
+    },
 }
 
 // External API models
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 pub struct ExternalApiResponse<T> {
     pub response: Option<ApiResponseData<T>>,
     pub success: Option<bool>,
@@ -114,6 +439,7 @@ pub struct CourseData {
 }
 
 #[derive(Debug, Deserialize)]
+#[allow(non_snake_case)]
 pub struct AttendanceSummary {
     pub Present: i32,
     pub Total: i32,
@@ -131,8 +457,11 @@ pub struct AttendanceRecord {
     pub id: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct QuizRecord {
+    // Card ids are not consistently typed upstream (string or number)
+    #[serde(default)]
+    pub id: Option<serde_json::Value>,
     pub state: String,
     pub start_time: Option<String>,
     pub date_formatted: Option<String>,
@@ -147,7 +476,7 @@ pub struct ErrorResponse {
 }
 
 // Cache models
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheEntry<T> {
     pub data: T,
     pub timestamp: DateTime<Utc>,
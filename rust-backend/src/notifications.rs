@@ -0,0 +1,675 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::{Duration as StdDuration, Instant};
+use chrono::{DateTime, Utc};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{
+    aggregation,
+    changes::ChangeTracker,
+    client::AimsClient,
+    config::{Config, LiveTunables},
+    error::AppError,
+    models::*,
+    redact::Secret,
+    subscriptions::SubscriptionStore,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "X-Aims-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Aims-Timestamp";
+
+const TELEGRAM_API_BASE: &str = "https://api.telegram.org";
+
+// How often the poller looks for subscriptions that are due
+const POLL_TICK_SECONDS: u64 = 30;
+
+// Consecutive polls the portal may reject a stored token before the subscription is dropped
+const MAX_AUTH_FAILURES: u32 = 3;
+
+// A decrypted subscription, ready for background monitoring
+#[derive(Debug, Clone)]
+pub struct Watch {
+    pub subscription_id: String,
+    pub student_id: String,
+    pub token: Secret<String>,
+    pub channel: NotificationChannel,
+    pub thresholds: SubscriptionThresholds,
+    pub poll_interval_seconds: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Signs `"{timestamp}.{body}"` with HMAC-SHA256, hex encoded.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Checks that a subscriber's URL is https and that its host resolves only to public
+/// addresses, so notifications cannot be aimed at loopback, private or link-local services.
+/// Hosts in `allowed_hosts` skip both checks.
+pub async fn check_destination(raw: &str, allowed_hosts: &[String]) -> Result<(), AppError> {
+    let invalid = |reason: &str| AppError::ValidationError(format!("Notification URL {}", reason));
+
+    let url = url::Url::parse(raw).map_err(|_| invalid("is not a valid URL"))?;
+    let host = match url.host() {
+        Some(url::Host::Domain(domain)) => domain.to_ascii_lowercase(),
+        Some(url::Host::Ipv4(ip)) => ip.to_string(),
+        Some(url::Host::Ipv6(ip)) => ip.to_string(),
+        None => return Err(invalid("has no host")),
+    };
+    if allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(&host)) {
+        return match url.scheme() {
+            "https" | "http" => Ok(()),
+            _ => Err(invalid("must be an http(s) URL")),
+        };
+    }
+    if url.scheme() != "https" {
+        return Err(invalid("must use https"));
+    }
+
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<IpAddr> = match url.host() {
+        Some(url::Host::Ipv4(ip)) => vec![ip.into()],
+        Some(url::Host::Ipv6(ip)) => vec![ip.into()],
+        _ => tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .map_err(|_| invalid("host cannot be resolved"))?
+            .map(|address| address.ip())
+            .collect(),
+    };
+    if addresses.is_empty() || !addresses.iter().all(is_public) {
+        return Err(invalid("must not point at a loopback, private or link-local address"));
+    }
+    Ok(())
+}
+
+// `IpAddr::is_global` is not stable yet, so the non-public ranges are listed here
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                || a >= 240
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                // Benchmarking, 198.18.0.0/15
+                || (a == 198 && (18..20).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public(&IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local fc00::/7, link-local fe80::/10, documentation 2001:db8::/32
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+/// What became of a notification that was not lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Delivered,
+    DeadLettered,
+}
+
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    allowed_hosts: Vec<String>,
+    max_retries: usize,
+    retry_base_delay: StdDuration,
+    dead_letter_path: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &Config) -> Self {
+        // A redirect could lead past the destination check, so none are followed
+        let client = reqwest::Client::builder()
+            .timeout(StdDuration::from_secs(config.request_timeout_seconds))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            allowed_hosts: config.webhook_allowed_hosts.clone(),
+            max_retries: config.webhook_max_retries,
+            retry_base_delay: StdDuration::from_millis(config.webhook_retry_base_delay_ms),
+            dead_letter_path: config.webhook_dead_letter_path.clone(),
+        }
+    }
+
+    /// Sends a notification. It is either delivered or, once retries are exhausted or the
+    /// destination is refused, appended to the dead-letter log; an error means neither.
+    pub async fn notify(&self, channel: &NotificationChannel, notification: &AttendanceNotification) -> Result<Delivery, AppError> {
+        let text = format!("AIMS: {}", notification.summary);
+
+        match channel {
+            NotificationChannel::Webhook { url, secret } => {
+                let body = serde_json::to_vec(notification)?;
+                // Checked again at delivery since the host may resolve elsewhere by now
+                if let Err(e) = check_destination(url, &self.allowed_hosts).await {
+                    return self.dead_letter(url, &body, 0, &e.to_string()).await;
+                }
+                self.deliver(url, Some(secret.expose()), url, body).await
+            }
+            NotificationChannel::Discord { webhook_url } => {
+                let body = serde_json::to_vec(&serde_json::json!({ "content": text }))?;
+                // Discord webhook URLs embed a token, so keep them out of the logs
+                if let Err(e) = check_destination(webhook_url, &self.allowed_hosts).await {
+                    return self.dead_letter("discord", &body, 0, &e.to_string()).await;
+                }
+                self.deliver(webhook_url, None, "discord", body).await
+            }
+            NotificationChannel::Telegram { bot_token, chat_id } => {
+                let url = format!("{}/bot{}/sendMessage", TELEGRAM_API_BASE, bot_token.expose());
+                let body = serde_json::to_vec(&serde_json::json!({ "chat_id": chat_id, "text": text }))?;
+                self.deliver(&url, None, "telegram", body).await
+            }
+        }
+    }
+
+    /// Records a notification that will not be sent, such as one over the subscriber's
+    /// hourly quota, in the dead-letter log.
+    pub async fn hold(&self, channel: &NotificationChannel, notification: &AttendanceNotification, reason: &str) -> Result<Delivery, AppError> {
+        let destination = match channel {
+            NotificationChannel::Webhook { url, .. } => url.as_str(),
+            NotificationChannel::Discord { .. } => "discord",
+            NotificationChannel::Telegram { .. } => "telegram",
+        };
+        self.dead_letter(destination, &serde_json::to_vec(notification)?, 0, reason).await
+    }
+
+    /// Delivers a payload, retrying with exponential backoff and signing it when a
+    /// secret is given. Payloads that still fail are appended to the dead-letter log.
+    async fn deliver(&self, url: &str, secret: Option<&str>, destination: &str, body: Vec<u8>) -> Result<Delivery, AppError> {
+        let mut last_error = String::new();
+
+        for attempt in 0..=self.max_retries {
+            let mut request = self
+                .client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(body.clone());
+
+            // Re-sign every attempt so receivers can reject stale timestamps
+            if let Some(secret) = secret {
+                let timestamp = Utc::now().timestamp();
+                request = request
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(SIGNATURE_HEADER, format!("sha256={}", sign_payload(secret, timestamp, &body)));
+            }
+
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    info!("[Webhook] Delivered to {} (attempt {})", destination, attempt + 1);
+                    return Ok(Delivery::Delivered);
+                }
+                Ok(response) => last_error = format!("HTTP {}", response.status()),
+                Err(e) => last_error = e.without_url().to_string(),
+            }
+
+            warn!("[Webhook] Delivery to {} failed (attempt {}): {}", destination, attempt + 1, last_error);
+
+            if attempt < self.max_retries {
+                tokio::time::sleep(self.retry_base_delay * 2_u32.pow(attempt as u32)).await;
+            }
+        }
+
+        self.dead_letter(destination, &body, self.max_retries + 1, &last_error).await
+    }
+
+    async fn dead_letter(&self, destination: &str, body: &[u8], attempts: usize, last_error: &str) -> Result<Delivery, AppError> {
+        let entry = serde_json::json!({
+            "failed_at": Utc::now().to_rfc3339(),
+            "destination": destination,
+            "attempts": attempts,
+            "error": last_error,
+            "payload": serde_json::from_slice::<serde_json::Value>(body).unwrap_or_default(),
+        });
+
+        let result = async {
+            if let Some(parent) = std::path::Path::new(&self.dead_letter_path).parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.dead_letter_path)
+                .await?;
+            file.write_all(format!("{}\n", entry).as_bytes()).await?;
+            file.flush().await
+        }.await;
+
+        match result {
+            Ok(()) => {
+                warn!("[Webhook] Dead-lettered payload for {}: {}", destination, last_error);
+                Ok(Delivery::DeadLettered)
+            }
+            Err(e) => {
+                error!("[Webhook] Failed to write dead-letter log: {}", e);
+                Err(AppError::ExternalApiError(format!("Notification for {} was lost: {}", destination, last_error)))
+            }
+        }
+    }
+}
+
+// Per-subscription poller bookkeeping. `below_threshold` and `unreported` only move on
+// once a notification is delivered or dead-lettered, so a lost one is tried again.
+struct PollState {
+    next_due: Instant,
+    below_threshold: bool,
+    unreported: Vec<AbsenceRecord>,
+    auth_failures: u32,
+    quota: u32,
+    limiter: Arc<DefaultDirectRateLimiter>,
+}
+
+// Re-fetches attendance for subscribed students on their own interval and reports
+// new absences or threshold crossings
+pub struct AbsencePoller {
+    tunables: Arc<LiveTunables>,
+    client: Arc<AimsClient>,
+    subscriptions: Arc<SubscriptionStore>,
+    notifier: Arc<WebhookNotifier>,
+    tracker: ChangeTracker,
+    states: Mutex<HashMap<String, PollState>>,
+}
+
+impl AbsencePoller {
+    pub fn new(
+        tunables: Arc<LiveTunables>,
+        client: Arc<AimsClient>,
+        subscriptions: Arc<SubscriptionStore>,
+        notifier: Arc<WebhookNotifier>,
+    ) -> Self {
+        Self {
+            tunables,
+            client,
+            subscriptions,
+            notifier,
+            tracker: ChangeTracker::new(),
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Polls until `cancel` fires; a poll already under way finishes first.
+    pub async fn run(self, cancel: CancellationToken) {
+        let mut ticker = tokio::time::interval(StdDuration::from_secs(POLL_TICK_SECONDS));
+        info!("[Poller] Checking subscriptions every {}s", POLL_TICK_SECONDS);
+
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = ticker.tick() => self.poll_once().await,
+            }
+        }
+        info!("[Poller] Stopped");
+    }
+
+    pub async fn poll_once(&self) {
+        let watches = self.subscriptions.watches().await;
+        let now = Instant::now();
+
+        let min_poll_interval_seconds = self.tunables.get().min_poll_interval_seconds;
+        let due: Vec<Watch> = {
+            let mut states = self.states.lock().await;
+            states.retain(|id, _| watches.iter().any(|w| &w.subscription_id == id));
+
+            watches
+                .into_iter()
+                .filter(|watch| {
+                    let quota = watch.thresholds.max_notifications_per_hour.max(1);
+                    let state = states.entry(watch.subscription_id.clone()).or_insert_with(|| PollState {
+                        next_due: now,
+                        below_threshold: false,
+                        unreported: Vec::new(),
+                        auth_failures: 0,
+                        quota,
+                        limiter: Arc::new(hourly_limiter(quota)),
+                    });
+
+                    if state.quota != quota {
+                        state.quota = quota;
+                        state.limiter = Arc::new(hourly_limiter(quota));
+                    }
+
+                    if state.next_due > now {
+                        return false;
+                    }
+                    let interval = watch.poll_interval_seconds.max(min_poll_interval_seconds);
+                    state.next_due = now + StdDuration::from_secs(interval);
+                    true
+                })
+                .collect()
+        };
+
+        for watch in due {
+            if let Err(e) = self.check_student(&watch).await {
+                warn!("[Poller] Check failed for student {}: {}", watch.student_id, e);
+            }
+        }
+    }
+
+    async fn check_student(&self, watch: &Watch) -> Result<(), AppError> {
+        let full = match self.client.full_attendance(watch.token.expose()).await {
+            Ok(full) => full,
+            Err(AppError::UpstreamUnauthorized(reason)) => return self.token_rejected(watch, &reason).await,
+            Err(e) => return Err(e),
+        };
+        let changes = self.tracker.observe(&full).await;
+
+        let summary = aggregation::summarize(&full);
+        let attended = summary.total_present_all_subjects + summary.total_absent_all_subjects;
+        let overall_percentage = if attended > 0 {
+            summary.total_present_all_subjects as f64 * 100.0 / attended as f64
+        } else {
+            100.0
+        };
+        let below = watch.thresholds.min_percentage.is_some_and(|min| overall_percentage < min);
+
+        let new = match &changes {
+            Some(changes) if watch.thresholds.notify_on_absence => new_absences(changes),
+            _ => Vec::new(),
+        };
+
+        let (earlier, crossed_threshold, limiter) = {
+            let mut states = self.states.lock().await;
+            let Some(state) = states.get_mut(&watch.subscription_id) else {
+                return Ok(());
+            };
+            state.auth_failures = 0;
+
+            let crossed = below && !state.below_threshold;
+            // Climbing back above the threshold needs no notification
+            if !below {
+                state.below_threshold = false;
+            }
+            (state.unreported.clone(), crossed, state.limiter.clone())
+        };
+
+        if new.is_empty() && earlier.is_empty() && !crossed_threshold {
+            return Ok(());
+        }
+
+        let mut parts: Vec<String> = changes
+            .iter()
+            .flat_map(|c| c.subjects.iter().map(|s| s.summary.clone()))
+            .collect();
+        if !earlier.is_empty() {
+            parts.push(format!("{} earlier absence(s) not reported yet", earlier.len()));
+        }
+        if crossed_threshold {
+            parts.push(format!(
+                "Overall attendance {:.1}% is below {:.1}%",
+                overall_percentage,
+                watch.thresholds.min_percentage.unwrap_or_default()
+            ));
+        }
+
+        info!("[Poller] {} new absence(s) for student {}", new.len(), watch.student_id);
+
+        let absences: Vec<AbsenceRecord> = earlier.into_iter().chain(new).collect();
+        let notification = AttendanceNotification {
+            event: if absences.is_empty() { "attendance.threshold" } else { "attendance.absence" }.to_string(),
+            student_id: full.student_id.clone(),
+            detected_at: Utc::now(),
+            summary: parts.join("; "),
+            overall_percentage: Some(overall_percentage),
+            absences,
+        };
+
+        let outcome = if limiter.check().is_err() {
+            warn!("[Poller] Notification rate limit reached for subscription {}", watch.subscription_id);
+            self.notifier.hold(&watch.channel, &notification, "notification rate limit reached").await
+        } else {
+            self.notifier.notify(&watch.channel, &notification).await
+        };
+
+        let mut states = self.states.lock().await;
+        let Some(state) = states.get_mut(&watch.subscription_id) else {
+            return outcome.map(|_| ());
+        };
+        match outcome {
+            Ok(_) => {
+                state.unreported.clear();
+                state.below_threshold = below;
+                Ok(())
+            }
+            // Keep the absences for the next poll; the threshold crossing is found again
+            Err(e) => {
+                state.unreported = notification.absences;
+                Err(e)
+            }
+        }
+    }
+
+    // The poller cannot log in again, so once the portal keeps rejecting the stored token
+    // the subscriber is told and the subscription is dropped rather than failing every poll
+    async fn token_rejected(&self, watch: &Watch, reason: &str) -> Result<(), AppError> {
+        let failures = {
+            let mut states = self.states.lock().await;
+            let Some(state) = states.get_mut(&watch.subscription_id) else {
+                return Ok(());
+            };
+            state.auth_failures += 1;
+            state.auth_failures
+        };
+
+        if failures < MAX_AUTH_FAILURES {
+            return Err(AppError::UpstreamUnauthorized(format!(
+                "{} ({} of {} before the subscription is disabled)",
+                reason, failures, MAX_AUTH_FAILURES
+            )));
+        }
+
+        warn!("[Poller] Disabling subscription {}: the portal keeps rejecting its token", watch.subscription_id);
+        let notification = AttendanceNotification {
+            event: "subscription.disabled".to_string(),
+            student_id: watch.student_id.clone(),
+            detected_at: Utc::now(),
+            summary: "The portal no longer accepts the saved login, so notifications have stopped. Subscribe again to resume them.".to_string(),
+            overall_percentage: None,
+            absences: Vec::new(),
+        };
+        // The token cannot work again, so the subscription goes even if the notice is lost
+        if let Err(e) = self.notifier.notify(&watch.channel, &notification).await {
+            warn!("[Poller] Could not tell student {} about the disabled subscription: {}", watch.student_id, e);
+        }
+
+        self.states.lock().await.remove(&watch.subscription_id);
+        self.subscriptions.remove_if_unchanged(&watch.student_id, watch.updated_at).await?;
+        Ok(())
+    }
+}
+
+fn hourly_limiter(quota: u32) -> DefaultDirectRateLimiter {
+    RateLimiter::direct(Quota::per_hour(NonZeroU32::new(quota).unwrap_or(NonZeroU32::MIN)))
+}
+
+// New Absent records plus records corrected to Absent upstream
+fn new_absences(changes: &AttendanceChanges) -> Vec<AbsenceRecord> {
+    let mut absences = Vec::new();
+
+    for subject in &changes.subjects {
+        let added = subject
+            .new_records
+            .iter()
+            .filter(|r| r.state == "Absent")
+            .map(|r| (&r.date, &r.start_time));
+        let corrected = subject
+            .state_changes
+            .iter()
+            .filter(|r| r.state == "Absent")
+            .map(|r| (&r.date, &r.start_time));
+
+        for (date, start_time) in added.chain(corrected) {
+            absences.push(AbsenceRecord {
+                subject: subject.subject.clone(),
+                date: date.clone(),
+                start_time: start_time.clone(),
+            });
+        }
+    }
+
+    absences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::{HeaderMap, StatusCode}, routing::post, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    type Received = Arc<tokio::sync::Mutex<Vec<(HeaderMap, Vec<u8>)>>>;
+
+    #[derive(Clone, Default)]
+    struct Sink {
+        failures_left: Arc<AtomicUsize>,
+        received: Received,
+    }
+
+    async fn sink_handler(State(sink): State<Sink>, headers: HeaderMap, body: axum::body::Bytes) -> StatusCode {
+        if sink.failures_left.load(Ordering::SeqCst) > 0 {
+            sink.failures_left.fetch_sub(1, Ordering::SeqCst);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+        sink.received.lock().await.push((headers, body.to_vec()));
+        StatusCode::OK
+    }
+
+    async fn start_sink(failures: usize) -> (String, Sink) {
+        let sink = Sink::default();
+        sink.failures_left.store(failures, Ordering::SeqCst);
+
+        let app = Router::new().route("/hook", post(sink_handler)).with_state(sink.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{}/hook", addr), sink)
+    }
+
+    fn notifier(dead_letter_path: &str) -> WebhookNotifier {
+        let mut config = Config::defaults();
+        config.webhook_max_retries = 2;
+        config.webhook_retry_base_delay_ms = 1;
+        config.webhook_dead_letter_path = dead_letter_path.to_string();
+        config.webhook_allowed_hosts = vec!["127.0.0.1".to_string()];
+        WebhookNotifier::new(&config)
+    }
+
+    fn notification() -> AttendanceNotification {
+        AttendanceNotification {
+            event: "attendance.absence".to_string(),
+            student_id: "2023001".to_string(),
+            detected_at: Utc::now(),
+            summary: "1 new lecture marked in DBMS, 1 absent".to_string(),
+            overall_percentage: Some(80.0),
+            absences: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload_after_retries() {
+        let (url, sink) = start_sink(2).await;
+        let channel = NotificationChannel::Webhook { url, secret: Secret::new("s3cret".to_string()) };
+        let dead_letter = std::env::temp_dir().join(format!("aims-dl-{}.jsonl", uuid::Uuid::new_v4()));
+
+        let delivery = notifier(dead_letter.to_str().unwrap()).notify(&channel, &notification()).await.unwrap();
+        assert_eq!(delivery, Delivery::Delivered);
+
+        let received = sink.received.lock().await;
+        assert_eq!(received.len(), 1);
+
+        let (headers, body) = &received[0];
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        let expected = format!("sha256={}", sign_payload("s3cret", timestamp, body));
+        assert_eq!(headers[SIGNATURE_HEADER].to_str().unwrap(), expected);
+        assert!(!dead_letter.exists());
+    }
+
+    #[tokio::test]
+    async fn dead_letters_after_exhausting_retries() {
+        let (url, sink) = start_sink(10).await;
+        let channel = NotificationChannel::Webhook { url: url.clone(), secret: Secret::new("s3cret".to_string()) };
+        let dead_letter = std::env::temp_dir().join(format!("aims-dl-{}.jsonl", uuid::Uuid::new_v4()));
+
+        let delivery = notifier(dead_letter.to_str().unwrap()).notify(&channel, &notification()).await.unwrap();
+
+        assert_eq!(delivery, Delivery::DeadLettered);
+        assert!(sink.received.lock().await.is_empty());
+
+        let log = std::fs::read_to_string(&dead_letter).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(entry["destination"], url);
+        assert_eq!(entry["attempts"], 3);
+        assert_eq!(entry["payload"]["event"], "attendance.absence");
+
+        std::fs::remove_file(dead_letter).ok();
+    }
+
+    #[tokio::test]
+    async fn disables_subscription_after_repeated_token_rejections() {
+        let portal = Router::new().fallback(|| async { StatusCode::UNAUTHORIZED });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let portal_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, portal).await.unwrap() });
+
+        let (url, sink) = start_sink(0).await;
+        let dir = std::env::temp_dir().join(format!("aims-poller-{}", uuid::Uuid::new_v4()));
+        let keyring = Arc::new(crate::secrets::Keyring::parse("test:MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=").unwrap());
+        let store = Arc::new(
+            SubscriptionStore::open(dir.join("subscriptions.json").to_str().unwrap(), keyring).await.unwrap(),
+        );
+        let channel = NotificationChannel::Webhook { url, secret: Secret::new("s3cret".to_string()) };
+        store.upsert("2023001", "expired-token", &channel, SubscriptionThresholds::default(), 900).await.unwrap();
+
+        let poller = AbsencePoller::new(
+            Arc::new(LiveTunables::new(Config::defaults().tunables())),
+            Arc::new(AimsClient::new(&portal_url)),
+            store.clone(),
+            Arc::new(notifier(dir.join("dead_letter.jsonl").to_str().unwrap())),
+        );
+        poller.poll_once().await;
+        let watch = store.watches().await.remove(0);
+        assert!(!format!("{:?}", watch).contains("s3cret"));
+        for _ in 1..MAX_AUTH_FAILURES {
+            assert!(store.get("2023001").await.is_some());
+            poller.check_student(&watch).await.ok();
+        }
+
+        assert!(store.get("2023001").await.is_none());
+        let received = sink.received.lock().await;
+        let notice: serde_json::Value = serde_json::from_slice(&received[0].1).unwrap();
+        assert_eq!(notice["event"], "subscription.disabled");
+        assert!(notice.get("overall_percentage").is_none());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -63,6 +63,32 @@ impl PerformanceMonitor {
         error!("[Performance] {} error: {}", route, error_type);
     }
 
+    /// Counts re-logins triggered by expired portal tokens; outcome is "success" or "failure".
+    pub async fn record_reauth(&self, outcome: &str) {
+        {
+            let mut counters = self.request_counters.write().await;
+            *counters.entry(format!("upstream_reauth_{}", outcome)).or_insert(0) += 1;
+        }
+
+        counter!("upstream_reauth_total", 1, "outcome" => outcome.to_string());
+
+        info!("[Performance] upstream re-auth: {}", outcome);
+    }
+
+    /// Requests and errors recorded since startup, for the shutdown summary.
+    pub async fn totals(&self) -> (u64, u64) {
+        let requests = self
+            .request_counters
+            .read()
+            .await
+            .iter()
+            .filter(|(key, _)| !key.starts_with("upstream_reauth_"))
+            .map(|(_, count)| count)
+            .sum();
+        let errors = self.error_counters.read().await.values().sum();
+        (requests, errors)
+    }
+
     pub async fn get_metrics(&self) -> HashMap<String, serde_json::Value> {
         let mut result = HashMap::new();
         
@@ -88,6 +114,7 @@ impl PerformanceMonitor {
         result
     }
 
+    #[allow(dead_code)]
     pub async fn clear_old_metrics(&self, max_age_hours: i64) {
         let cutoff = Utc::now() - chrono::Duration::hours(max_age_hours);
         
@@ -99,6 +126,7 @@ impl PerformanceMonitor {
         info!("[Performance] Cleared metrics older than {} hours", max_age_hours);
     }
 
+    #[allow(dead_code)]
     pub async fn get_route_stats(&self, route: &str) -> HashMap<String, serde_json::Value> {
         let mut stats = HashMap::new();
         
@@ -0,0 +1,434 @@
+use std::collections::BTreeMap;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{error::AppError, models::*};
+
+// Portal field names for each modelled field, in order of preference
+const ID_FIELDS: &[&str] = &["id", "quiz_id"];
+const TITLE_FIELDS: &[&str] = &["quiz_name", "title", "name"];
+const SUBJECT_FIELDS: &[&str] = &["master_course_code", "course_code", "subject"];
+const DATE_FIELDS: &[&str] = &["loggedin_at", "start_time", "login_time", "date"];
+const MARKS_FIELDS: &[&str] = &["marks_obtained", "marks", "score"];
+const MAX_MARKS_FIELDS: &[&str] = &["max_marks", "total_marks", "maximum_marks"];
+const RANK_FIELDS: &[&str] = &["rank", "class_rank"];
+const PERCENTILE_FIELDS: &[&str] = &["percentile", "class_percentile"];
+
+// Slopes smaller than this (marks per week) count as a steady trend
+const TREND_THRESHOLD: f64 = 0.1;
+const MIN_TREND_POINTS: usize = 3;
+
+// UTC+05:30
+const PORTAL_UTC_OFFSET_SECONDS: i32 = 5 * 3600 + 30 * 60;
+
+/// Normalizes the `myEvaluatedQuizzes` payload. The portal wraps the list as
+/// `{ response: { data: [...] } }`, but a bare array is accepted too.
+pub fn normalize(payload: &Value) -> Vec<Quiz> {
+    payload["response"]["data"]
+        .as_array()
+        .or_else(|| payload.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_object().cloned().map(normalize_quiz))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn normalize_quiz(mut fields: Map<String, Value>) -> Quiz {
+    let id = take(&mut fields, ID_FIELDS, as_string);
+    let title = take(&mut fields, TITLE_FIELDS, as_string);
+    let subject = take(&mut fields, SUBJECT_FIELDS, as_string);
+    let date = take(&mut fields, DATE_FIELDS, |v| v.as_str().and_then(parse_timestamp));
+    let marks_obtained = take(&mut fields, MARKS_FIELDS, as_f64);
+    let max_marks = take(&mut fields, MAX_MARKS_FIELDS, as_f64);
+    let correct = take(&mut fields, &["correct"], as_u32);
+    let incorrect = take(&mut fields, &["incorrect"], as_u32);
+    let not_attempted = take(&mut fields, &["not_attempted"], as_u32);
+    let rank = take(&mut fields, RANK_FIELDS, as_u32);
+    let percentile = take(&mut fields, PERCENTILE_FIELDS, as_f64);
+
+    Quiz {
+        id,
+        title,
+        subject,
+        date,
+        marks_obtained,
+        max_marks,
+        correct,
+        incorrect,
+        not_attempted,
+        rank,
+        percentile,
+        extra: fields,
+    }
+}
+
+// Removes the first field that parses; values that do not parse stay in `extra`
+fn take<T>(fields: &mut Map<String, Value>, names: &[&str], parse: impl Fn(&Value) -> Option<T>) -> Option<T> {
+    let name = names.iter().find(|name| fields.get(**name).and_then(&parse).is_some())?;
+    fields.remove(*name).as_ref().and_then(parse)
+}
+
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+// The portal sends numbers both as JSON numbers and as strings
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+    .filter(|n: &f64| n.is_finite())
+}
+
+fn as_u32(value: &Value) -> Option<u32> {
+    as_f64(value).filter(|n| *n >= 0.0 && n.fract() == 0.0 && *n <= u32::MAX as f64).map(|n| n as u32)
+}
+
+/// Checksum of normalized quiz data, used to tell whether cached analytics are stale.
+pub fn checksum(quizzes: &[Quiz]) -> String {
+    let bytes = serde_json::to_vec(quizzes).unwrap_or_default();
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Derives per-subject and overall statistics. Quizzes without marks are ignored.
+pub fn analyze(quizzes: &[Quiz], now: DateTime<Utc>) -> QuizAnalyticsResponse {
+    let scored: Vec<&Quiz> = quizzes.iter().filter(|q| q.marks_obtained.is_some()).collect();
+
+    let mut by_subject: BTreeMap<String, Vec<&Quiz>> = BTreeMap::new();
+    for quiz in &scored {
+        let subject = quiz.subject.clone().unwrap_or_else(|| "Unknown".to_string());
+        by_subject.entry(subject).or_default().push(quiz);
+    }
+
+    let subjects = by_subject
+        .into_iter()
+        .filter_map(|(subject, quizzes)| {
+            Some(SubjectQuizStats {
+                subject,
+                quiz_count: quizzes.len(),
+                average_score: average(quizzes.iter().filter_map(|q| q.marks_obtained))?,
+                average_percentage: average(quizzes.iter().filter_map(|q| percentage(q))),
+                average_percentile: average(quizzes.iter().filter_map(|q| q.percentile)),
+                best: extreme(&quizzes, true)?,
+                worst: extreme(&quizzes, false)?,
+                trend: trend(&quizzes),
+            })
+        })
+        .collect();
+
+    QuizAnalyticsResponse {
+        schema_version: QUIZ_SCHEMA_VERSION,
+        quiz_count: scored.len(),
+        average_score: average(scored.iter().filter_map(|q| q.marks_obtained)),
+        average_percentile: average(scored.iter().filter_map(|q| q.percentile)),
+        best: extreme(&scored, true),
+        worst: extreme(&scored, false),
+        trend: trend(&scored),
+        subjects,
+        source_checksum: checksum(quizzes),
+        computed_at: now,
+    }
+}
+
+fn percentage(quiz: &Quiz) -> Option<f64> {
+    let max = quiz.max_marks.filter(|m| *m > 0.0)?;
+    Some(quiz.marks_obtained? / max * 100.0)
+}
+
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| round2(sum / count as f64))
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+// Highest (or lowest) marks; ties go to the earliest quiz
+fn extreme(quizzes: &[&Quiz], best: bool) -> Option<QuizSummary> {
+    let mut ordered: Vec<&&Quiz> = quizzes.iter().collect();
+    ordered.sort_by_key(|q| q.date);
+
+    let quiz = ordered.into_iter().reduce(|chosen, candidate| {
+        let (c, n) = (chosen.marks_obtained.unwrap_or_default(), candidate.marks_obtained.unwrap_or_default());
+        if (best && n > c) || (!best && n < c) { candidate } else { chosen }
+    })?;
+
+    Some(QuizSummary {
+        id: quiz.id.clone(),
+        title: quiz.title.clone(),
+        subject: quiz.subject.clone(),
+        date: quiz.date,
+        marks_obtained: quiz.marks_obtained?,
+        max_marks: quiz.max_marks,
+    })
+}
+
+fn trend(quizzes: &[&Quiz]) -> QuizTrend {
+    let mut points: Vec<QuizTrendPoint> = quizzes
+        .iter()
+        .filter_map(|q| {
+            Some(QuizTrendPoint {
+                date: q.date?,
+                subject: q.subject.clone(),
+                marks_obtained: q.marks_obtained?,
+            })
+        })
+        .collect();
+    points.sort_by_key(|p| p.date);
+
+    let marks_per_week = slope(&points);
+    let direction = match marks_per_week {
+        None => "insufficient_data",
+        Some(s) if s > TREND_THRESHOLD => "improving",
+        Some(s) if s < -TREND_THRESHOLD => "declining",
+        Some(_) => "steady",
+    };
+
+    QuizTrend {
+        direction: direction.to_string(),
+        marks_per_week: marks_per_week.map(round2),
+        points,
+    }
+}
+
+// Least-squares fit of marks against weeks since the first quiz
+fn slope(points: &[QuizTrendPoint]) -> Option<f64> {
+    if points.len() < MIN_TREND_POINTS {
+        return None;
+    }
+
+    let first = points[0].date;
+    let xs: Vec<f64> = points
+        .iter()
+        .map(|p| (p.date - first).num_seconds() as f64 / (7.0 * 24.0 * 3600.0))
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.marks_obtained).sum::<f64>() / n;
+
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, p) in xs.iter().zip(points) {
+        covariance += (x - mean_x) * (p.marks_obtained - mean_y);
+        variance += (x - mean_x).powi(2);
+    }
+
+    // All quizzes on the same instant
+    (variance > f64::EPSILON).then(|| covariance / variance)
+}
+
+/// Parses portal timestamps. Naive values are in the portal's local time (IST), which is
+/// also how the browser interpreted them before this logic moved server-side.
+pub fn parse_timestamp(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    DateTime::parse_from_rfc3339(raw)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+                .iter()
+                .find_map(|format| chrono::NaiveDateTime::parse_from_str(raw, format).ok())?;
+            let offset = FixedOffset::east_opt(PORTAL_UTC_OFFSET_SECONDS)?;
+            naive.and_local_timezone(offset).single().map(|dt| dt.with_timezone(&Utc))
+        })
+}
+
+/// Decides whether a quiz can be opened now from the quiz details response.
+pub fn start_status(
+    details: &Value,
+    request: &StartQuizRequest,
+    access_url: &str,
+    now: DateTime<Utc>,
+) -> Result<QuizStartResponse, AppError> {
+    let quiz = &details["response"]["data"];
+    let has_quiz = match quiz {
+        Value::Object(fields) => !fields.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    };
+    if details["msg"].as_str() == Some("Invalid Quiz ID") || !has_quiz {
+        return Err(AppError::NotFound("Invalid quiz code".to_string()));
+    }
+
+    // The function has been seen returning both a single object and a one-element list
+    let quiz = if quiz.is_array() { &quiz[0] } else { quiz };
+
+    let starts_at = quiz["login_time"]
+        .as_str()
+        .and_then(parse_timestamp)
+        .ok_or_else(|| AppError::ExternalApiError("Quiz details are missing a valid login_time".to_string()))?;
+    let ends_at = quiz["end_time"].as_str().and_then(parse_timestamp);
+
+    if now < starts_at {
+        return Ok(QuizStartResponse::NotStarted {
+            starts_at,
+            ends_at,
+            seconds_remaining: (starts_at - now).num_seconds(),
+        });
+    }
+
+    if let Some(ended_at) = ends_at.filter(|end| now >= *end) {
+        return Ok(QuizStartResponse::Ended { ended_at });
+    }
+
+    let cf_id = as_string(&quiz["cf_id"])
+        .ok_or_else(|| AppError::ExternalApiError("Quiz details are missing cf_id".to_string()))?;
+    let req_id = format!(
+        "{}_{}_{}_{}",
+        now.format("%Y-%m-%d"),
+        request.user_unique_code,
+        request.quiz_code,
+        cf_id
+    );
+
+    Ok(QuizStartResponse::Active {
+        ends_at,
+        seconds_remaining: ends_at.map(|end| (end - now).num_seconds()),
+        access_url: format!("{}?req_id={}", access_url, urlencoding::encode(&BASE64.encode(req_id))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn normalizes_portal_payload_leniently() {
+        let payload = json!({
+            "response": {
+                "data": [
+                    {
+                        "id": 981,
+                        "master_course_code": "BCS501",
+                        "student_name": "Test Student",
+                        "marks_obtained": "7.5",
+                        "total_marks": 10,
+                        "correct": 15,
+                        "incorrect": "3",
+                        "not_attempted": 2,
+                        "loggedin_at": "2024-03-01 09:30:00",
+                        "quiz_link": "<a href=\"#/quiz?pin=1234\">Open</a>"
+                    },
+                    { "quiz_name": "Surprise", "marks_obtained": "n/a", "rank": 4 },
+                    "not an object"
+                ]
+            }
+        });
+
+        let quizzes = normalize(&payload);
+        assert_eq!(quizzes.len(), 2);
+
+        let first = &quizzes[0];
+        assert_eq!(first.id.as_deref(), Some("981"));
+        assert_eq!(first.subject.as_deref(), Some("BCS501"));
+        assert_eq!(first.marks_obtained, Some(7.5));
+        assert_eq!(first.max_marks, Some(10.0));
+        assert_eq!(first.incorrect, Some(3));
+        assert_eq!(first.date.map(|d| d.to_rfc3339()).as_deref(), Some("2024-03-01T04:00:00+00:00"));
+        assert!(first.extra.contains_key("student_name"));
+        assert!(first.extra.contains_key("quiz_link"));
+        assert!(!first.extra.contains_key("marks_obtained"));
+
+        let second = &quizzes[1];
+        assert_eq!(second.title.as_deref(), Some("Surprise"));
+        assert_eq!(second.marks_obtained, None);
+        assert_eq!(second.rank, Some(4));
+        assert_eq!(second.extra["marks_obtained"], json!("n/a"));
+    }
+
+    #[test]
+    fn computes_per_subject_analytics() {
+        let quiz = |subject: &str, day: u32, marks: f64| {
+            normalize(&json!([{
+                "master_course_code": subject,
+                "marks_obtained": marks,
+                "max_marks": 10,
+                "percentile": 50 + day,
+                "loggedin_at": format!("2024-03-{:02} 10:00:00", day)
+            }]))
+            .remove(0)
+        };
+        let quizzes = vec![
+            quiz("BCS501", 1, 4.0),
+            quiz("BCS501", 8, 6.0),
+            quiz("BCS501", 15, 8.0),
+            quiz("BCS502", 2, 9.0),
+        ];
+
+        let analytics = analyze(&quizzes, Utc::now());
+        assert_eq!(analytics.quiz_count, 4);
+        assert_eq!(analytics.best.as_ref().and_then(|q| q.subject.as_deref()), Some("BCS502"));
+        assert_eq!(analytics.worst.as_ref().map(|q| q.marks_obtained), Some(4.0));
+
+        let dbms = &analytics.subjects[0];
+        assert_eq!(dbms.subject, "BCS501");
+        assert_eq!(dbms.average_score, 6.0);
+        assert_eq!(dbms.average_percentage, Some(60.0));
+        assert_eq!(dbms.average_percentile, Some(58.0));
+        assert_eq!(dbms.trend.direction, "improving");
+        assert_eq!(dbms.trend.marks_per_week, Some(2.0));
+        assert_eq!(analytics.subjects[1].trend.direction, "insufficient_data");
+
+        assert_eq!(analytics.source_checksum, checksum(&quizzes));
+        assert_ne!(checksum(&quizzes[..3]), analytics.source_checksum);
+    }
+
+    #[test]
+    fn reports_quiz_start_status() {
+        let request = StartQuizRequest {
+            quiz_code: "AB12".to_string(),
+            user_unique_code: "2200320100001".to_string(),
+            pin: "4321".to_string(),
+        };
+        let details = json!({
+            "response": {
+                "data": {
+                    "cf_id": 77,
+                    "login_time": "2024-03-01 10:00:00",
+                    "end_time": "2024-03-01T05:00:00Z"
+                }
+            }
+        });
+        let at = |h, m| DateTime::parse_from_rfc3339(&format!("2024-03-01T{:02}:{:02}:00Z", h, m)).unwrap().with_timezone(&Utc);
+        let status = |now| start_status(&details, &request, "https://quiz.example/#/access-quiz", now).unwrap();
+
+        match status(at(4, 20)) {
+            QuizStartResponse::NotStarted { seconds_remaining, .. } => assert_eq!(seconds_remaining, 10 * 60),
+            other => panic!("unexpected status: {:?}", other),
+        }
+        match status(at(4, 40)) {
+            QuizStartResponse::Active { seconds_remaining, access_url, .. } => {
+                assert_eq!(seconds_remaining, Some(20 * 60));
+                assert!(access_url.starts_with("https://quiz.example/#/access-quiz?req_id="));
+                assert!(!access_url.contains("4321"));
+            }
+            other => panic!("unexpected status: {:?}", other),
+        }
+        assert!(matches!(status(at(5, 0)), QuizStartResponse::Ended { .. }));
+
+        let invalid = json!({ "msg": "Invalid Quiz ID", "response": { "data": [] } });
+        assert!(matches!(
+            start_status(&invalid, &request, "", at(4, 40)),
+            Err(AppError::NotFound(_))
+        ));
+        assert!(!format!("{:?}", request).contains("4321"));
+    }
+
+    #[test]
+    fn accepts_bare_arrays() {
+        assert_eq!(normalize(&json!([{ "id": "a" }])).len(), 1);
+        assert!(normalize(&json!({ "message": "nope" })).is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+// Redis backend for the cache, on the `redis` crate's connection manager. Keys are namespaced
+// under `aims:` so a shared server is safe to use.
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, aio::ConnectionManagerConfig, AsyncCommands, Script};
+use tokio::sync::OnceCell;
+
+use crate::{cache_backend::CacheBackend, config::Config, error::AppError};
+
+const KEY_PREFIX: &str = "aims:";
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+// Connection attempts after the first, at most this far apart
+const CONNECT_RETRIES: usize = 2;
+const CONNECT_RETRY_MAX_DELAY_MS: u64 = 500;
+
+// Deletes the lock only while it still holds the caller's token, so a holder whose lock
+// lapsed cannot release the one another replica took after it
+const UNLOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+pub struct RedisBackend {
+    client: redis::Client,
+    // Connected on first use so startup does not depend on Redis being up
+    connection: OnceCell<ConnectionManager>,
+    // Owner token for each lock this process holds
+    lock_tokens: Mutex<HashMap<String, String>>,
+    unlock_script: Script,
+}
+
+impl RedisBackend {
+    /// From `redis_url`: `redis://[[username]:password@]host[:port][/database]`.
+    pub fn from_config(config: &Config) -> Result<Self, AppError> {
+        let url = config.redis_url.as_deref();
+        Self::from_url(url.ok_or_else(|| AppError::InternalError("redis_url is not set".to_string()))?)
+    }
+
+    pub fn from_url(redis_url: &str) -> Result<Self, AppError> {
+        if !redis_url.starts_with("redis://") {
+            return Err(AppError::InternalError("Invalid redis_url: the scheme must be redis://".to_string()));
+        }
+        let client = redis::Client::open(redis_url)
+            .map_err(|e| AppError::InternalError(format!("Invalid redis_url: {}", e)))?;
+
+        Ok(Self {
+            client,
+            connection: OnceCell::new(),
+            lock_tokens: Mutex::new(HashMap::new()),
+            unlock_script: Script::new(UNLOCK_SCRIPT),
+        })
+    }
+
+    // The manager reconnects by itself after a failure. A command that failed is reported,
+    // never resent, since it may already have reached the server.
+    async fn connection(&self) -> Result<ConnectionManager, AppError> {
+        let config = ConnectionManagerConfig::new()
+            .set_connection_timeout(COMMAND_TIMEOUT)
+            .set_response_timeout(COMMAND_TIMEOUT)
+            .set_number_of_retries(CONNECT_RETRIES)
+            .set_max_delay(CONNECT_RETRY_MAX_DELAY_MS);
+        self.connection
+            .get_or_try_init(|| ConnectionManager::new_with_config(self.client.clone(), config))
+            .await
+            .cloned()
+            .map_err(redis_error)
+    }
+}
+
+fn redis_error(error: redis::RedisError) -> AppError {
+    AppError::CacheError(format!("Redis error: {}", error))
+}
+
+// Escapes the glob characters SCAN's MATCH would interpret
+fn glob_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '*' | '?' | '[' | ']' | '\\') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+#[async_trait]
+impl CacheBackend for RedisBackend {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError> {
+        let key = format!("{}{}", KEY_PREFIX, key);
+        self.connection().await?.get(key).await.map_err(redis_error)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), AppError> {
+        let key = format!("{}{}", KEY_PREFIX, key);
+        let ttl = ttl.as_millis().max(1) as u64;
+        self.connection().await?.pset_ex(key, value, ttl).await.map_err(redis_error)
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, AppError> {
+        let key = format!("{}{}", KEY_PREFIX, key);
+        let removed: u64 = self.connection().await?.del(key).await.map_err(redis_error)?;
+        Ok(removed > 0)
+    }
+
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, AppError> {
+        let pattern = format!("{}*", glob_escape(&format!("{}{}", KEY_PREFIX, prefix)));
+        let mut connection = self.connection().await?;
+        let mut cursor = 0u64;
+        let mut keys = Vec::new();
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(1000)
+                .query_async(&mut connection)
+                .await
+                .map_err(redis_error)?;
+            keys.extend(batch.iter().filter_map(|key| key.strip_prefix(KEY_PREFIX)).map(str::to_string));
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        // SCAN may return a key more than once
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    async fn try_lock(&self, key: &str, ttl: Duration) -> Result<bool, AppError> {
+        let key = format!("{}{}", KEY_PREFIX, key);
+        let token = uuid::Uuid::new_v4().to_string();
+        let taken: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query_async(&mut self.connection().await?)
+            .await
+            .map_err(redis_error)?;
+
+        if taken.is_some() {
+            self.lock_tokens.lock().unwrap_or_else(|e| e.into_inner()).insert(key, token);
+        }
+        Ok(taken.is_some())
+    }
+
+    async fn unlock(&self, key: &str) -> Result<(), AppError> {
+        let key = format!("{}{}", KEY_PREFIX, key);
+        let token = self.lock_tokens.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+        // Not taken by this process, so not ours to release
+        let Some(token) = token else {
+            return Ok(());
+        };
+
+        let _: i64 = self
+            .unlock_script
+            .key(&key)
+            .arg(token)
+            .invoke_async(&mut self.connection().await?)
+            .await
+            .map_err(redis_error)?;
+        Ok(())
+    }
+}
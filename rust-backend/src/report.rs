@@ -0,0 +1,289 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::models::AttendanceResponse;
+
+// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const ROW_HEIGHT: f32 = 18.0;
+const ROWS_PER_PAGE: usize = 30;
+const MAX_COURSE_CHARS: usize = 52;
+
+// Table columns: course, present, total, percentage
+const COLUMNS: [f32; 4] = [MARGIN, 350.0, 420.0, 490.0];
+
+/// SHA-256 over the report data and generation time, identifying which data a report was
+/// rendered from. It is unkeyed, so anyone can recompute it for edited data; it is not a
+/// signature and does not prove a printout is genuine.
+pub fn content_hash(data: &AttendanceResponse, generated_at: DateTime<Utc>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data.student_id.as_bytes());
+    hasher.update(b"\n");
+    for course in &data.daily_attendance {
+        hasher.update(format!("{}|{}|{}|{:.2}\n", course.course, course.present, course.total, course.percent));
+    }
+    hasher.update(format!(
+        "{}|{}|{:.2}\n{}",
+        data.total_present,
+        data.total_classes,
+        data.overall_percentage,
+        generated_at.to_rfc3339()
+    ));
+    hex::encode(hasher.finalize())
+}
+
+/// Renders the attendance report as an uncompressed PDF. Output depends only on the
+/// inputs, which keeps it byte-for-byte comparable in tests.
+pub fn render_pdf(data: &AttendanceResponse, generated_at: DateTime<Utc>) -> Vec<u8> {
+    let hash = content_hash(data, generated_at);
+
+    let chunks: Vec<_> = if data.daily_attendance.is_empty() {
+        vec![&data.daily_attendance[..]]
+    } else {
+        data.daily_attendance.chunks(ROWS_PER_PAGE).collect()
+    };
+    let page_count = chunks.len();
+
+    let pages: Vec<String> = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, rows)| {
+            let mut page = PageContent::default();
+
+            let mut y = PAGE_HEIGHT - MARGIN - 10.0;
+            if index == 0 {
+                page.text(MARGIN, y, Font::Bold, 18.0, "Attendance Report");
+                y -= 30.0;
+
+                for (label, value) in [
+                    ("Student ID", &data.student_id),
+                    ("Batch", &data.batch),
+                    ("Section", &data.section),
+                    ("Branch", &data.branch),
+                ] {
+                    page.text(MARGIN, y, Font::Bold, 11.0, label);
+                    page.text(MARGIN + 90.0, y, Font::Regular, 11.0, value);
+                    y -= 16.0;
+                }
+                y -= 14.0;
+            }
+
+            for (x, header) in COLUMNS.iter().zip(["Course", "Present", "Total", "Percentage"]) {
+                page.text(*x, y, Font::Bold, 10.0, header);
+            }
+            page.rule(y - 6.0, 1.0);
+            y -= ROW_HEIGHT + 4.0;
+
+            for course in rows.iter() {
+                page.text(COLUMNS[0], y, Font::Regular, 10.0, &truncate(&course.course, MAX_COURSE_CHARS));
+                page.text(COLUMNS[1], y, Font::Regular, 10.0, &course.present.to_string());
+                page.text(COLUMNS[2], y, Font::Regular, 10.0, &course.total.to_string());
+                page.text(COLUMNS[3], y, Font::Regular, 10.0, &format!("{:.2}%", course.percent));
+                y -= ROW_HEIGHT;
+            }
+
+            if index + 1 == page_count {
+                page.rule(y + ROW_HEIGHT - 6.0, 0.5);
+                page.text(COLUMNS[0], y, Font::Bold, 10.0, "Overall");
+                page.text(COLUMNS[1], y, Font::Bold, 10.0, &data.total_present.to_string());
+                page.text(COLUMNS[2], y, Font::Bold, 10.0, &data.total_classes.to_string());
+                page.text(COLUMNS[3], y, Font::Bold, 10.0, &format!("{:.2}%", data.overall_percentage));
+
+                let generated = format!("Generated at: {}", generated_at.format("%Y-%m-%d %H:%M:%S UTC"));
+                page.text(MARGIN, MARGIN + 28.0, Font::Regular, 9.0, &generated);
+                page.text(MARGIN, MARGIN + 14.0, Font::Regular, 9.0, "Content hash (SHA-256):");
+                page.text(MARGIN, MARGIN, Font::Mono, 8.0, &hash);
+            }
+
+            page.text(
+                PAGE_WIDTH - MARGIN - 50.0,
+                MARGIN,
+                Font::Regular,
+                8.0,
+                &format!("Page {} of {}", index + 1, page_count),
+            );
+
+            page.ops
+        })
+        .collect();
+
+    write_document(&pages, generated_at)
+}
+
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+}
+
+#[derive(Default)]
+struct PageContent {
+    ops: String,
+}
+
+impl PageContent {
+    fn text(&mut self, x: f32, y: f32, font: Font, size: f32, value: &str) {
+        self.ops.push_str(&format!(
+            "BT /{} {} Tf {} {} Td ({}) Tj ET\n",
+            font.resource(),
+            size,
+            x,
+            y,
+            escape_string(value)
+        ));
+    }
+
+    fn rule(&mut self, y: f32, width: f32) {
+        self.ops.push_str(&format!("{} w {} {} m {} {} l S\n", width, MARGIN, y, PAGE_WIDTH - MARGIN, y));
+    }
+}
+
+// Object layout: 1 catalog, 2 page tree, 3 info, 4-6 fonts, then a page and
+// content stream per page
+fn write_document(pages: &[String], generated_at: DateTime<Utc>) -> Vec<u8> {
+    const FIRST_PAGE_ID: usize = 7;
+
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| FIRST_PAGE_ID + i * 2).collect();
+    let kids = page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" ");
+
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, pages.len()),
+        format!(
+            "<< /Title (Attendance Report) /Producer (AIMS) /CreationDate (D:{}Z) >>",
+            generated_at.format("%Y%m%d%H%M%S")
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_string(),
+    ];
+
+    for (page_id, content) in page_ids.iter().zip(pages) {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 4 0 R /F2 5 0 R /F3 6 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            page_id + 1
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+    }
+
+    let mut out = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+    }
+
+    let xref_offset = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 3 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_offset
+        )
+        .as_bytes(),
+    );
+
+    out
+}
+
+// Standard fonts only cover WinAnsi, so anything outside printable ASCII becomes '?'
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' | '(' | ')' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ' '..='~' => escaped.push(ch),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+fn truncate(value: &str, max: usize) -> String {
+    if value.chars().count() <= max {
+        value.to_string()
+    } else {
+        let mut truncated: String = value.chars().take(max - 3).collect();
+        truncated.push_str("...");
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DailyAttendance;
+    use chrono::TimeZone;
+
+    fn sample() -> AttendanceResponse {
+        let course = |name: &str, present, total, percent| DailyAttendance {
+            course: name.to_string(),
+            present,
+            total,
+            percent,
+        };
+
+        AttendanceResponse {
+            daily_attendance: vec![
+                course("Database Management Systems", 38, 42, 90.48),
+                course("Operating Systems (Theory)", 30, 40, 75.0),
+                course("Design and Analysis of Algorithms Laboratory with Mini Project", 12, 14, 85.71),
+            ],
+            total_present: 80,
+            total_classes: 96,
+            overall_percentage: 83.33,
+            batch: "2022-26".to_string(),
+            section: "CSE-A".to_string(),
+            branch: "Computer Science".to_string(),
+            student_id: "2200320100001".to_string(),
+        }
+    }
+
+    // Set UPDATE_GOLDEN=1 to regenerate testdata/attendance_report.pdf after layout changes
+    #[test]
+    fn matches_golden_report() {
+        let generated_at = Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap();
+        let pdf = render_pdf(&sample(), generated_at);
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/attendance_report.pdf");
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(path, &pdf).unwrap();
+        }
+
+        let golden = std::fs::read(path).expect("golden report missing; run with UPDATE_GOLDEN=1");
+        assert!(pdf == golden, "report layout changed; rerun with UPDATE_GOLDEN=1 if intended");
+    }
+
+    #[test]
+    fn hash_covers_report_data() {
+        let generated_at = Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 0).unwrap();
+        let mut data = sample();
+        let original = content_hash(&data, generated_at);
+
+        data.daily_attendance[1].present = 40;
+        assert_ne!(content_hash(&data, generated_at), original);
+    }
+}
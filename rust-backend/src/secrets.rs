@@ -0,0 +1,228 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::{config::Config, error::AppError};
+
+const FORMAT_VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+// Id given to the key from SUBSCRIPTION_ENCRYPTION_KEY
+const LEGACY_KEY_ID: &str = "legacy";
+const DEFAULT_KEY_ID: &str = "default";
+
+/// AES-256-GCM keys by id. New values are sealed with the active key; older keys stay
+/// around so values sealed before a rotation can still be opened.
+///
+/// Sealed values are base64 of `version | id length | key id | nonce | ciphertext`, with the
+/// header bound to the ciphertext as associated data.
+pub struct Keyring {
+    keys: Vec<(String, Aes256Gcm)>,
+    active: usize,
+}
+
+impl Keyring {
+    /// Loads keys from `SECRETS_KEY_FILE`, `SECRETS_KEYS` and the legacy
+    /// `SUBSCRIPTION_ENCRYPTION_KEY`. Returns `None` when no key is configured.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, AppError> {
+        let mut specs = Vec::new();
+        if let Some(path) = &config.secrets_key_file {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| AppError::ValidationError(format!("Failed to read secrets key file {}: {}", path, e)))?;
+            specs.push(contents);
+        }
+        if let Some(keys) = &config.secrets_keys {
+            specs.push(keys.clone());
+        }
+
+        let mut keys = Vec::new();
+        for spec in &specs {
+            keys.extend(parse_keys(spec)?);
+        }
+        if let Some(key) = &config.subscription_encryption_key {
+            keys.push((LEGACY_KEY_ID.to_string(), key_from_base64(LEGACY_KEY_ID, key)?));
+        }
+
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        let keyring = Self::new(keys, config.secrets_active_key_id.as_deref())?;
+        info!(
+            "[Secrets] Loaded {} key(s), sealing with '{}'",
+            keyring.keys.len(),
+            keyring.active_key_id()
+        );
+        Ok(Some(keyring))
+    }
+
+    /// Builds a keyring from `(id, key)` pairs. Without `active_id` the first key is active.
+    pub fn new(keys: Vec<(String, Aes256Gcm)>, active_id: Option<&str>) -> Result<Self, AppError> {
+        for (index, (id, _)) in keys.iter().enumerate() {
+            if keys[..index].iter().any(|(other, _)| other == id) {
+                return Err(AppError::ValidationError(format!("Duplicate secret key id '{}'", id)));
+            }
+        }
+
+        let active = match active_id {
+            Some(active_id) => keys
+                .iter()
+                .position(|(id, _)| id == active_id)
+                .ok_or_else(|| AppError::ValidationError(format!("Unknown active secret key id '{}'", active_id)))?,
+            None if keys.is_empty() => {
+                return Err(AppError::ValidationError("No secret keys configured".to_string()));
+            }
+            None => 0,
+        };
+
+        Ok(Self { keys, active })
+    }
+
+    /// Parses `id:base64key` entries (see `SECRETS_KEYS`) with the first key active.
+    #[cfg(test)]
+    pub fn parse(spec: &str) -> Result<Self, AppError> {
+        Self::new(parse_keys(spec)?, None)
+    }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.keys[self.active].0
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String, AppError> {
+        let (id, cipher) = &self.keys[self.active];
+        let mut sealed = vec![FORMAT_VERSION, id.len() as u8];
+        sealed.extend_from_slice(id.as_bytes());
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, Payload { msg: plaintext, aad: &sealed })
+            .map_err(|_| AppError::InternalError("Encryption failed".to_string()))?;
+
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(BASE64.encode(sealed))
+    }
+
+    /// Opens a sealed value. The plaintext is overwritten with zeros when dropped.
+    pub fn decrypt(&self, sealed: &str) -> Result<Zeroizing<Vec<u8>>, AppError> {
+        let sealed = BASE64
+            .decode(sealed)
+            .map_err(|_| AppError::InternalError("Corrupt ciphertext".to_string()))?;
+
+        let failed = || AppError::InternalError("Decryption failed".to_string());
+        let (header_len, id) = parse_header(&sealed).ok_or_else(failed)?;
+        let (_, cipher) = self.keys.iter().find(|(key_id, _)| key_id == id).ok_or_else(failed)?;
+
+        let (header, rest) = sealed.split_at(header_len);
+        if rest.len() < NONCE_LEN {
+            return Err(failed());
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+            .map(Zeroizing::new)
+            .map_err(|_| failed())
+    }
+
+    /// True when `sealed` was produced with a key other than the active one and should be
+    /// re-encrypted.
+    pub fn needs_reseal(&self, sealed: &str) -> bool {
+        let Ok(sealed) = BASE64.decode(sealed) else {
+            return false;
+        };
+        parse_header(&sealed).is_some_and(|(_, id)| id != self.active_key_id())
+    }
+
+    /// Decrypts with whichever key sealed the value and encrypts again with the active key.
+    pub fn reseal(&self, sealed: &str) -> Result<String, AppError> {
+        self.encrypt(&self.decrypt(sealed)?)
+    }
+}
+
+fn parse_header(sealed: &[u8]) -> Option<(usize, &str)> {
+    let (&version, rest) = sealed.split_first()?;
+    let (&id_len, rest) = rest.split_first()?;
+    if version != FORMAT_VERSION || id_len == 0 || rest.len() < id_len as usize {
+        return None;
+    }
+    let id = std::str::from_utf8(&rest[..id_len as usize]).ok()?;
+    Some((2 + id_len as usize, id))
+}
+
+// Comma or newline separated `id:base64key` entries; a bare key gets the id "default".
+// Lines starting with '#' are comments.
+fn parse_keys(spec: &str) -> Result<Vec<(String, Aes256Gcm)>, AppError> {
+    spec.split([',', '\n'])
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+        .map(|entry| {
+            let (id, key) = entry.split_once(':').unwrap_or((DEFAULT_KEY_ID, entry));
+            let id = id.trim();
+            if id.is_empty() || id.len() > u8::MAX as usize {
+                return Err(AppError::ValidationError(format!("Invalid secret key id '{}'", id)));
+            }
+            Ok((id.to_string(), key_from_base64(id, key)?))
+        })
+        .collect()
+}
+
+fn key_from_base64(id: &str, key: &str) -> Result<Aes256Gcm, AppError> {
+    let bytes = BASE64
+        .decode(key.trim())
+        .map(Zeroizing::new)
+        .map_err(|_| AppError::ValidationError(format!("Secret key '{}' must be base64", id)))?;
+    let cipher = if bytes.len() == KEY_LEN {
+        Aes256Gcm::new_from_slice(&bytes).ok()
+    } else {
+        None
+    };
+
+    cipher.ok_or_else(|| AppError::ValidationError(format!("Secret key '{}' must be 32 bytes", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+    const NEW_KEY: &str = "ZmVkY2JhOTg3NjU0MzIxMGZlZGNiYTk4NzY1NDMyMTA=";
+
+    #[test]
+    fn opens_values_sealed_before_rotation() {
+        let before = Keyring::parse(&format!("2024:{}", OLD_KEY)).unwrap();
+        let sealed = before.encrypt(b"portal-token").unwrap();
+
+        let after = Keyring::parse(&format!("2025:{},2024:{}", NEW_KEY, OLD_KEY)).unwrap();
+        assert_eq!(&*after.decrypt(&sealed).unwrap(), b"portal-token");
+        assert!(after.needs_reseal(&sealed));
+
+        let resealed = after.reseal(&sealed).unwrap();
+        assert!(!after.needs_reseal(&resealed));
+        assert!(before.decrypt(&resealed).is_err());
+
+        // A retired key can no longer open anything
+        let retired = Keyring::parse(&format!("2025:{}", NEW_KEY)).unwrap();
+        assert!(retired.decrypt(&sealed).is_err());
+        assert_eq!(&*retired.decrypt(&resealed).unwrap(), b"portal-token");
+    }
+
+    #[test]
+    fn rejects_values_without_a_header() {
+        let cipher = key_from_base64(LEGACY_KEY_ID, OLD_KEY).unwrap();
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut headerless = nonce.to_vec();
+        headerless.extend_from_slice(&cipher.encrypt(&nonce, b"old".as_ref()).unwrap());
+        let headerless = BASE64.encode(headerless);
+
+        let keyring = Keyring::new(vec![(LEGACY_KEY_ID.to_string(), cipher)], None).unwrap();
+        assert!(keyring.decrypt(&headerless).is_err());
+        assert!(!keyring.needs_reseal(&headerless));
+        assert!(Keyring::new(Vec::new(), None).is_err());
+        assert!(Keyring::parse("short:AAAA").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+// The axum server behind the `aims-backend` binary. Public only so the binary can start it;
+// not covered by the library's semver guarantees.
+use axum::{
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use std::future::IntoFuture;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tower_http::compression::CompressionLayer;
+use tracing::{error, info, warn};
+
+use crate::{
+    admin,
+    cache::Cache,
+    cache_backend,
+    calendar_feeds::CalendarFeedStore,
+    changes::ChangeTracker,
+    client::AimsClient,
+    config::LiveTunables,
+    handlers::*,
+    health::{self, Health},
+    middleware::*,
+    notifications::{AbsencePoller, WebhookNotifier},
+    performance::PerformanceMonitor,
+    secrets::Keyring,
+    sessions::SessionStore,
+    shutdown::{self, Shutdown},
+    subscriptions::SubscriptionStore,
+};
+
+pub use crate::cache_backend::{from_config as build_cache_backend, BackendKind, CacheBackend};
+pub use crate::cassette::UpstreamMode;
+pub use crate::config::{Config, ConfigSources};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub(crate) client: Arc<AimsClient>,
+    pub(crate) cache: Arc<Cache>,
+    pub(crate) config: Arc<Config>,
+    pub(crate) tunables: Arc<LiveTunables>,
+    pub(crate) performance_monitor: Arc<PerformanceMonitor>,
+    pub(crate) change_tracker: Arc<ChangeTracker>,
+    pub(crate) sessions: Arc<SessionStore>,
+    pub(crate) subscriptions: Option<Arc<SubscriptionStore>>,
+    pub(crate) calendar_feeds: Option<Arc<CalendarFeedStore>>,
+    pub(crate) shutdown: Arc<Shutdown>,
+    pub(crate) health: Arc<Health>,
+    #[cfg(feature = "history")]
+    pub(crate) history: Arc<crate::history::HistoryStore>,
+}
+
+pub async fn build_state(config: Arc<Config>) -> Result<AppState, Box<dyn std::error::Error>> {
+    let client = Arc::new(AimsClient::from_config(&config));
+    let tunables = Arc::new(LiveTunables::new(config.tunables()));
+    let shutdown = Arc::new(Shutdown::new());
+
+    // Initialize cache
+    let cache = Arc::new(Cache::with_backend(cache_backend::from_config(&config)?, tunables.clone()));
+
+    // Initialize performance monitor
+    let performance_monitor = Arc::new(PerformanceMonitor::new());
+
+    // Initialize change tracker
+    let change_tracker = Arc::new(ChangeTracker::new());
+
+    // Subscriptions, calendar feeds and session re-login need an encryption key
+    let keyring = Keyring::from_config(&config)?.map(Arc::new);
+    if keyring.is_none() {
+        warn!("No encryption key configured, calendar feeds and session re-login are disabled");
+    }
+
+    let subscriptions = match (&keyring, config.enable_subscriptions) {
+        (Some(keyring), true) => {
+            let store = Arc::new(SubscriptionStore::open(&config.subscriptions_path, keyring.clone()).await?);
+            let notifier = Arc::new(WebhookNotifier::new(&config));
+            let poller = AbsencePoller::new(tunables.clone(), client.clone(), store.clone(), notifier);
+            shutdown.spawn(poller.run(shutdown.token()));
+            Some(store)
+        }
+        _ => None,
+    };
+
+    let calendar_feeds = match &keyring {
+        Some(keyring) => Some(Arc::new(CalendarFeedStore::open(&config.calendar_feeds_path, keyring.clone()).await?)),
+        None => None,
+    };
+
+    // Initialize attendance history store
+    #[cfg(feature = "history")]
+    let history = Arc::new(crate::history::HistoryStore::open(
+        &config.history_db_path,
+        config.history_snapshot_interval_seconds,
+    )?);
+
+    let state = AppState {
+        client,
+        cache,
+        sessions: Arc::new(SessionStore::new(&config, keyring.clone())),
+        config,
+        tunables,
+        performance_monitor,
+        change_tracker,
+        subscriptions,
+        calendar_feeds,
+        shutdown,
+        health: Arc::new(Health::new()),
+        #[cfg(feature = "history")]
+        history,
+    };
+    health::spawn_prober(&state, Duration::from_secs(state.config.health_probe_interval_seconds));
+    Ok(state)
+}
+
+pub fn build_router(state: AppState) -> Router {
+    // Build router with middleware
+    let router = Router::new()
+        .route("/api/login", post(login_handler))
+        .route("/api/logout", post(logout_handler))
+        .route("/api/session", get(session_handler))
+        .route("/api/attendance", post(attendance_handler))
+        .route("/api/attendance/report.pdf", get(report_handler))
+        .route("/api/all-attendance", get(all_attendance_handler))
+        .route("/api/all-attendance/export", get(export_handler))
+        .route("/api/attendance/changes", get(attendance_changes_handler))
+        .route("/api/calendar.ics", get(calendar_handler))
+        .route(
+            "/api/calendar/subscribe",
+            get(calendar_subscribe_handler).delete(calendar_unsubscribe_handler),
+        )
+        .route(
+            "/api/subscriptions",
+            post(create_subscription_handler)
+                .get(get_subscription_handler)
+                .delete(delete_subscription_handler),
+        )
+        .route("/api/quiz", get(quiz_handler))
+        .route("/api/quiz/analytics", get(quiz_analytics_handler))
+        .route("/api/quiz/start", post(quiz_start_handler))
+        .route("/health/live", get(health::live_handler))
+        .route("/health/ready", get(health::ready_handler))
+        // Kept for probes configured before the split; same as /health/live
+        .route("/health", get(health::live_handler))
+        .route("/metrics", get(metrics_handler));
+
+    #[cfg(feature = "history")]
+    let router = router.route("/api/attendance/history", get(attendance_history_handler));
+
+    let router = match state.config.admin_token {
+        Some(_) => router.nest("/admin", admin::router()),
+        None => router,
+    };
+
+    router
+        // gzip or brotli as the client's Accept-Encoding allows; tiny bodies are left as is
+        .layer(CompressionLayer::new())
+        .layer(rate_limit_layer())
+        .layer(axum::middleware::from_fn_with_state(state.clone(), shutdown::track_requests))
+        .layer(cors_layer(state.tunables.clone()))
+        .with_state(state)
+}
+
+/// Reloads the configuration from `sources` on SIGHUP. Only the tunables are applied; other
+/// changed settings are logged as needing a restart, and an invalid configuration is
+/// rejected as a whole.
+#[cfg(unix)]
+pub fn spawn_config_reloader(state: &AppState, sources: ConfigSources) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    let running = state.config.clone();
+    let tunables = state.tunables.clone();
+    let cancel = state.shutdown.token();
+
+    state.shutdown.spawn(async move {
+        while cancel.run_until_cancelled(hangups.recv()).await.flatten().is_some() {
+            info!("[Config] SIGHUP received, reloading");
+            match Config::load(&sources) {
+                Ok(config) => apply_reload(&running, &tunables, &config),
+                Err(e) => error!("[Config] Reload rejected, keeping the current settings: {:#}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+fn apply_reload(running: &Config, tunables: &LiveTunables, config: &Config) {
+    let restart = running.restart_required(config);
+    if !restart.is_empty() {
+        warn!("[Config] Changes to {} take effect after a restart", restart.join(", "));
+    }
+
+    let next = config.tunables();
+    if *tunables.get() == next {
+        info!("[Config] Reload complete, no reloadable setting changed");
+        return;
+    }
+    info!("[Config] Applied {:?}", next);
+    tunables.set(next);
+}
+
+/// Serves until SIGTERM or SIGINT. Then the listener closes, in-flight requests get up to
+/// `shutdown_grace_seconds` to finish, background tasks are cancelled and waited for within
+/// the same deadline, and persisted state is flushed before returning.
+pub async fn serve(listener: TcpListener, state: AppState) -> std::io::Result<()> {
+    let shutdown = state.shutdown.clone();
+    let grace = Duration::from_secs(state.config.shutdown_grace_seconds);
+
+    let signalled = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        signalled.trigger();
+    });
+
+    let app = build_router(state.clone()).into_make_service();
+    let cancel = shutdown.token();
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(async move { cancel.cancelled().await })
+        .into_future();
+
+    let deadline = async {
+        shutdown.token().cancelled().await;
+        tokio::time::sleep(shutdown.remaining(grace)).await;
+    };
+    let drained = tokio::select! {
+        result = server => {
+            result?;
+            true
+        }
+        _ = deadline => false,
+    };
+
+    if !drained {
+        warn!(
+            "[Shutdown] {} request(s) still in flight after {}s, closing their connections",
+            shutdown.in_flight(),
+            grace.as_secs()
+        );
+    }
+    finish(&state, grace).await;
+    Ok(())
+}
+
+// Stops the background tasks, flushes what they persist and logs the summary
+async fn finish(state: &AppState, grace: Duration) {
+    let started = Instant::now();
+    let shutdown = &state.shutdown;
+    let abandoned = shutdown.in_flight();
+
+    if !shutdown.wait_for_tasks(shutdown.remaining(grace)).await {
+        warn!("[Shutdown] {} background task(s) did not stop in time", shutdown.running_tasks());
+    }
+
+    if let Some(subscriptions) = &state.subscriptions {
+        if let Err(e) = subscriptions.flush().await {
+            error!("[Shutdown] Failed to flush subscriptions: {}", e);
+        }
+    }
+    #[cfg(feature = "history")]
+    if let Err(e) = state.history.flush() {
+        error!("[Shutdown] Failed to flush attendance history: {}", e);
+    }
+
+    let (requests, errors) = state.performance_monitor.totals().await;
+    info!(
+        "[Shutdown] Complete: {} HTTP request(s) served, {} abandoned; {} handler call(s) recorded with {} error(s); cleanup took {}ms",
+        shutdown.served(),
+        abandoned,
+        requests,
+        errors,
+        started.elapsed().as_millis()
+    );
+}
+
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let metrics = state.performance_monitor.get_metrics().await;
+    Json(metrics)
+}
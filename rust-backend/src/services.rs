@@ -1,44 +1,131 @@
+use std::time::Duration;
+use serde::de::DeserializeOwned;
 use tracing::{error, warn};
 use crate::{
+    cassette::{Cassettes, RecordedRequest, UpstreamMode},
+    circuit::{CircuitBreaker, CircuitState},
+    config::Config,
     error::AppError,
     models::*,
 };
 
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+// For library clients; the server uses `request_timeout_seconds`
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct ExternalApiService {
     base_url: String,
     client: reqwest::Client,
+    mode: UpstreamMode,
+    cassettes: Cassettes,
+    circuit: CircuitBreaker,
 }
 
 impl ExternalApiService {
-    pub fn new(base_url: &str) -> Self {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            mode: config.upstream_mode,
+            cassettes: Cassettes::new(&config.cassette_dir),
+            circuit: CircuitBreaker::new(
+                config.circuit_failure_threshold,
+                Duration::from_secs(config.circuit_open_seconds),
+            ),
+            ..Self::with_timeout(&config.external_api_base, Duration::from_secs(config.request_timeout_seconds))
+        }
+    }
+
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self::with_timeout(base_url, DEFAULT_REQUEST_TIMEOUT)
+    }
+
+    fn with_timeout(base_url: &str, timeout: Duration) -> Self {
         let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(10))
+            .timeout(timeout)
             .build()
             .expect("Failed to create HTTP client");
 
         Self {
             base_url: base_url.to_string(),
             client,
+            mode: UpstreamMode::Live,
+            cassettes: Cassettes::new(""),
+            circuit: CircuitBreaker::new(5, Duration::from_secs(30)),
+        }
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        self.circuit.state()
+    }
+
+    /// Checks the portal answers at all; any HTTP status counts as reachable. Replay mode
+    /// has nothing to reach.
+    pub async fn probe(&self) -> Result<(), AppError> {
+        if self.mode == UpstreamMode::Replay {
+            return Ok(());
+        }
+        self.client.head(&self.base_url).timeout(PROBE_TIMEOUT).send().await?;
+        Ok(())
+    }
+
+    // Every upstream call goes through here so it can be recorded or replayed
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<(reqwest::StatusCode, Vec<u8>), AppError> {
+        let request = request.build()?;
+        let recorded = (self.mode != UpstreamMode::Live).then(|| RecordedRequest::from_request(&request));
+
+        if let (UpstreamMode::Replay, Some(recorded)) = (self.mode, &recorded) {
+            let (status, body) = self.cassettes.replay(recorded).await?;
+            let status = reqwest::StatusCode::from_u16(status)
+                .map_err(|_| AppError::InternalError(format!("Invalid status {} in cassette", status)))?;
+            return Ok((status, body));
+        }
+
+        if !self.circuit.allow() {
+            return Err(AppError::ExternalApiError("Portal is unavailable, try again shortly".to_string()));
+        }
+        let response = async {
+            let response = self.client.execute(request).await?;
+            let status = response.status();
+            Ok::<_, reqwest::Error>((status, response.bytes().await?.to_vec()))
         }
+        .await;
+        // Only transport errors and 5xx count against the portal; 4xx are the caller's
+        let (status, body) = match response {
+            Ok((status, body)) if !status.is_server_error() => {
+                self.circuit.record_success();
+                (status, body)
+            }
+            Ok(response) => {
+                self.circuit.record_failure();
+                response
+            }
+            Err(e) => {
+                self.circuit.record_failure();
+                return Err(e.into());
+            }
+        };
+
+        if let Some(recorded) = recorded {
+            self.cassettes.record(recorded, status.as_u16(), &body).await;
+        }
+        Ok((status, body))
     }
 
     pub async fn get_attendance_records(&self, token: &str) -> Result<Vec<AttendanceRecord>, AppError> {
         let url = format!("{}/custom/getCFMappedWithStudentID?embed_attendance_summary=1", self.base_url);
         
-        let response = self.client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await?;
+        let (status, body) = self.send(
+            self.client
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", token))
+        ).await?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            let error_text = String::from_utf8_lossy(&body);
             error!("[ExternalAPI] Attendance fetch failed: {}", error_text);
-            return Err(AppError::ExternalApiError(format!("External API error {}", status)));
+            return Err(status_error(status, &error_text, format!("External API error {}", status)));
         }
 
-        let api_response: ExternalApiResponse<Vec<AttendanceRecord>> = response.json().await?;
+        let api_response: ExternalApiResponse<Vec<AttendanceRecord>> = parse(&body)?;
         
         if let Some(response_data) = api_response.response {
             let records = response_data.data;
@@ -47,31 +134,31 @@ impl ExternalApiService {
             }
             Ok(records)
         } else {
-            Err(AppError::ExternalApiError("Invalid API response structure".to_string()))
+            Err(envelope_error(&api_response.message))
         }
     }
 
     pub async fn get_subjects(&self, token: &str) -> Result<Vec<AttendanceRecord>, AppError> {
         let url = format!("{}/custom/getCFMappedWithStudentID", self.base_url);
         
-        let response = self.client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
+        let (status, body) = self.send(
+            self.client
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", token))
+        ).await?;
+
+        if !status.is_success() {
+            let error_text = String::from_utf8_lossy(&body);
             error!("[ExternalAPI] Subjects fetch failed: {}", error_text);
-            return Err(AppError::ExternalApiError("Failed to fetch subjects".to_string()));
+            return Err(status_error(status, &error_text, "Failed to fetch subjects".to_string()));
         }
 
-        let api_response: ExternalApiResponse<Vec<AttendanceRecord>> = response.json().await?;
+        let api_response: ExternalApiResponse<Vec<AttendanceRecord>> = parse(&body)?;
         
         if let Some(response_data) = api_response.response {
             Ok(response_data.data)
         } else {
-            Err(AppError::ExternalApiError("Invalid API response structure".to_string()))
+            Err(envelope_error(&api_response.message))
         }
     }
 
@@ -94,17 +181,14 @@ impl ExternalApiService {
             .append_pair("equalto___cf_id", cf_id)
             .append_pair("token", token);
 
-        let response = self.client
-            .get(url.as_str())
-            .send()
-            .await?;
+        let (status, body) = self.send(self.client.get(url.as_str())).await?;
 
-        if !response.status().is_success() {
-            warn!("[ExternalAPI] Fetch cards failed for {}: HTTP {}", subject_name, response.status());
-            return Err(AppError::ExternalApiError(format!("HTTP {}", response.status())));
+        if !status.is_success() {
+            warn!("[ExternalAPI] Fetch cards failed for {}: HTTP {}", subject_name, status);
+            return Err(AppError::ExternalApiError(format!("HTTP {}", status)));
         }
 
-        let api_response: ExternalApiResponse<Vec<QuizRecord>> = response.json().await?;
+        let api_response: ExternalApiResponse<Vec<QuizRecord>> = parse(&body)?;
         
         if let Some(response_data) = api_response.response {
             Ok(response_data.data)
@@ -113,29 +197,79 @@ impl ExternalApiService {
         }
     }
 
-    pub async fn get_quiz_data(&self, token: &str) -> Result<serde_json::Value, AppError> {
+    pub async fn get_quiz_data(&self, token: &str) -> Result<Vec<Quiz>, AppError> {
         let url = format!("{}/custom/myEvaluatedQuizzes", self.base_url);
         
-        let response = self.client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await?;
+        let (status, body) = self.send(
+            self.client
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", token))
+        ).await?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let quiz_data: serde_json::Value = response.json().await.unwrap_or_default();
+        if !status.is_success() {
+            let quiz_data: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
             let error_message = quiz_data["message"].as_str().unwrap_or("Unknown error");
-            return Err(AppError::ExternalApiError(
-                format!("API request failed with status {}: {}", status, error_message)
+            return Err(status_error(
+                status,
+                error_message,
+                format!("API request failed with status {}: {}", status, error_message),
             ));
         }
 
-        let quiz_data: serde_json::Value = response.json().await?;
-        Ok(quiz_data)
+        let quiz_data: serde_json::Value = parse(&body)?;
+        if quiz_data["response"].is_null() {
+            if let Some(message) = quiz_data["message"].as_str().or(quiz_data["msg"].as_str()) {
+                if is_expired_token_message(message) {
+                    return Err(AppError::UpstreamUnauthorized(message.to_string()));
+                }
+            }
+        }
+        Ok(crate::quiz::normalize(&quiz_data))
+    }
+
+    /// Logs in to the portal and returns its status and JSON body unchanged.
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<(reqwest::StatusCode, serde_json::Value), AppError> {
+        let url = format!("{}/admin/authenticate", self.base_url);
+        let form_data = format!("username={}&password={}", 
+            urlencoding::encode(username), 
+            urlencoding::encode(password)
+        );
+
+        let (status, body) = self.send(
+            self.client
+                .post(&url)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .header("Origin", "https://abes.web.simplifii.com")
+                .header("Referer", "https://abes.web.simplifii.com/")
+                .body(form_data)
+        ).await?;
+
+        Ok((status, parse(&body)?))
+    }
+
+    // The quiz details function lives outside the portal API, so its URL is passed in
+    pub async fn fetch_quiz_details(&self, url: &str, request: &StartQuizRequest) -> Result<serde_json::Value, AppError> {
+        let (status, body) = self.send(
+            self.client
+                .post(url)
+                .json(&serde_json::json!({
+                    "quiz_uc": request.quiz_code,
+                    "user_unique_code": request.user_unique_code,
+                    "pin": request.pin,
+                }))
+        ).await?;
+
+        // The upstream body may echo the request, so it is not included in errors
+        if !status.is_success() {
+            warn!("[ExternalAPI] Quiz details failed for {}: HTTP {}", request.quiz_code, status);
+            return Err(AppError::ExternalApiError(format!("HTTP {}", status)));
+        }
+
+        parse(&body)
     }
 
     // Helper method for retry logic
+    #[allow(dead_code)]
     pub async fn with_retry<F, T, E>(&self, mut f: F, max_retries: usize) -> Result<T, E>
     where
         F: FnMut() -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T, E>> + Send>>,
@@ -159,3 +293,35 @@ impl ExternalApiService {
         Err(last_error.unwrap())
     }
 }
+
+// Portal bodies that do not match the expected shape are an upstream fault, not a bad request
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, AppError> {
+    serde_json::from_slice(body)
+        .map_err(|e| AppError::ExternalApiError(format!("Malformed response from portal: {}", e)))
+}
+
+// The portal reports expired tokens either with 401/403 or with a 200 envelope whose
+// message mentions the token
+fn is_expired_token_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("unauthori")
+        || (message.contains("token") && (message.contains("expire") || message.contains("invalid")))
+}
+
+fn status_error(status: reqwest::StatusCode, body: &str, fallback: String) -> AppError {
+    if status == reqwest::StatusCode::UNAUTHORIZED
+        || status == reqwest::StatusCode::FORBIDDEN
+        || is_expired_token_message(body)
+    {
+        AppError::UpstreamUnauthorized(format!("HTTP {}", status))
+    } else {
+        AppError::ExternalApiError(fallback)
+    }
+}
+
+fn envelope_error(message: &Option<String>) -> AppError {
+    match message {
+        Some(message) if is_expired_token_message(message) => AppError::UpstreamUnauthorized(message.clone()),
+        _ => AppError::ExternalApiError("Invalid API response structure".to_string()),
+    }
+}
@@ -0,0 +1,223 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration as StdDuration;
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use moka::future::Cache as MokaCache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::{config::Config, error::AppError, models::LoginRequest, redact::Secret, secrets::Keyring};
+
+pub const SESSION_COOKIE: &str = "aims_session";
+
+#[derive(Serialize, Deserialize)]
+struct StoredCredentials {
+    username: Zeroizing<String>,
+    password: Zeroizing<String>,
+}
+
+pub struct Session {
+    upstream_token: Mutex<Secret<String>>,
+    // Portal credentials sealed with the server key, only for students who opted in
+    credentials: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // Serializes re-logins so concurrent 401s trigger a single one
+    refresh: tokio::sync::Mutex<()>,
+}
+
+impl Session {
+    pub fn upstream_token(&self) -> Secret<String> {
+        self.upstream_token.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn can_reauthenticate(&self) -> bool {
+        self.credentials.is_some()
+    }
+}
+
+// Server-side sessions keyed by a hash of the opaque id handed out in the cookie,
+// so the upstream token never leaves the backend
+pub struct SessionStore {
+    sessions: MokaCache<String, Arc<Session>>,
+    keyring: Option<Arc<Keyring>>,
+    ttl_seconds: u64,
+    secure_cookie: bool,
+}
+
+impl SessionStore {
+    pub fn new(config: &Config, keyring: Option<Arc<Keyring>>) -> Self {
+        let sessions = MokaCache::builder()
+            .time_to_live(StdDuration::from_secs(config.session_ttl_seconds))
+            .time_to_idle(StdDuration::from_secs(config.session_idle_timeout_seconds))
+            .max_capacity(100_000)
+            .build();
+
+        Self {
+            sessions,
+            keyring,
+            ttl_seconds: config.session_ttl_seconds,
+            secure_cookie: config.session_cookie_secure,
+        }
+    }
+
+    /// Stores the upstream token and returns the new session id. Credentials are kept
+    /// only when given and an encryption key is configured.
+    pub async fn create(&self, upstream_token: Secret<String>, credentials: Option<&LoginRequest>) -> String {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let id = hex::encode(bytes);
+
+        let credentials = credentials.and_then(|login| self.seal_credentials(login));
+        let session = Session {
+            upstream_token: Mutex::new(upstream_token),
+            credentials,
+            created_at: Utc::now(),
+            refresh: tokio::sync::Mutex::new(()),
+        };
+        self.sessions.insert(hash_id(&id), Arc::new(session)).await;
+
+        info!("[Sessions] Created session");
+        id
+    }
+
+    fn seal_credentials(&self, login: &LoginRequest) -> Option<String> {
+        let Some(keyring) = &self.keyring else {
+            warn!("[Sessions] Re-login requested but no encryption key is configured");
+            return None;
+        };
+
+        let stored = StoredCredentials {
+            username: Zeroizing::new(login.username.clone()),
+            password: Zeroizing::new(login.password.expose().clone()),
+        };
+        let plaintext = Zeroizing::new(serde_json::to_vec(&stored).ok()?);
+        keyring.encrypt(&plaintext).ok()
+    }
+
+    /// Logs in again with the stored credentials after `failed_token` was rejected and
+    /// returns the token to retry with. If another request already refreshed the session,
+    /// its token is returned without logging in again.
+    pub async fn reauthenticate<F, Fut>(&self, session: &Session, failed_token: &str, login: F) -> Result<Secret<String>, AppError>
+    where
+        F: FnOnce(Zeroizing<String>, Zeroizing<String>) -> Fut,
+        Fut: Future<Output = Result<Secret<String>, AppError>>,
+    {
+        let _guard = session.refresh.lock().await;
+
+        let current = session.upstream_token();
+        if current.expose() != failed_token {
+            return Ok(current);
+        }
+
+        let (Some(sealed), Some(keyring)) = (&session.credentials, &self.keyring) else {
+            return Err(AppError::AuthenticationError("Session cannot be renewed".to_string()));
+        };
+        let stored: StoredCredentials = serde_json::from_slice(&keyring.decrypt(sealed)?)?;
+
+        let token = login(stored.username, stored.password).await?;
+        *session.upstream_token.lock().unwrap_or_else(|e| e.into_inner()) = token.clone();
+
+        info!("[Sessions] Renewed upstream token");
+        Ok(token)
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.get(&hash_id(id)).await
+    }
+
+    pub async fn remove(&self, id: &str) -> bool {
+        self.sessions.remove(&hash_id(id)).await.is_some()
+    }
+
+    pub fn cookie(&self, id: &str) -> String {
+        self.cookie_with(id, self.ttl_seconds)
+    }
+
+    pub fn expired_cookie(&self) -> String {
+        self.cookie_with("", 0)
+    }
+
+    fn cookie_with(&self, value: &str, max_age: u64) -> String {
+        let mut cookie = format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+            SESSION_COOKIE, value, max_age
+        );
+        if self.secure_cookie {
+            cookie.push_str("; Secure");
+        }
+        cookie
+    }
+}
+
+/// Reads the session id from the `Cookie` header, if any.
+pub fn session_id(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all("cookie")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, value)| *name == SESSION_COOKIE && !value.is_empty())
+        .map(|(_, value)| value)
+}
+
+fn hash_id(id: &str) -> String {
+    hex::encode(Sha256::digest(id.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn resolves_sessions_from_cookie_header() {
+        let store = SessionStore::new(&Config::defaults(), None);
+        let id = store.create(Secret::new("upstream-token".to_string()), None).await;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", format!("theme=dark; {}={}", SESSION_COOKIE, id).parse().unwrap());
+        let found = session_id(&headers).unwrap();
+        assert_eq!(store.get(found).await.unwrap().upstream_token().expose(), "upstream-token");
+
+        assert!(store.cookie(&id).contains("HttpOnly"));
+        assert!(store.remove(found).await);
+        assert!(store.get(found).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn renews_token_once_with_stored_credentials() {
+        let keyring = Keyring::parse("test:MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=").unwrap();
+        let store = SessionStore::new(&Config::defaults(), Some(Arc::new(keyring)));
+        let login = LoginRequest {
+            username: "student".to_string(),
+            password: Secret::new("secret".to_string()),
+            remember: true,
+        };
+        let id = store.create(Secret::new("expired".to_string()), Some(&login)).await;
+        let session = store.get(&id).await.unwrap();
+        assert!(session.can_reauthenticate());
+
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let relogin = |username: Zeroizing<String>, password: Zeroizing<String>| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async move {
+                assert_eq!((username.as_str(), password.as_str()), ("student", "secret"));
+                Ok(Secret::new("fresh".to_string()))
+            }
+        };
+
+        assert_eq!(store.reauthenticate(&session, "expired", relogin).await.unwrap().expose(), "fresh");
+        // A second request that failed with the old token reuses the renewed one
+        assert_eq!(store.reauthenticate(&session, "expired", relogin).await.unwrap().expose(), "fresh");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(session.upstream_token().expose(), "fresh");
+
+        let plain = store.create(Secret::new("token".to_string()), None).await;
+        let plain = store.get(&plain).await.unwrap();
+        assert!(store.reauthenticate(&plain, "token", relogin).await.is_err());
+    }
+}
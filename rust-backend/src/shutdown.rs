@@ -0,0 +1,133 @@
+// Graceful shutdown: one cancellation token that the listener and every background task
+// watch, a tracker to wait for those tasks, and a count of requests still being served.
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::info;
+
+use crate::server::AppState;
+
+#[derive(Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tasks: TaskTracker,
+    triggered_at: OnceLock<Instant>,
+    in_flight: AtomicUsize,
+    served: AtomicU64,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancelled once shutdown starts; background loops select on `cancelled()`.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn trigger(&self) {
+        self.triggered_at.get_or_init(Instant::now);
+        self.token.cancel();
+    }
+
+    /// Time left of `grace`, counted from when shutdown started.
+    pub fn remaining(&self, grace: Duration) -> Duration {
+        self.triggered_at
+            .get()
+            .map_or(grace, |at| grace.saturating_sub(at.elapsed()))
+    }
+
+    /// Spawns a background task that shutdown waits for.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.spawn(task);
+    }
+
+    /// Waits up to `deadline` for the background tasks to stop; false if some did not.
+    pub async fn wait_for_tasks(&self, deadline: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(deadline, self.tasks.wait()).await.is_ok()
+    }
+
+    pub fn running_tasks(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    pub fn served(&self) -> u64 {
+        self.served.load(Ordering::SeqCst)
+    }
+}
+
+// Decrements the in-flight count even when the request future is dropped mid-way
+struct InFlight(Arc<Shutdown>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub async fn track_requests(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    state.shutdown.in_flight.fetch_add(1, Ordering::SeqCst);
+    let _guard = InFlight(state.shutdown.clone());
+    let response = next.run(request).await;
+    state.shutdown.served.fetch_add(1, Ordering::SeqCst);
+    response
+}
+
+/// Resolves on SIGTERM or Ctrl-C (SIGINT) and names the signal.
+pub async fn signal() -> &'static str {
+    let interrupt = async {
+        tokio::signal::ctrl_c().await.expect("failed to listen for Ctrl-C");
+        "SIGINT"
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        signal(SignalKind::terminate()).expect("failed to listen for SIGTERM").recv().await;
+        "SIGTERM"
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<&'static str>();
+
+    let name = tokio::select! {
+        name = interrupt => name,
+        name = terminate => name,
+    };
+    info!("[Shutdown] {} received, no longer accepting connections", name);
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancels_and_waits_for_background_tasks() {
+        let shutdown = Shutdown::new();
+        let token = shutdown.token();
+        shutdown.spawn(async move { token.cancelled().await });
+        shutdown.spawn(std::future::pending());
+
+        shutdown.trigger();
+        assert!(shutdown.remaining(Duration::from_secs(30)) > Duration::from_secs(29));
+        assert!(!shutdown.wait_for_tasks(Duration::from_millis(50)).await);
+        assert_eq!(shutdown.running_tasks(), 1);
+    }
+}
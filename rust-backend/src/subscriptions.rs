@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::{
+    error::AppError,
+    models::{NotificationChannel, SubscriptionResponse, SubscriptionThresholds},
+    notifications::Watch,
+    redact::Secret,
+    secrets::Keyring,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub student_id: String,
+    pub encrypted_token: String,
+    pub channel_type: String,
+    pub encrypted_channel: String,
+    pub thresholds: SubscriptionThresholds,
+    pub poll_interval_seconds: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Subscription {
+    pub fn to_response(&self) -> SubscriptionResponse {
+        SubscriptionResponse {
+            id: self.id.clone(),
+            student_id: self.student_id.clone(),
+            channel_type: self.channel_type.clone(),
+            thresholds: self.thresholds.clone(),
+            poll_interval_seconds: self.poll_interval_seconds,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+// One subscription per student, persisted as a JSON file with secrets encrypted
+pub struct SubscriptionStore {
+    path: PathBuf,
+    keyring: Arc<Keyring>,
+    subscriptions: RwLock<HashMap<String, Subscription>>,
+}
+
+impl SubscriptionStore {
+    pub async fn open(path: &str, keyring: Arc<Keyring>) -> Result<Self, AppError> {
+        let mut subscriptions: HashMap<String, Subscription> = match tokio::fs::read(path).await {
+            Ok(bytes) => {
+                let list: Vec<Subscription> = serde_json::from_slice(&bytes)?;
+                list.into_iter().map(|s| (s.student_id.clone(), s)).collect()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(AppError::InternalError(format!("Failed to read subscriptions: {}", e)));
+            }
+        };
+
+        info!("[Subscriptions] Loaded {} subscription(s)", subscriptions.len());
+
+        // Move entries sealed with a retired key over to the active one
+        let mut resealed = 0;
+        for subscription in subscriptions.values_mut() {
+            if !keyring.needs_reseal(&subscription.encrypted_token)
+                && !keyring.needs_reseal(&subscription.encrypted_channel)
+            {
+                continue;
+            }
+            match (keyring.reseal(&subscription.encrypted_token), keyring.reseal(&subscription.encrypted_channel)) {
+                (Ok(token), Ok(channel)) => {
+                    subscription.encrypted_token = token;
+                    subscription.encrypted_channel = channel;
+                    resealed += 1;
+                }
+                _ => warn!("[Subscriptions] Cannot re-encrypt subscription {}, key missing", subscription.id),
+            }
+        }
+
+        let store = Self {
+            path: PathBuf::from(path),
+            keyring,
+            subscriptions: RwLock::new(subscriptions),
+        };
+
+        if resealed > 0 {
+            store.persist(&*store.subscriptions.read().await).await?;
+            info!("[Subscriptions] Re-encrypted {} subscription(s) with the active key", resealed);
+        }
+
+        Ok(store)
+    }
+
+    /// Creates or replaces the subscription for a student.
+    pub async fn upsert(
+        &self,
+        student_id: &str,
+        token: &str,
+        channel: &NotificationChannel,
+        thresholds: SubscriptionThresholds,
+        poll_interval_seconds: u64,
+    ) -> Result<Subscription, AppError> {
+        let now = Utc::now();
+        let encrypted_token = self.keyring.encrypt(token.as_bytes())?;
+        let encrypted_channel = self.keyring.encrypt(&serde_json::to_vec(channel)?)?;
+
+        let mut subscriptions = self.subscriptions.write().await;
+        let (id, created_at) = match subscriptions.get(student_id) {
+            Some(existing) => (existing.id.clone(), existing.created_at),
+            None => (uuid::Uuid::new_v4().to_string(), now),
+        };
+
+        let subscription = Subscription {
+            id,
+            student_id: student_id.to_string(),
+            encrypted_token,
+            channel_type: channel.kind().to_string(),
+            encrypted_channel,
+            thresholds,
+            poll_interval_seconds,
+            created_at,
+            updated_at: now,
+        };
+
+        subscriptions.insert(student_id.to_string(), subscription.clone());
+        self.persist(&subscriptions).await?;
+
+        info!("[Subscriptions] Saved subscription {} for student {}", subscription.id, student_id);
+        Ok(subscription)
+    }
+
+    pub async fn get(&self, student_id: &str) -> Option<Subscription> {
+        self.subscriptions.read().await.get(student_id).cloned()
+    }
+
+    pub async fn remove(&self, student_id: &str) -> Result<bool, AppError> {
+        let mut subscriptions = self.subscriptions.write().await;
+        let removed = subscriptions.remove(student_id).is_some();
+        if removed {
+            self.persist(&subscriptions).await?;
+            info!("[Subscriptions] Removed subscription for student {}", student_id);
+        }
+        Ok(removed)
+    }
+
+    /// Removes the student's subscription unless it was saved again after `updated_at`,
+    /// so a fresh subscription is not dropped for a problem with the one it replaced.
+    pub async fn remove_if_unchanged(&self, student_id: &str, updated_at: DateTime<Utc>) -> Result<bool, AppError> {
+        let mut subscriptions = self.subscriptions.write().await;
+        if subscriptions.get(student_id).is_none_or(|s| s.updated_at != updated_at) {
+            return Ok(false);
+        }
+        subscriptions.remove(student_id);
+        self.persist(&subscriptions).await?;
+        info!("[Subscriptions] Removed subscription for student {}", student_id);
+        Ok(true)
+    }
+
+    /// Decrypted view of every subscription for the background poller.
+    pub async fn watches(&self) -> Vec<Watch> {
+        let subscriptions = self.subscriptions.read().await;
+        let mut watches = Vec::with_capacity(subscriptions.len());
+
+        for subscription in subscriptions.values() {
+            match self.decrypt_watch(subscription) {
+                Ok(watch) => watches.push(watch),
+                Err(e) => warn!("[Subscriptions] Skipping subscription {}: {}", subscription.id, e),
+            }
+        }
+
+        watches
+    }
+
+    fn decrypt_watch(&self, subscription: &Subscription) -> Result<Watch, AppError> {
+        let token = std::str::from_utf8(&self.keyring.decrypt(&subscription.encrypted_token)?)
+            .map(|token| Secret::new(token.to_string()))
+            .map_err(|_| AppError::InternalError("Corrupt token".to_string()))?;
+        let channel: NotificationChannel =
+            serde_json::from_slice(&self.keyring.decrypt(&subscription.encrypted_channel)?)?;
+
+        Ok(Watch {
+            subscription_id: subscription.id.clone(),
+            student_id: subscription.student_id.clone(),
+            token,
+            channel,
+            thresholds: subscription.thresholds.clone(),
+            poll_interval_seconds: subscription.poll_interval_seconds,
+            updated_at: subscription.updated_at,
+        })
+    }
+
+    /// Writes the current subscriptions out again; called on shutdown.
+    pub async fn flush(&self) -> Result<(), AppError> {
+        self.persist(&*self.subscriptions.read().await).await
+    }
+
+    // Write to a temp file and rename so a crash never leaves a truncated store
+    async fn persist(&self, subscriptions: &HashMap<String, Subscription>) -> Result<(), AppError> {
+        let list: Vec<&Subscription> = subscriptions.values().collect();
+        let bytes = serde_json::to_vec_pretty(&list)?;
+        let tmp_path = self.path.with_extension("json.tmp");
+
+        let result = async {
+            if let Some(parent) = self.path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&tmp_path, &bytes).await?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                tokio::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600)).await?;
+            }
+            tokio::fs::rename(&tmp_path, &self.path).await
+        }.await;
+
+        result.map_err(|e| AppError::InternalError(format!("Failed to persist subscriptions: {}", e)))
+    }
+}
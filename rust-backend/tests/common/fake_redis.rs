@@ -0,0 +1,215 @@
+// In-process stand-in for redis-server: GET, SET with PX and NX, PSETEX, DEL, SCAN with MATCH
+// on a trailing `*`, AUTH, SELECT, CLIENT and PING over real TCP, so the Redis backend can be
+// tested without a server. There is no Lua: a loaded script is taken to be the backend's
+// compare-and-delete unlock.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+enum Reply {
+    Status(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Reply::Status(status) => out.extend_from_slice(format!("+{}\r\n", status).as_bytes()),
+            Reply::Error(error) => out.extend_from_slice(format!("-{}\r\n", error).as_bytes()),
+            Reply::Integer(n) => out.extend_from_slice(format!(":{}\r\n", n).as_bytes()),
+            Reply::Bulk(None) => out.extend_from_slice(b"$-1\r\n"),
+            Reply::Bulk(Some(bytes)) => {
+                out.extend_from_slice(format!("${}\r\n", bytes.len()).as_bytes());
+                out.extend_from_slice(bytes);
+                out.extend_from_slice(b"\r\n");
+            }
+            Reply::Array(items) => {
+                out.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                for item in items {
+                    item.encode(out);
+                }
+            }
+        }
+    }
+}
+
+// Clients send every command as an array of bulk strings
+async fn read_command(stream: &mut (impl AsyncBufRead + Unpin)) -> Option<Vec<Vec<u8>>> {
+    async fn header(stream: &mut (impl AsyncBufRead + Unpin), kind: u8) -> Option<usize> {
+        let mut line = String::new();
+        stream.read_line(&mut line).await.ok()?;
+        line.trim_end().strip_prefix(kind as char)?.parse().ok()
+    }
+
+    let count = header(stream, b'*').await?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut arg = vec![0; header(stream, b'$').await? + 2];
+        stream.read_exact(&mut arg).await.ok()?;
+        arg.truncate(arg.len() - 2);
+        args.push(arg);
+    }
+    Some(args)
+}
+
+#[derive(Default)]
+struct Store {
+    values: HashMap<Vec<u8>, (Vec<u8>, Option<Instant>)>,
+    script_loaded: bool,
+}
+
+impl Store {
+    fn live(&mut self, key: &[u8]) -> Option<&Vec<u8>> {
+        if matches!(self.values.get(key), Some((_, Some(expires))) if *expires <= Instant::now()) {
+            self.values.remove(key);
+        }
+        self.values.get(key).map(|(value, _)| value)
+    }
+}
+
+pub struct FakeRedis {
+    address: String,
+    store: Arc<Mutex<Store>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl FakeRedis {
+    /// Starts on an ephemeral port; with a password, every connection must AUTH first.
+    pub async fn start(password: Option<&str>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let store = Arc::new(Mutex::new(Store::default()));
+        let password = password.map(str::to_string);
+
+        let server = tokio::spawn({
+            let store = store.clone();
+            async move {
+                loop {
+                    let Ok((stream, _)) = listener.accept().await else { return };
+                    let (store, password) = (store.clone(), password.clone());
+                    tokio::spawn(async move {
+                        let mut stream = BufReader::new(stream);
+                        let mut authenticated = password.is_none();
+                        while let Some(args) = read_command(&mut stream).await {
+                            let reply = execute(&store, password.as_deref(), &mut authenticated, &args);
+                            let mut out = Vec::new();
+                            reply.encode(&mut out);
+                            if stream.get_mut().write_all(&out).await.is_err() {
+                                return;
+                            }
+                        }
+                    });
+                }
+            }
+        });
+
+        Self { address, store, server }
+    }
+
+    pub fn url(&self) -> String {
+        format!("redis://{}", self.address)
+    }
+
+    pub fn url_with_password(&self, password: &str) -> String {
+        format!("redis://:{}@{}", password, self.address)
+    }
+
+    /// Every stored key, including the backend's `aims:` namespace.
+    pub fn keys(&self) -> Vec<String> {
+        let mut store = self.store.lock().unwrap();
+        let keys: Vec<Vec<u8>> = store.values.keys().cloned().collect();
+        let mut live: Vec<String> = keys
+            .into_iter()
+            .filter(|key| store.live(key).is_some())
+            .map(|key| String::from_utf8_lossy(&key).into_owned())
+            .collect();
+        live.sort();
+        live
+    }
+}
+
+impl Drop for FakeRedis {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn execute(store: &Mutex<Store>, password: Option<&str>, authenticated: &mut bool, args: &[Vec<u8>]) -> Reply {
+    let command = args.first().map(|name| String::from_utf8_lossy(name).to_uppercase()).unwrap_or_default();
+
+    if command == "AUTH" {
+        let given = args.last().map(|p| String::from_utf8_lossy(p).into_owned());
+        *authenticated = given.as_deref() == password;
+        return if *authenticated {
+            Reply::Status("OK")
+        } else {
+            Reply::Error("WRONGPASS invalid username-password pair".to_string())
+        };
+    }
+    if !*authenticated {
+        return Reply::Error("NOAUTH Authentication required.".to_string());
+    }
+
+    let mut store = store.lock().unwrap();
+    match (command.as_str(), args) {
+        ("PING", _) => Reply::Status("PONG"),
+        ("SELECT" | "CLIENT", _) => Reply::Status("OK"),
+        ("GET", [_, key]) => Reply::Bulk(store.live(key).cloned()),
+        ("SET", [_, key, value, options @ ..]) => {
+            let options: Vec<String> = options.iter().map(|o| String::from_utf8_lossy(o).to_uppercase()).collect();
+            let expires = options
+                .iter()
+                .position(|option| option == "PX")
+                .and_then(|at| options.get(at + 1)?.parse().ok())
+                .map(|ms| Instant::now() + Duration::from_millis(ms));
+            if options.iter().any(|option| option == "NX") && store.live(key).is_some() {
+                return Reply::Bulk(None);
+            }
+            store.values.insert(key.clone(), (value.clone(), expires));
+            Reply::Status("OK")
+        }
+        ("PSETEX", [_, key, ms, value]) => {
+            let expires = String::from_utf8_lossy(ms).parse().ok().map(|ms| Instant::now() + Duration::from_millis(ms));
+            store.values.insert(key.clone(), (value.clone(), expires));
+            Reply::Status("OK")
+        }
+        ("SCRIPT", [_, load, _]) if load.eq_ignore_ascii_case(b"LOAD") => {
+            store.script_loaded = true;
+            Reply::Bulk(Some(b"unlock".to_vec()))
+        }
+        ("EVALSHA", _) if !store.script_loaded => Reply::Error("NOSCRIPT No matching script.".to_string()),
+        ("EVALSHA", [_, _sha, _count, key, token]) => {
+            if store.live(key) == Some(token) {
+                store.values.remove(key);
+                Reply::Integer(1)
+            } else {
+                Reply::Integer(0)
+            }
+        }
+        ("DEL", [_, keys @ ..]) => {
+            let removed = keys.iter().filter(|key| store.live(key).is_some() && store.values.remove(*key).is_some());
+            Reply::Integer(removed.count() as i64)
+        }
+        ("SCAN", [_, _cursor, options @ ..]) => {
+            let pattern = options
+                .iter()
+                .position(|option| option.eq_ignore_ascii_case(b"MATCH"))
+                .and_then(|at| options.get(at + 1))
+                .map(|pattern| String::from_utf8_lossy(pattern).trim_end_matches('*').replace('\\', ""))
+                .unwrap_or_default();
+            let keys: Vec<Vec<u8>> = store.values.keys().cloned().collect();
+            let matching = keys
+                .into_iter()
+                .filter(|key| key.starts_with(pattern.as_bytes()) && store.live(key).is_some())
+                .map(|key| Reply::Bulk(Some(key)))
+                .collect();
+            Reply::Array(vec![Reply::Bulk(Some(b"0".to_vec())), Reply::Array(matching)])
+        }
+        _ => Reply::Error(format!("ERR unknown command '{}'", command)),
+    }
+}
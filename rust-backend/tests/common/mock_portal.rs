@@ -0,0 +1,250 @@
+// In-process stand-in for the portal endpoints the backend calls, serving fixtures from
+// testdata/portal. Faults can be injected per endpoint to exercise error paths.
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    Authenticate,
+    Subjects,
+    AttendanceSummary,
+    Cards,
+    Quizzes,
+    QuizDetails,
+}
+
+#[derive(Clone, Debug)]
+pub enum Fault {
+    /// Delay the response, then answer normally
+    Latency(Duration),
+    /// Answer with this status and a JSON error body
+    Status(u16),
+    /// Answer 200 with a body that is not JSON
+    Malformed,
+    /// Answer 200 with the envelope the portal uses for expired tokens
+    ExpiredToken,
+}
+
+#[derive(Default)]
+struct MockState {
+    fixtures: Mutex<HashMap<String, Value>>,
+    faults: Mutex<HashMap<Endpoint, Fault>>,
+    hits: Mutex<HashMap<Endpoint, usize>>,
+    tokens: Mutex<HashSet<String>>,
+    issued: Mutex<usize>,
+}
+
+pub struct MockPortal {
+    pub base_url: String,
+    state: Arc<MockState>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl MockPortal {
+    /// Starts the mock on an ephemeral port with the fixtures in testdata/portal.
+    pub async fn start() -> Self {
+        let state = Arc::new(MockState::default());
+        load_fixtures(&fixture_dir(), "", &mut state.fixtures.lock().unwrap());
+
+        let router = Router::new()
+            .route("/admin/authenticate", post(authenticate))
+            .route("/custom/getCFMappedWithStudentID", get(cf_mapped))
+            .route("/cards", get(cards))
+            .route("/custom/myEvaluatedQuizzes", get(quizzes))
+            .route("/fetchQuizDetails", post(quiz_details))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        Self { base_url, state, server }
+    }
+
+    pub fn quiz_details_url(&self) -> String {
+        format!("{}/fetchQuizDetails", self.base_url)
+    }
+
+    /// Accepts a new token for the fixture student, as if they had logged in elsewhere.
+    pub fn issue_token(&self) -> String {
+        issue_token(&self.state)
+    }
+
+    /// Makes every token issued so far invalid.
+    pub fn expire_tokens(&self) {
+        self.state.tokens.lock().unwrap().clear();
+    }
+
+    /// Replaces a fixture, named by its path under testdata/portal without `.json`.
+    pub fn set_fixture(&self, name: &str, value: Value) {
+        self.state.fixtures.lock().unwrap().insert(name.to_string(), value);
+    }
+
+    pub fn inject(&self, endpoint: Endpoint, fault: Fault) {
+        self.state.faults.lock().unwrap().insert(endpoint, fault);
+    }
+
+    pub fn clear_faults(&self) {
+        self.state.faults.lock().unwrap().clear();
+    }
+
+    pub fn hits(&self, endpoint: Endpoint) -> usize {
+        self.state.hits.lock().unwrap().get(&endpoint).copied().unwrap_or(0)
+    }
+}
+
+impl Drop for MockPortal {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn fixture_dir() -> PathBuf {
+    PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/portal"))
+}
+
+fn load_fixtures(dir: &std::path::Path, prefix: &str, fixtures: &mut HashMap<String, Value>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+        if path.is_dir() {
+            load_fixtures(&path, &format!("{}{}/", prefix, stem), fixtures);
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            let value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+            fixtures.insert(format!("{}{}", prefix, stem), value);
+        }
+    }
+}
+
+fn issue_token(state: &MockState) -> String {
+    let mut issued = state.issued.lock().unwrap();
+    *issued += 1;
+    let token = format!("mock-portal-token-{}", issued);
+    state.tokens.lock().unwrap().insert(token.clone());
+    token
+}
+
+fn fixture(state: &MockState, name: &str) -> Option<Value> {
+    state.fixtures.lock().unwrap().get(name).cloned()
+}
+
+fn json_error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "response": null, "msg": message, "message": message }))).into_response()
+}
+
+// Counts the hit and applies any injected fault; `Some` short-circuits the handler
+async fn intercept(state: &MockState, endpoint: Endpoint) -> Option<Response> {
+    *state.hits.lock().unwrap().entry(endpoint).or_default() += 1;
+
+    let fault = state.faults.lock().unwrap().get(&endpoint).cloned();
+    match fault? {
+        Fault::Latency(delay) => {
+            tokio::time::sleep(delay).await;
+            None
+        }
+        Fault::Status(status) => {
+            let status = StatusCode::from_u16(status).unwrap();
+            Some(json_error(status, status.canonical_reason().unwrap_or("error")))
+        }
+        Fault::Malformed => Some(
+            (StatusCode::OK, [(header::CONTENT_TYPE, "application/json")], "{\"response\": {\"data\": [").into_response(),
+        ),
+        Fault::ExpiredToken => Some(json_error(StatusCode::OK, "Token expired, please login again")),
+    }
+}
+
+fn authorized(state: &MockState, token: Option<&str>) -> bool {
+    token.is_some_and(|token| state.tokens.lock().unwrap().contains(token))
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+async fn authenticate(State(state): State<Arc<MockState>>, body: String) -> Response {
+    if let Some(response) = intercept(&state, Endpoint::Authenticate).await {
+        return response;
+    }
+
+    let form: HashMap<String, String> = url::form_urlencoded::parse(body.as_bytes()).into_owned().collect();
+    let account = fixture(&state, "authenticate").unwrap();
+    if form.get("username").map(String::as_str) != account["username"].as_str()
+        || form.get("password").map(String::as_str) != account["password"].as_str()
+    {
+        return json_error(StatusCode::UNAUTHORIZED, "Invalid username or password");
+    }
+
+    let mut response = account["response"].clone();
+    response["token"] = json!(issue_token(&state));
+    Json(response).into_response()
+}
+
+async fn cf_mapped(
+    State(state): State<Arc<MockState>>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let (endpoint, name) = match query.contains_key("embed_attendance_summary") {
+        true => (Endpoint::AttendanceSummary, "cf_mapped_with_summary"),
+        false => (Endpoint::Subjects, "cf_mapped"),
+    };
+    if let Some(response) = intercept(&state, endpoint).await {
+        return response;
+    }
+    if !authorized(&state, bearer(&headers)) {
+        return json_error(StatusCode::UNAUTHORIZED, "Unauthorized");
+    }
+
+    Json(fixture(&state, name).unwrap()).into_response()
+}
+
+async fn cards(State(state): State<Arc<MockState>>, Query(query): Query<HashMap<String, String>>) -> Response {
+    if let Some(response) = intercept(&state, Endpoint::Cards).await {
+        return response;
+    }
+    if !authorized(&state, query.get("token").map(String::as_str)) {
+        return json_error(StatusCode::UNAUTHORIZED, "Unauthorized");
+    }
+
+    let cf_id = query.get("equalto___cf_id").cloned().unwrap_or_default();
+    let cards = fixture(&state, &format!("cards/{}", cf_id)).unwrap_or_else(|| json!({ "response": { "data": [] } }));
+    Json(cards).into_response()
+}
+
+async fn quizzes(State(state): State<Arc<MockState>>, headers: HeaderMap) -> Response {
+    if let Some(response) = intercept(&state, Endpoint::Quizzes).await {
+        return response;
+    }
+    if !authorized(&state, bearer(&headers)) {
+        return json_error(StatusCode::UNAUTHORIZED, "Unauthorized");
+    }
+
+    Json(fixture(&state, "quizzes").unwrap()).into_response()
+}
+
+async fn quiz_details(State(state): State<Arc<MockState>>, Json(body): Json<Value>) -> Response {
+    if let Some(response) = intercept(&state, Endpoint::QuizDetails).await {
+        return response;
+    }
+
+    let details = fixture(&state, "quiz_details").unwrap();
+    if body["quiz_uc"] != details["response"]["data"]["quiz_uc"] {
+        return Json(json!({ "response": { "data": {} }, "msg": "Invalid Quiz ID" })).into_response();
+    }
+    Json(details).into_response()
+}
@@ -0,0 +1,4 @@
+// Test doubles for the services the backend talks to
+#[cfg(feature = "redis")]
+pub mod fake_redis;
+pub mod mock_portal;
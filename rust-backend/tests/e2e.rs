@@ -0,0 +1,712 @@
+// End-to-end tests: the full router from main.rs, and the client SDK, against the mock portal
+mod common;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use serde_json::{json, Value};
+
+use aims_backend::{
+    server::{build_router, build_state, Config, UpstreamMode},
+    AimsClient, AppError,
+};
+use common::mock_portal::{Endpoint, Fault, MockPortal};
+
+const USERNAME: &str = "2200320100001";
+const PASSWORD: &str = "portal-pass";
+const SECRETS_KEYS: &str = "e2e:MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+
+struct TestApp {
+    base_url: String,
+    client: reqwest::Client,
+    server: tokio::task::JoinHandle<()>,
+    portal: MockPortal,
+    dir: PathBuf,
+}
+
+struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or_else(|_| panic!("not JSON: {}", self.text()))
+    }
+
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+
+    fn header(&self, name: header::HeaderName) -> &str {
+        self.headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or("")
+    }
+}
+
+impl TestApp {
+    async fn start() -> Self {
+        Self::start_with(|_| {}).await
+    }
+
+    // Like `start`, with a last chance to adjust the config before the state is built
+    async fn start_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let portal = MockPortal::start().await;
+        let dir = std::env::temp_dir().join(format!("aims-e2e-{}", uuid::Uuid::new_v4()));
+
+        let mut config = Config::defaults();
+        config.external_api_base = portal.base_url.clone();
+        config.quiz_details_url = portal.quiz_details_url();
+        config.secrets_keys = Some(SECRETS_KEYS.to_string());
+        config.secrets_key_file = None;
+        config.subscription_encryption_key = None;
+        config.secrets_active_key_id = None;
+        config.enable_subscriptions = true;
+        config.subscriptions_path = dir.join("subscriptions.json").to_string_lossy().to_string();
+        config.calendar_feeds_path = dir.join("calendar_feeds.json").to_string_lossy().to_string();
+        config.history_db_path = dir.join("history.db").to_string_lossy().to_string();
+        config.public_base_url = "http://aims.test".to_string();
+        config.session_cookie_secure = false;
+        config.legacy_body_token = true;
+        config.upstream_mode = UpstreamMode::Live;
+        configure(&mut config);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let state = build_state(Arc::new(config)).await.unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, build_router(state)).await.unwrap();
+        });
+
+        Self {
+            base_url,
+            client: reqwest::Client::new(),
+            server,
+            portal,
+            dir,
+        }
+    }
+
+    async fn send(&self, method: Method, uri: &str, auth: Option<&str>, body: Option<Value>) -> TestResponse {
+        let method = reqwest::Method::from_bytes(method.as_str().as_bytes()).unwrap();
+        let mut request = self.client.request(method, format!("{}{}", self.base_url, uri));
+        match auth {
+            Some(cookie) if cookie.starts_with("aims_session=") => request = request.header("cookie", cookie),
+            Some(token) => request = request.bearer_auth(token),
+            None => {}
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        // reqwest 0.11 uses http 0.2, so headers are copied over by name
+        let response = request.send().await.unwrap();
+        let status = StatusCode::from_u16(response.status().as_u16()).unwrap();
+        let mut headers = HeaderMap::new();
+        for (name, value) in response.headers() {
+            headers.append(
+                header::HeaderName::from_bytes(name.as_str().as_bytes()).unwrap(),
+                header::HeaderValue::from_bytes(value.as_bytes()).unwrap(),
+            );
+        }
+        let body = response.bytes().await.unwrap().to_vec();
+        TestResponse { status, headers, body }
+    }
+
+    async fn get(&self, uri: &str, auth: Option<&str>) -> TestResponse {
+        self.send(Method::GET, uri, auth, None).await
+    }
+
+    async fn post(&self, uri: &str, auth: Option<&str>, body: Value) -> TestResponse {
+        self.send(Method::POST, uri, auth, Some(body)).await
+    }
+
+    // Returns the `aims_session=<id>` pair to send back as a Cookie header
+    async fn login(&self, remember: bool) -> String {
+        let response = self
+            .post("/api/login", None, json!({ "username": USERNAME, "password": PASSWORD, "remember": remember }))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        response.header(header::SET_COOKIE).split(';').next().unwrap().to_string()
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        self.server.abort();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[tokio::test]
+async fn health_and_metrics() {
+    let app = TestApp::start().await;
+
+    let live = app.get("/health/live", None).await;
+    assert_eq!(live.status, StatusCode::OK);
+    assert_eq!(live.json()["status"], "alive");
+
+    let ready = app.get("/health/ready", None).await;
+    assert_eq!(ready.status, StatusCode::OK, "{}", ready.text());
+    let ready = ready.json();
+    assert_eq!(ready["upstream"]["reachable"], true);
+    assert_eq!(ready["circuit"], "closed");
+    assert_eq!(ready["version"], env!("CARGO_PKG_VERSION"));
+    assert!(ready["cache"]["attendance_hits"].is_u64());
+
+    let metrics = app.get("/metrics", None).await;
+    assert_eq!(metrics.status, StatusCode::OK);
+    assert!(metrics.json().is_object());
+}
+
+#[tokio::test]
+async fn login_session_and_logout() {
+    let app = TestApp::start().await;
+
+    let rejected = app.post("/api/login", None, json!({ "username": USERNAME, "password": "wrong" })).await;
+    assert_eq!(rejected.status, StatusCode::UNAUTHORIZED);
+
+    let response = app.post("/api/login", None, json!({ "username": USERNAME, "password": PASSWORD })).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.header(header::SET_COOKIE).contains("HttpOnly"));
+    assert!(response.json().get("token").is_none(), "portal token leaked to the client");
+    assert_eq!(response.json()["response"]["name"], "Test Student");
+
+    let cookie = response.header(header::SET_COOKIE).split(';').next().unwrap().to_string();
+    let session = app.get("/api/session", Some(&cookie)).await;
+    assert_eq!(session.status, StatusCode::OK);
+    assert_eq!(session.json()["reauth_enabled"], false);
+
+    let logout = app.send(Method::POST, "/api/logout", Some(&cookie), None).await;
+    assert_eq!(logout.status, StatusCode::NO_CONTENT);
+    assert!(logout.header(header::SET_COOKIE).contains("Max-Age=0"));
+
+    let expired = app.get("/api/all-attendance", Some(&cookie)).await;
+    assert_eq!(expired.status, StatusCode::UNAUTHORIZED);
+    assert!(expired.header(header::WWW_AUTHENTICATE).contains("invalid_token"));
+}
+
+#[tokio::test]
+async fn attendance_from_body_header_and_session() {
+    let app = TestApp::start().await;
+    let token = app.portal.issue_token();
+
+    let response = app.post("/api/attendance", None, json!({ "token": token })).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.header(header::HeaderName::from_static("x-cache")), "MISS");
+    let body = response.json();
+    assert_eq!(body["student_id"], USERNAME);
+    assert_eq!(body["total_present"], 5);
+    assert_eq!(body["total_classes"], 6);
+    assert_eq!(body["daily_attendance"][0]["course"], "Database Management Systems");
+
+    let cached = app.post("/api/attendance", Some(&token), json!({})).await;
+    assert_eq!(cached.header(header::HeaderName::from_static("x-cache")), "HIT");
+    assert_eq!(app.portal.hits(Endpoint::AttendanceSummary), 1);
+
+    let cookie = app.login(false).await;
+    let via_session = app.post("/api/attendance", Some(&cookie), json!({})).await;
+    assert_eq!(via_session.status, StatusCode::OK);
+
+    let missing = app.post("/api/attendance", None, json!({})).await;
+    assert_eq!(missing.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(missing.header(header::WWW_AUTHENTICATE), "Bearer realm=\"aims\"");
+}
+
+#[tokio::test]
+async fn full_attendance_and_derived_views() {
+    let app = TestApp::start().await;
+    let token = app.portal.issue_token();
+
+    let all = app.get("/api/all-attendance", Some(&token)).await;
+    assert_eq!(all.status, StatusCode::OK, "{}", all.text());
+    let body = all.json();
+    assert_eq!(body["total_present_all_subjects"], 5);
+    assert_eq!(body["total_absent_all_subjects"], 1);
+    assert_eq!(body["subjects"]["Operating Systems"]["total_present"], 2);
+    assert_eq!(body["course_code_map"]["BCS501"], "Database Management Systems");
+    assert_eq!(app.portal.hits(Endpoint::Subjects), 1);
+    assert_eq!(app.portal.hits(Endpoint::Cards), 2);
+
+    let csv = app.get("/api/all-attendance/export?format=csv", Some(&token)).await;
+    assert_eq!(csv.status, StatusCode::OK);
+    assert!(csv.header(header::CONTENT_TYPE).starts_with("text/csv"));
+    assert_eq!(csv.text().lines().count(), 6, "header plus one row per subject and day");
+
+    let xlsx = app.get("/api/all-attendance/export?format=xlsx", Some(&token)).await;
+    assert_eq!(xlsx.status, StatusCode::OK);
+    assert!(xlsx.body.starts_with(b"PK"));
+
+    let report = app.get("/api/attendance/report.pdf", Some(&token)).await;
+    assert_eq!(report.status, StatusCode::OK);
+    assert!(report.body.starts_with(b"%PDF-1.4"));
+
+    let changes = app.get("/api/attendance/changes", Some(&token)).await;
+    assert_eq!(changes.status, StatusCode::OK);
+    assert_eq!(changes.json()["student_id"], USERNAME);
+}
+
+#[tokio::test]
+async fn responses_are_compressed_when_accepted() {
+    use std::io::Read;
+    let app = TestApp::start().await;
+    let token = app.portal.issue_token();
+    let plain = app.get("/api/all-attendance", Some(&token)).await;
+    assert_eq!(plain.header(header::CONTENT_ENCODING), "");
+
+    let fetch = |encoding: &'static str| {
+        app.client
+            .get(format!("{}/api/all-attendance", app.base_url))
+            .bearer_auth(&token)
+            .header("accept-encoding", encoding)
+            .send()
+    };
+    let brotli = fetch("br").await.unwrap();
+    assert_eq!(brotli.headers()["content-encoding"], "br");
+
+    let gzip = fetch("gzip").await.unwrap();
+    assert_eq!(gzip.headers()["content-encoding"], "gzip");
+    let compressed = gzip.bytes().await.unwrap();
+    assert!(compressed.len() < plain.body.len());
+    let mut body = String::new();
+    flate2::read::GzDecoder::new(&compressed[..]).read_to_string(&mut body).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), plain.json());
+}
+
+#[tokio::test]
+async fn calendar_feed_and_subscribe_url() {
+    let app = TestApp::start().await;
+    let token = app.portal.issue_token();
+
+    let feed = app.get("/api/calendar.ics", Some(&token)).await;
+    assert_eq!(feed.status, StatusCode::OK, "{}", feed.text());
+    assert!(feed.text().starts_with("BEGIN:VCALENDAR"));
+    assert!(feed.text().contains("DBMS Quiz 1"));
+    assert!(feed.text().contains("CATEGORIES:Attendance"));
+    // One timed event per lecture card: 09:00 and 11:00 IST on 2024-08-02
+    let text = feed.text().replace("\r\n ", "");
+    assert!(text.contains("DTSTART:20240802T033000Z\r\nDTEND:20240802T043000Z"));
+    assert!(text.contains("DTSTART:20240802T053000Z\r\nDTEND:20240802T063000Z"));
+    assert!(text.contains(" — Absent\r\n"));
+
+    let subscribe = app.get("/api/calendar/subscribe", Some(&token)).await;
+    assert_eq!(subscribe.status, StatusCode::OK);
+    let url = subscribe.json()["url"].as_str().unwrap().to_string();
+    assert!(url.contains("/api/calendar.ics?feed=") && !url.contains(&token));
+    let stored = std::fs::read_to_string(app.dir.join("calendar_feeds.json")).unwrap();
+    assert!(!stored.contains(&token) && !stored.contains(url.split("feed=").nth(1).unwrap()));
+
+    // Asking again hands out the same URL
+    let again = app.get("/api/calendar/subscribe", Some(&token)).await;
+    assert_eq!(again.json()["url"], url.as_str());
+
+    // The feed is served from the caches filled by the first request
+    let (cards, quizzes) = (app.portal.hits(Endpoint::Cards), app.portal.hits(Endpoint::Quizzes));
+    let by_feed = app.get(url.strip_prefix("http://aims.test").unwrap(), None).await;
+    assert_eq!(by_feed.status, StatusCode::OK);
+    assert!(by_feed.text().contains("DBMS Quiz 1"));
+    assert_eq!((app.portal.hits(Endpoint::Cards), app.portal.hits(Endpoint::Quizzes)), (cards, quizzes));
+
+    let revoked = app.send(Method::DELETE, "/api/calendar/subscribe", Some(&token), None).await;
+    assert_eq!(revoked.status, StatusCode::NO_CONTENT);
+    let after = app.get(url.strip_prefix("http://aims.test").unwrap(), None).await;
+    assert_eq!(after.status, StatusCode::UNAUTHORIZED);
+
+    let forged = app.get("/api/calendar.ics?feed=not-a-feed", None).await;
+    assert_eq!(forged.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn subscriptions_lifecycle() {
+    // Allowed so the test does not depend on DNS
+    let app = TestApp::start_with(|config| config.webhook_allowed_hosts = vec!["example.com".to_string()]).await;
+    let token = app.portal.issue_token();
+    let channel = json!({ "type": "webhook", "url": "https://example.com/hook", "secret": "0123456789abcdef" });
+
+    for url in [
+        "http://hooks.example.org/hook",
+        "https://127.0.0.1/hook",
+        "https://169.254.169.254/latest/meta-data",
+        "https://10.0.0.5/hook",
+        "https://[::1]/hook",
+        "https://[::ffff:192.168.1.1]/hook",
+    ] {
+        let channel = json!({ "type": "webhook", "url": url, "secret": "0123456789abcdef" });
+        let rejected = app.post("/api/subscriptions", Some(&token), json!({ "channel": channel })).await;
+        assert_eq!(rejected.status, StatusCode::BAD_REQUEST, "{} was accepted", url);
+    }
+
+    let missing = app.get("/api/subscriptions", Some(&token)).await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+
+    let created = app.post("/api/subscriptions", Some(&token), json!({ "channel": channel })).await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.text());
+    assert_eq!(created.json()["student_id"], USERNAME);
+
+    let stored = std::fs::read_to_string(app.dir.join("subscriptions.json")).unwrap();
+    assert!(!stored.contains(&token) && !stored.contains("0123456789abcdef"));
+
+    let fetched = app.get("/api/subscriptions", Some(&token)).await;
+    assert_eq!(fetched.json()["channel_type"], "webhook");
+
+    let deleted = app.send(Method::DELETE, "/api/subscriptions", Some(&token), None).await;
+    assert_eq!(deleted.status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn cors_preflight_allows_delete() {
+    let app = TestApp::start_with(|config| config.cors_origin = vec!["https://aims.example".to_string()]).await;
+    let preflight = app
+        .client
+        .request(reqwest::Method::OPTIONS, format!("{}/api/subscriptions", app.base_url))
+        .header("origin", "https://aims.example")
+        .header("access-control-request-method", "DELETE")
+        .header("access-control-request-headers", "authorization")
+        .send()
+        .await
+        .unwrap();
+    assert!(preflight.status().is_success());
+    assert_eq!(preflight.headers()["access-control-allow-origin"], "https://aims.example");
+    let methods = preflight.headers()["access-control-allow-methods"].to_str().unwrap();
+    assert!(methods.split(',').any(|method| method.trim() == "DELETE"), "{}", methods);
+}
+
+#[tokio::test]
+async fn quiz_results_analytics_and_start() {
+    let app = TestApp::start().await;
+    let token = app.portal.issue_token();
+
+    let quizzes = app.get("/api/quiz", Some(&token)).await;
+    assert_eq!(quizzes.status, StatusCode::OK, "{}", quizzes.text());
+    assert_eq!(quizzes.json()["quizzes"].as_array().unwrap().len(), 3);
+
+    let analytics = app.get("/api/quiz/analytics", Some(&token)).await;
+    assert_eq!(analytics.status, StatusCode::OK);
+    assert_eq!(analytics.json()["quiz_count"], 3);
+    assert_eq!(analytics.json()["trend"]["direction"], "improving");
+    assert_eq!(app.portal.hits(Endpoint::Quizzes), 1);
+
+    let start = |code: &str| json!({ "quiz_code": code, "user_unique_code": USERNAME, "pin": "4321" });
+    let ended = app.post("/api/quiz/start", None, start("ab12")).await;
+    assert_eq!(ended.status, StatusCode::OK, "{}", ended.text());
+    assert_eq!(ended.json()["status"], "ended");
+
+    let now = chrono::Utc::now();
+    app.portal.set_fixture(
+        "quiz_details",
+        json!({ "response": { "data": {
+            "quiz_uc": "AB12",
+            "cf_id": "1201",
+            "login_time": (now - chrono::Duration::minutes(5)).to_rfc3339(),
+            "end_time": (now + chrono::Duration::minutes(30)).to_rfc3339(),
+        } } }),
+    );
+    let active = app.post("/api/quiz/start", None, start("AB12")).await;
+    assert_eq!(active.json()["status"], "active");
+    assert!(active.json()["access_url"].as_str().unwrap().contains("req_id="));
+
+    assert_eq!(app.post("/api/quiz/start", None, start("ZZ99")).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(app.post("/api/quiz/start", None, start("A")).await.status, StatusCode::BAD_REQUEST);
+}
+
+#[cfg(feature = "history")]
+#[tokio::test]
+async fn attendance_history_snapshots() {
+    let app = TestApp::start().await;
+    let token = app.portal.issue_token();
+
+    app.post("/api/attendance", Some(&token), json!({})).await;
+    let history = app.get("/api/attendance/history", Some(&token)).await;
+    assert_eq!(history.status, StatusCode::OK, "{}", history.text());
+    assert_eq!(history.json()["snapshots"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn upstream_faults_map_to_errors() {
+    let app = TestApp::start().await;
+    let token = app.portal.issue_token();
+
+    app.portal.inject(Endpoint::AttendanceSummary, Fault::Status(500));
+    let failed = app.post("/api/attendance", Some(&token), json!({})).await;
+    assert_eq!(failed.status, StatusCode::BAD_GATEWAY);
+
+    app.portal.inject(Endpoint::Quizzes, Fault::Malformed);
+    let malformed = app.get("/api/quiz", Some(&token)).await;
+    assert_eq!(malformed.status, StatusCode::BAD_GATEWAY);
+
+    app.portal.inject(Endpoint::Quizzes, Fault::ExpiredToken);
+    let expired = app.get("/api/quiz", Some(&token)).await;
+    assert_eq!(expired.status, StatusCode::UNAUTHORIZED);
+    assert!(expired.header(header::WWW_AUTHENTICATE).contains("invalid_token"));
+
+    // A subject whose cards fail is left out instead of failing the whole view
+    app.portal.clear_faults();
+    app.portal.inject(Endpoint::Cards, Fault::Status(503));
+    let partial = app.get("/api/all-attendance", Some(&token)).await;
+    assert_eq!(partial.status, StatusCode::OK);
+    assert_eq!(partial.json()["subjects"], json!({}));
+}
+
+#[tokio::test]
+async fn portal_calls_honour_request_timeout_seconds() {
+    let app = TestApp::start_with(|config| config.request_timeout_seconds = 1).await;
+    let token = app.portal.issue_token();
+
+    app.portal.inject(Endpoint::AttendanceSummary, Fault::Latency(Duration::from_secs(3)));
+    let started = std::time::Instant::now();
+    let slow = app.post("/api/attendance", Some(&token), json!({})).await;
+    assert_eq!(slow.status, StatusCode::BAD_GATEWAY);
+    assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn failing_portal_opens_the_circuit_and_fails_readiness() {
+    let app = TestApp::start_with(|config| config.circuit_failure_threshold = 2).await;
+    let token = app.portal.issue_token();
+
+    app.portal.inject(Endpoint::AttendanceSummary, Fault::Status(502));
+    for _ in 0..2 {
+        app.post("/api/attendance", Some(&token), json!({})).await;
+    }
+    let hits = app.portal.hits(Endpoint::AttendanceSummary);
+
+    // Refused without reaching the portal while open
+    app.portal.clear_faults();
+    let refused = app.post("/api/attendance", Some(&token), json!({})).await;
+    assert_eq!(refused.status, StatusCode::BAD_GATEWAY);
+    assert_eq!(app.portal.hits(Endpoint::AttendanceSummary), hits);
+
+    let ready = app.get("/health/ready", None).await;
+    assert_eq!(ready.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(ready.json()["circuit"], "open");
+    assert_eq!(app.get("/health/live", None).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn admin_inspects_and_invalidates_the_cache() {
+    const ADMIN_TOKEN: &str = "admin-token-for-the-e2e-tests-0123456789";
+    let app = TestApp::start_with(|config| config.admin_token = Some(ADMIN_TOKEN.to_string())).await;
+    let token = app.portal.issue_token();
+
+    assert_eq!(app.get("/admin/cache", None).await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/admin/cache", Some(&token)).await.status, StatusCode::UNAUTHORIZED);
+
+    app.post("/api/attendance", Some(&token), json!({})).await;
+    app.get("/api/all-attendance", Some(&token)).await;
+    app.get("/api/quiz", Some(&token)).await;
+
+    let stats = app.get("/admin/cache", Some(ADMIN_TOKEN)).await;
+    assert_eq!(stats.status, StatusCode::OK, "{}", stats.text());
+    let stats = stats.json();
+    for cache in ["attendance", "quiz", "all-attendance"] {
+        assert_eq!(stats["caches"][cache]["entries"], 1, "{}", cache);
+        assert_eq!(stats["caches"][cache]["misses"], 1, "{}", cache);
+    }
+    // The calendar's lecture list is filled by the same portal fetch as all-attendance
+    assert_eq!(stats["caches"]["lectures"]["entries"], 1);
+    assert_eq!(app.get("/admin/pending", Some(ADMIN_TOKEN)).await.json()["pending"], json!([]));
+
+    // The quiz entry is found through the token of the student's attendance
+    let uri = format!("/admin/cache/students/{}", USERNAME);
+    let removed = app.send(Method::DELETE, &uri, Some(ADMIN_TOKEN), None).await;
+    assert_eq!(removed.json()["removed"], 4);
+    let refetched = app.post("/api/attendance", Some(&token), json!({})).await;
+    assert_eq!(refetched.header(header::HeaderName::from_static("x-cache")), "MISS");
+
+    let unknown = app.send(Method::DELETE, "/admin/cache/sessions", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(unknown.status, StatusCode::NOT_FOUND);
+    let cleared = app.send(Method::DELETE, "/admin/cache/attendance", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(cleared.json()["removed"], 1);
+    app.get("/api/quiz", Some(&token)).await;
+    let flushed = app.send(Method::DELETE, "/admin/cache", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(flushed.json()["removed"], 1);
+}
+
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn replicas_share_the_redis_cache() {
+    use aims_backend::server::BackendKind;
+    use common::fake_redis::FakeRedis;
+    const ADMIN_TOKEN: &str = "admin-token-for-the-e2e-tests-0123456789";
+    let redis = FakeRedis::start(None).await;
+    let start_replica = || {
+        TestApp::start_with(|config| {
+            config.cache_backend = BackendKind::Redis;
+            config.redis_url = Some(redis.url());
+            config.admin_token = Some(ADMIN_TOKEN.to_string());
+        })
+    };
+    let (first, second) = (start_replica().await, start_replica().await);
+    // Both mock portals issue the same first token, so the replicas share cache keys
+    let (token, same_token) = (first.portal.issue_token(), second.portal.issue_token());
+    assert_eq!(token, same_token);
+
+    let fetched = first.post("/api/attendance", Some(&token), json!({})).await;
+    assert_eq!(fetched.header(header::HeaderName::from_static("x-cache")), "MISS");
+    assert!(redis.keys().iter().any(|key| key.starts_with("aims:attendance:")));
+
+    let shared = second.post("/api/attendance", Some(&token), json!({})).await;
+    assert_eq!(shared.status, StatusCode::OK, "{}", shared.text());
+    assert_eq!(shared.header(header::HeaderName::from_static("x-cache")), "HIT");
+    assert_eq!(second.portal.hits(Endpoint::AttendanceSummary), 0);
+
+    let flushed = second.send(Method::DELETE, "/admin/cache", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(flushed.json()["removed"], 1);
+    let refetched = first.post("/api/attendance", Some(&token), json!({})).await;
+    assert_eq!(refetched.header(header::HeaderName::from_static("x-cache")), "MISS");
+}
+
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn redis_backend_stores_lists_and_locks() {
+    use aims_backend::server::{build_cache_backend, BackendKind};
+    use common::fake_redis::FakeRedis;
+    let redis = FakeRedis::start(Some("s3cret")).await;
+    let connect = |url: String| {
+        let mut config = Config::defaults();
+        config.cache_backend = BackendKind::Redis;
+        config.redis_url = Some(url);
+        build_cache_backend(&config).unwrap()
+    };
+    let backend = connect(redis.url_with_password("s3cret"));
+
+    backend.set("quiz:quiz_a", b"{}", Duration::from_secs(60)).await.unwrap();
+    backend.set("quiz:quiz_b", b"[]", Duration::from_millis(20)).await.unwrap();
+    backend.set("attendance:attendance_a", b"{}", Duration::from_secs(60)).await.unwrap();
+    assert_eq!(backend.get("quiz:quiz_a").await.unwrap().as_deref(), Some(&b"{}"[..]));
+    assert!(redis.keys().contains(&"aims:quiz:quiz_a".to_string()));
+
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    assert_eq!(backend.get("quiz:quiz_b").await.unwrap(), None);
+    assert_eq!(backend.keys("quiz:").await.unwrap(), vec!["quiz:quiz_a".to_string()]);
+    assert!(backend.delete("quiz:quiz_a").await.unwrap());
+    assert!(!backend.delete("quiz:quiz_a").await.unwrap());
+
+    assert!(backend.try_lock("lock:quiz_a", Duration::from_secs(30)).await.unwrap());
+    assert!(!backend.try_lock("lock:quiz_a", Duration::from_secs(30)).await.unwrap());
+    backend.unlock("lock:quiz_a").await.unwrap();
+    assert!(backend.try_lock("lock:quiz_a", Duration::from_secs(30)).await.unwrap());
+
+    // A holder whose lock lapsed cannot release the one another replica took since
+    let replica = connect(redis.url_with_password("s3cret"));
+    assert!(backend.try_lock("lock:quiz_b", Duration::from_millis(20)).await.unwrap());
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    assert!(replica.try_lock("lock:quiz_b", Duration::from_secs(30)).await.unwrap());
+    backend.unlock("lock:quiz_b").await.unwrap();
+    assert!(redis.keys().contains(&"aims:lock:quiz_b".to_string()));
+    replica.unlock("lock:quiz_b").await.unwrap();
+    assert!(!redis.keys().contains(&"aims:lock:quiz_b".to_string()));
+
+    let refused = connect(redis.url_with_password("wrong"));
+    let error = refused.get("quiz:quiz_a").await.unwrap_err().to_string();
+    assert!(error.to_lowercase().contains("authentication failed"), "{}", error);
+}
+
+#[tokio::test]
+async fn admin_api_is_off_without_a_token() {
+    let app = TestApp::start().await;
+    assert_eq!(app.get("/admin/cache", None).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn slow_portal_responses_still_complete() {
+    let app = TestApp::start().await;
+    let token = app.portal.issue_token();
+    app.portal.inject(Endpoint::Cards, Fault::Latency(Duration::from_millis(300)));
+
+    let started = Instant::now();
+    let response = app.get("/api/all-attendance", Some(&token)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(response.json()["total_present_all_subjects"], 5);
+}
+
+#[tokio::test]
+async fn expired_session_token_is_renewed_for_opted_in_sessions() {
+    let app = TestApp::start().await;
+    let cookie = app.login(true).await;
+
+    app.portal.expire_tokens();
+    let renewed = app.get("/api/quiz", Some(&cookie)).await;
+    assert_eq!(renewed.status, StatusCode::OK, "{}", renewed.text());
+    assert_eq!(app.portal.hits(Endpoint::Authenticate), 2);
+
+    let plain = app.login(false).await;
+    app.portal.expire_tokens();
+    let rejected = app.get("/api/all-attendance", Some(&plain)).await;
+    assert_eq!(rejected.status, StatusCode::UNAUTHORIZED);
+}
+
+// Set UPDATE_CASSETTES=1 to re-record testdata/cassettes/all_attendance from the mock portal
+#[tokio::test]
+async fn all_attendance_replays_from_cassettes() {
+    let cassettes = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/cassettes/all_attendance");
+
+    if std::env::var_os("UPDATE_CASSETTES").is_some() {
+        let _ = std::fs::remove_dir_all(cassettes);
+        let app = TestApp::start_with(|config| {
+            config.upstream_mode = UpstreamMode::Record;
+            config.cassette_dir = cassettes.to_string();
+        })
+        .await;
+        let token = app.portal.issue_token();
+        let recorded = app.get("/api/all-attendance", Some(&token)).await;
+        assert_eq!(recorded.status, StatusCode::OK, "{}", recorded.text());
+        for entry in std::fs::read_dir(cassettes).unwrap() {
+            let stored = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(!stored.contains(&token), "cassette leaked the portal token");
+        }
+    }
+
+    let app = TestApp::start_with(|config| {
+        config.upstream_mode = UpstreamMode::Replay;
+        config.cassette_dir = cassettes.to_string();
+    })
+    .await;
+
+    let response = app.get("/api/all-attendance", Some("any-token")).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let body = response.json();
+    assert_eq!(body["student_id"], USERNAME);
+    assert_eq!(body["total_present_all_subjects"], 5);
+    assert_eq!(body["total_absent_all_subjects"], 1);
+    assert_eq!(body["subjects"]["Database Management Systems"]["total_present"], 3);
+    assert_eq!(body["subjects"]["Database Management Systems"]["daily"].as_array().unwrap().len(), 3);
+    assert_eq!(body["subjects"]["Operating Systems"]["total_absent"], 0);
+    assert_eq!(body["course_code_map"]["BCS502"], "Operating Systems");
+
+    // Nothing reached the portal
+    for endpoint in [Endpoint::AttendanceSummary, Endpoint::Subjects, Endpoint::Cards] {
+        assert_eq!(app.portal.hits(endpoint), 0);
+    }
+
+    let unrecorded = app.get("/api/quiz", Some("any-token")).await;
+    assert_eq!(unrecorded.status, StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn client_reads_the_portal_without_the_server() {
+    let portal = MockPortal::start().await;
+    let client = AimsClient::new(&portal.base_url);
+
+    let rejected = client.login(USERNAME, "wrong").await.unwrap_err();
+    assert!(matches!(rejected, AppError::AuthenticationError(_)));
+
+    let login = client.login(USERNAME, PASSWORD).await.unwrap();
+    assert!(login.profile.get("token").is_none() && login.profile["response"].get("token").is_none());
+    let token = login.token.expose();
+
+    let summary = client.attendance_summary(token).await.unwrap();
+    assert_eq!((summary.total_present, summary.total_classes), (5, 6));
+
+    let subjects = client.subjects(token).await.unwrap();
+    assert_eq!(subjects[0].name, "Database Management Systems");
+    let cards = client.subject_attendance(token, &subjects[1], &summary.student_id).await.unwrap();
+    assert_eq!(cards.len(), 2);
+
+    let full = client.full_attendance(token).await.unwrap();
+    assert_eq!(aims_backend::aggregation::summarize(&full).total_present_all_subjects, 5);
+    assert!(!client.quizzes(token).await.unwrap().is_empty());
+}